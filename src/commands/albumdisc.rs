@@ -1,22 +1,26 @@
+use crate::utils::config::load_config;
 use crate::utils::dir;
 use crate::utils::metadata::AurMetadata;
 use crate::utils::tagger::Tagger;
-use crate::utils::types::GlobalOpts;
+use crate::utils::types::{DiscStyle, GlobalOpts};
 use crate::{err_if_empty, verbose};
 use anyhow::anyhow;
 use camino::{Utf8Path, Utf8PathBuf};
 use regex::Regex;
 
+const DISC_DIR_PATTERN: &str = r"^disc_(\d+)";
+
 pub fn run(files: &[Utf8PathBuf], global_opts: &GlobalOpts) -> anyhow::Result<bool> {
     let files = dir::media_files(&dir::pathbuf_set(files));
     err_if_empty!(files);
 
     let mut ret_code = true;
 
-    let rx = Regex::new(r"^disc_(\d+)")?;
+    let style = load_config(&global_opts.config)?.get_disc_style();
+    let rx = Regex::new(DISC_DIR_PATTERN)?;
 
     for file in files {
-        if let Err(e) = tag_file(&file, &rx, global_opts) {
+        if let Err(e) = tag_file(&file, &rx, style, global_opts) {
             eprintln!("Error tagging {file}: {e}");
             ret_code = false;
         }
@@ -25,13 +29,32 @@ pub fn run(files: &[Utf8PathBuf], global_opts: &GlobalOpts) -> anyhow::Result<bo
     Ok(ret_code)
 }
 
-fn tag_file(file: &Utf8Path, rx: &Regex, opts: &GlobalOpts) -> anyhow::Result<bool> {
-    let end_pattern = match disc_number(file, rx)? {
-        Some(number) => format!(" (Disc {})", number),
+// For lint's fix mode.
+pub fn fix_file(file: &Utf8Path, style: DiscStyle, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    tag_file(file, &Regex::new(DISC_DIR_PATTERN)?, style, opts)
+}
+
+fn tag_file(
+    file: &Utf8Path,
+    rx: &Regex,
+    style: DiscStyle,
+    opts: &GlobalOpts,
+) -> anyhow::Result<bool> {
+    let number = match disc_number(file, rx)? {
+        Some(number) => number,
         None => return Err(anyhow!("{} is not in a disc_n directory", file)),
     };
 
     let info = AurMetadata::new(file)?;
+
+    match style {
+        DiscStyle::AlbumSuffix => add_album_suffix(&info, &number, opts),
+        DiscStyle::Tag => set_disc_tag(&info, &number, opts),
+    }
+}
+
+fn add_album_suffix(info: &AurMetadata, number: &str, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let end_pattern = format!(" (Disc {})", number);
     let current_album_name = &info.tags.album;
 
     if current_album_name.ends_with(end_pattern.as_str()) {
@@ -39,13 +62,30 @@ fn tag_file(file: &Utf8Path, rx: &Regex, opts: &GlobalOpts) -> anyhow::Result<bo
         return Ok(false);
     }
 
-    let tagger = Tagger::new(&info)?;
+    let tagger = Tagger::new(info)?;
     tagger.set_album(
         format!("{}{}", current_album_name, end_pattern).as_str(),
         opts.quiet,
     )
 }
 
+// Setting the tag also strips any suffix left over from the old convention, so running this over
+// an album moves it from one style to the other.
+fn set_disc_tag(info: &AurMetadata, number: &str, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let number = number.parse::<u32>()?;
+    let tagger = Tagger::new(info)?;
+    let disc_changed = tagger.set_disc(&number.to_string(), opts.quiet)?;
+
+    let end_pattern = format!(" (Disc {})", number);
+
+    let album_changed = match info.tags.album.strip_suffix(&end_pattern) {
+        Some(album) => tagger.set_album(album, opts.quiet)?,
+        None => false,
+    };
+
+    Ok(disc_changed || album_changed)
+}
+
 fn disc_number(file: &Utf8Path, rx: &Regex) -> anyhow::Result<Option<String>> {
     let path = file.canonicalize_utf8()?;
     let parent = match path.parent() {
@@ -111,15 +151,57 @@ mod test {
         let original_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("Test Album", original_info.tags.album);
-        assert!(tag_file(&file_under_test, &rx, &GlobalOpts::default()).unwrap());
+        assert!(
+            tag_file(
+                &file_under_test,
+                &rx,
+                DiscStyle::AlbumSuffix,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
 
         let new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("Test Album (Disc 3)", new_info.tags.album);
-        assert!(!tag_file(&file_under_test, &rx, &GlobalOpts::default()).unwrap());
+        assert!(
+            !tag_file(
+                &file_under_test,
+                &rx,
+                DiscStyle::AlbumSuffix,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
 
         let new_new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("Test Album (Disc 3)", new_new_info.tags.album);
     }
+
+    #[test]
+    fn test_tag_file_disc_tag() {
+        let rx = regex();
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.child("album/disc_3").create_dir_all().unwrap();
+        let target = tmp.child("album/disc_3");
+        target
+            .copy_from(
+                fixture!("commands/albumdisc/disc_3/"),
+                &["01.artist.song.mp3"],
+            )
+            .unwrap();
+
+        let file_under_test = target.join("01.artist.song.mp3");
+        let opts = GlobalOpts::default();
+
+        assert!(tag_file(&file_under_test, &rx, DiscStyle::AlbumSuffix, &opts).unwrap());
+        assert!(tag_file(&file_under_test, &rx, DiscStyle::Tag, &opts).unwrap());
+
+        let new_info = AurMetadata::new(&file_under_test).unwrap();
+
+        assert_eq!("Test Album", new_info.tags.album);
+        assert_eq!(3, new_info.tags.disc);
+        assert!(!tag_file(&file_under_test, &rx, DiscStyle::Tag, &opts).unwrap());
+    }
 }
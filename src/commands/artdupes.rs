@@ -0,0 +1,160 @@
+use crate::utils::config::{ARTWORK_FILENAME, load_config};
+use crate::utils::dir;
+use crate::utils::image::{Fingerprint, Image};
+use crate::utils::types::GlobalOpts;
+use crate::verbose;
+use anyhow::ensure;
+use camino::{Utf8Path, Utf8PathBuf};
+use rayon::prelude::*;
+
+type Cluster = Vec<Utf8PathBuf>;
+
+// Anything listed in ignore.artdupes is left out entirely, so reissues which rightly share a
+// sleeve can be kept quiet.
+pub fn run(root: &Utf8Path, distance: u32, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    ensure!(root.is_dir(), "{} not found", root);
+    let config = load_config(&opts.config)?;
+    let ignore_list = config.get_artdupes_list();
+
+    let covers: Vec<Utf8PathBuf> = dir::expand_file_list(&[root.to_path_buf()], true)?
+        .into_iter()
+        .filter(|f| f.file_name() == Some(ARTWORK_FILENAME))
+        .filter(|f| !ignore_list.is_some_and(|l| l.is_match(f)))
+        .collect();
+
+    let results: Vec<(Utf8PathBuf, anyhow::Result<Fingerprint>)> = covers
+        .into_par_iter()
+        .map(|f| {
+            let fingerprint = Image::load(&f).and_then(|img| img.fingerprint());
+            (f, fingerprint)
+        })
+        .collect();
+
+    let mut ret_code = true;
+    let mut fingerprints = Vec::new();
+
+    for (file, result) in results {
+        match result {
+            Ok(fingerprint) => {
+                verbose!(opts, "{:016x} {}", fingerprint.hash, file);
+                fingerprints.push((file, fingerprint));
+            }
+            Err(e) => {
+                eprintln!("Error reading {file}: {e}");
+                ret_code = false;
+            }
+        }
+    }
+
+    let clusters = clusters(&fingerprints, distance);
+    clusters
+        .iter()
+        .for_each(|c| println!("{}", format_cluster(c)));
+    Ok(ret_code && clusters.is_empty())
+}
+
+fn format_cluster(cluster: &[Utf8PathBuf]) -> String {
+    let mut ret = cluster[0].to_string();
+    cluster[1..]
+        .iter()
+        .for_each(|c| ret.push_str(&format!("\n  {}", c)));
+    ret.push('\n');
+    ret
+}
+
+// Likeness is transitive here: if A looks like B and B looks like C, all three are reported
+// together. Each cluster is sorted, and so is the list of them.
+fn clusters(fingerprints: &[(Utf8PathBuf, Fingerprint)], distance: u32) -> Vec<Cluster> {
+    let mut parents: Vec<usize> = (0..fingerprints.len()).collect();
+
+    for i in 0..fingerprints.len() {
+        for j in (i + 1)..fingerprints.len() {
+            if fingerprints[i].1.looks_like(&fingerprints[j].1, distance) {
+                let (a, b) = (root_of(&mut parents, i), root_of(&mut parents, j));
+                parents[b] = a;
+            }
+        }
+    }
+
+    let mut groups: Vec<Cluster> = vec![Vec::new(); fingerprints.len()];
+
+    for (i, (file, _)) in fingerprints.iter().enumerate() {
+        let root = root_of(&mut parents, i);
+        groups[root].push(file.clone());
+    }
+
+    let mut ret: Vec<Cluster> = groups.into_iter().filter(|g| g.len() > 1).collect();
+    ret.iter_mut().for_each(|c| c.sort());
+    ret.sort();
+    ret
+}
+
+fn root_of(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+
+    i
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fingerprints(list: &[(&str, u64)]) -> Vec<(Utf8PathBuf, Fingerprint)> {
+        list.iter()
+            .map(|(f, hash)| {
+                (
+                    Utf8PathBuf::from(f),
+                    Fingerprint {
+                        hash: *hash,
+                        colour: [100, 100, 100],
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_clusters() {
+        let input = fingerprints(&[
+            ("/flac/albums/abc/c.album/cover.jpg", 0b1111_0000),
+            ("/flac/albums/abc/a.album/cover.jpg", 0b1111_0001),
+            ("/flac/eps/b.ep/cover.jpg", 0xffff_0000_ffff_0000),
+            ("/flac/albums/abc/b.album/cover.jpg", 0b0111_0011),
+            ("/flac/albums/xyz/z.album/cover.jpg", 0xffff_0000_ffff_0001),
+            ("/flac/albums/xyz/y.album/cover.jpg", 0x1234_5678_9abc_def0),
+        ]);
+
+        assert_eq!(
+            vec![
+                vec![
+                    Utf8PathBuf::from("/flac/albums/abc/a.album/cover.jpg"),
+                    Utf8PathBuf::from("/flac/albums/abc/b.album/cover.jpg"),
+                    Utf8PathBuf::from("/flac/albums/abc/c.album/cover.jpg"),
+                ],
+                vec![
+                    Utf8PathBuf::from("/flac/albums/xyz/z.album/cover.jpg"),
+                    Utf8PathBuf::from("/flac/eps/b.ep/cover.jpg"),
+                ],
+            ],
+            clusters(&input, 2)
+        );
+
+        assert!(clusters(&input, 0).is_empty());
+        assert!(clusters(&[], 6).is_empty());
+    }
+
+    #[test]
+    fn test_format_cluster() {
+        assert_eq!(
+            "/a/cover.jpg\n  /b/cover.jpg\n  /c/cover.jpg\n",
+            format_cluster(&[
+                Utf8PathBuf::from("/a/cover.jpg"),
+                Utf8PathBuf::from("/b/cover.jpg"),
+                Utf8PathBuf::from("/c/cover.jpg"),
+            ])
+        );
+    }
+}
@@ -1,16 +1,12 @@
-use crate::utils::config::{ARTWORK_FILENAME, ARTWORK_QUALITY, MAX_ARTWORK_SIZE};
+use crate::utils::config::{ARTWORK_FILENAME, ARTWORK_QUALITY, MAX_ARTWORK_SIZE, load_config};
 use crate::utils::dir;
 use crate::utils::helpers::MaybeProgress;
+use crate::utils::image::{self, Image};
 use crate::utils::types::GlobalOpts;
 use crate::{err_if_empty, verbose};
-use anyhow::{Context, anyhow};
 use camino::{Utf8Path, Utf8PathBuf};
 use imagesize;
 use indicatif::ProgressBar;
-use jpeg_decoder::Decoder;
-use jpeg_encoder::{ColorType, Encoder};
-use resize::{Pixel::RGB8, Type::Lanczos3};
-use rgb::{ComponentBytes, FromSlice, RGB};
 use std::fs;
 use std::os::unix::fs::symlink;
 
@@ -22,6 +18,7 @@ pub fn run(
 ) -> anyhow::Result<bool> {
     let dirs = dir::expand_dir_list(dirlist, recurse);
     err_if_empty!(dirs);
+    let tolerance = load_config(&opts.config)?.get_square_tolerance();
 
     let mut ret_code = true;
 
@@ -33,7 +30,7 @@ pub fn run(
 
     for dir in dirs {
         pb.inc(1);
-        if check_artwork(&dir, &linkdir, &pb, opts).is_err() {
+        if check_artwork(&dir, &linkdir, tolerance, &pb, opts).is_err() {
             ret_code = false;
         }
     }
@@ -45,6 +42,7 @@ pub fn run(
 fn check_artwork(
     dir: &Utf8Path,
     linkdir: &Utf8Path,
+    tolerance: f64,
     pb: &MaybeProgress,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
@@ -53,11 +51,17 @@ fn check_artwork(
     let mut changes: Vec<bool> = Vec::new();
 
     if !expected_artwork.exists() {
-        changes.push(rename(dir, &expected_artwork, pb, opts)?);
+        changes.push(rename(dir, &expected_artwork, tolerance, pb, opts)?);
     }
 
     if expected_artwork.exists() {
-        changes.push(resize_or_link(&expected_artwork, linkdir, pb, opts)?);
+        changes.push(resize_or_link(
+            &expected_artwork,
+            linkdir,
+            tolerance,
+            pb,
+            opts,
+        )?);
     }
 
     if changes.iter().any(|c| *c) {
@@ -70,124 +74,106 @@ fn check_artwork(
 fn rename(
     dir: &Utf8Path,
     front: &Utf8Path,
+    tolerance: f64,
     pb: &MaybeProgress,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
     let mut ret = false;
 
     // Yes, this will flatten multiple files into one. That's fine.
-    for file in jpgs_in(dir)? {
-        if file != front {
+    for file in artwork_in(dir)? {
+        if file == front {
+            continue;
+        }
+
+        if image::is_jpeg(&file) {
             if !opts.quiet {
                 pb.println(&format!("Rename: {file} -> {ARTWORK_FILENAME}"));
             }
             if !opts.noop {
                 fs::rename(file, front)?;
             }
-            ret = true;
+        } else {
+            if !opts.quiet {
+                pb.println(&format!("Convert: {file} -> {ARTWORK_FILENAME}"));
+            }
+            if !opts.noop {
+                let img = fix_image(Image::load(&file)?, tolerance)?;
+                fs::write(front, img.to_jpeg(ARTWORK_QUALITY)?)?;
+                fs::remove_file(file)?;
+            }
         }
+
+        ret = true;
     }
 
     Ok(ret)
 }
 
 // It's not our job to flag up problems, only to fix what we can. Lintdir will point out what
-// we can't fix.
+// we can't fix. Art which is only a little out of square is squared up: anything further out is
+// most likely a wide digipak or similar, and needs a human.
 fn resize_or_link(
     file: &Utf8Path,
     linkdir: &Utf8Path,
+    tolerance: f64,
     pb: &MaybeProgress,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
     let img_size = imagesize::size(file)?;
 
-    if img_size.width != img_size.height {
+    if !image::is_nearly_square(img_size.width, img_size.height, tolerance) {
         return symlink_art(file, linkdir, pb, opts);
     }
 
-    if img_size.width <= MAX_ARTWORK_SIZE {
+    let needs_squaring = img_size.width != img_size.height;
+    let too_big = img_size.width.max(img_size.height) > MAX_ARTWORK_SIZE;
+    let needs_conversion = image::jpeg_needs_conversion(file)?;
+
+    if !needs_squaring && !too_big && !needs_conversion {
         return Ok(false);
     }
 
     if !opts.quiet {
-        pb.println(&format!(
-            "Resize: {} -> {s}x{s}",
-            file,
-            s = MAX_ARTWORK_SIZE
-        ));
+        if needs_conversion {
+            pb.println(&format!("Convert: {file} -> RGB JPEG"));
+        }
+        if needs_squaring {
+            pb.println(&format!(
+                "Square: {} ({}x{})",
+                file, img_size.width, img_size.height
+            ));
+        }
+        if too_big {
+            pb.println(&format!(
+                "Resize: {} -> {s}x{s}",
+                file,
+                s = MAX_ARTWORK_SIZE
+            ));
+        }
     }
 
     if !opts.noop {
-        resize_art(file, img_size.width)?;
-        // let new_img = img.resize_exact(MAX_ARTWORK_SIZE, MAX_ARTWORK_SIZE, Lanczos3);
-        // new_img.save(file)?;
+        let img = fix_image(Image::load(file)?, tolerance)?;
+        fs::write(file, img.to_jpeg(ARTWORK_QUALITY)?)?;
     }
 
     Ok(true)
 }
 
-// Resizes a square image
-fn resize_art(path: &Utf8Path, orig_size: usize) -> anyhow::Result<()> {
-    let bytes = fs::read(path)?;
-    let mut decoder = Decoder::new(bytes.as_slice());
-    let pixels = decoder.decode()?;
-    let info = decoder.info().context("failed to get JPEG info")?;
-
-    let color_type = match info.pixel_format {
-        jpeg_decoder::PixelFormat::RGB24 => ColorType::Rgb,
-        jpeg_decoder::PixelFormat::L8 => ColorType::Luma,
-        fmt => return Err(anyhow!("unsupported pixel format: {fmt:?}")),
+// Squares anything close enough to square, then shrinks it if it's too big.
+pub fn fix_image(img: Image, tolerance: f64) -> anyhow::Result<Image> {
+    let img = if image::is_nearly_square(img.width, img.height, tolerance) {
+        img.square()
+    } else {
+        img
     };
 
-    let mut output = Vec::new();
-    let encoder = Encoder::new(&mut output, ARTWORK_QUALITY);
-
-    match color_type {
-        ColorType::Rgb => {
-            let src: &[RGB<u8>] = pixels.as_rgb();
-            let mut dst = vec![RGB::new(0u8, 0, 0); MAX_ARTWORK_SIZE * MAX_ARTWORK_SIZE];
-            resize::new(
-                orig_size,
-                orig_size,
-                MAX_ARTWORK_SIZE,
-                MAX_ARTWORK_SIZE,
-                RGB8,
-                Lanczos3,
-            )?
-            .resize(src, &mut dst)?;
-            encoder.encode(
-                dst.as_bytes(),
-                MAX_ARTWORK_SIZE as u16,
-                MAX_ARTWORK_SIZE as u16,
-                ColorType::Rgb,
-            )?;
-        }
-        ColorType::Luma => {
-            use resize::Pixel::Gray8;
-            use rgb::alt::Gray;
-            let src: &[Gray<u8>] = bytemuck::cast_slice(&pixels);
-            let mut dst = vec![Gray(0u8); MAX_ARTWORK_SIZE * MAX_ARTWORK_SIZE];
-            resize::new(
-                orig_size,
-                orig_size,
-                MAX_ARTWORK_SIZE,
-                MAX_ARTWORK_SIZE,
-                Gray8,
-                Lanczos3,
-            )?
-            .resize(src, &mut dst)?;
-            encoder.encode(
-                bytemuck::cast_slice(&dst),
-                MAX_ARTWORK_SIZE as u16,
-                MAX_ARTWORK_SIZE as u16,
-                ColorType::Luma,
-            )?;
-        }
-        _ => unreachable!(),
+    if img.is_square() && img.width > MAX_ARTWORK_SIZE {
+        img.resize(MAX_ARTWORK_SIZE, MAX_ARTWORK_SIZE)
+    } else {
+        Ok(img)
     }
-
-    fs::write(path, &output)?;
-    Ok(())
 }
 
 fn target_filename(file: &Utf8Path) -> String {
@@ -228,24 +214,23 @@ fn symlink_art(
     Ok(true)
 }
 
-fn jpgs_in(dir: &Utf8Path) -> anyhow::Result<Vec<Utf8PathBuf>> {
-    let files = dir
+fn artwork_in(dir: &Utf8Path) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let mut files: Vec<Utf8PathBuf> = dir
         .read_dir_utf8()?
         .filter_map(Result::ok)
         .map(|e| e.path().to_path_buf())
-        .filter(|p| {
-            p.is_file()
-                && matches!(p.extension(), Some(ext) if ext.eq_ignore_ascii_case("jpg") 
-                || ext.eq_ignore_ascii_case("jpeg"))
-        })
+        .filter(|p| p.is_file() && image::is_artwork(p))
         .collect();
 
+    // Anything converted is overwritten by a JPEG, which is more likely to be what we want.
+    files.sort_by_key(|f| image::is_jpeg(f));
     Ok(files)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::config::DEFAULT_SQUARE_TOLERANCE;
     use camino_tempfile_ext::prelude::*;
     use snltest::fixture;
 
@@ -255,6 +240,7 @@ mod test {
             !resize_or_link(
                 &fixture!("commands/artfix/tester.good_art/cover.jpg"),
                 &Utf8PathBuf::from("/tmp"),
+                DEFAULT_SQUARE_TOLERANCE,
                 &MaybeProgress::Direct,
                 &GlobalOpts::default()
             )
@@ -279,6 +265,7 @@ mod test {
             resize_or_link(
                 &file_under_test,
                 &linkdir,
+                DEFAULT_SQUARE_TOLERANCE,
                 &MaybeProgress::Direct,
                 &GlobalOpts::default()
             )
@@ -301,6 +288,7 @@ mod test {
             resize_or_link(
                 &source_file,
                 target_dir.path(),
+                DEFAULT_SQUARE_TOLERANCE,
                 &MaybeProgress::Direct,
                 &GlobalOpts::default()
             )
@@ -326,6 +314,7 @@ mod test {
             rename(
                 &dir_under_test,
                 &expected_artwork,
+                DEFAULT_SQUARE_TOLERANCE,
                 &MaybeProgress::Direct,
                 &GlobalOpts::default()
             )
@@ -337,6 +326,127 @@ mod test {
             !rename(
                 &dir_under_test,
                 &expected_artwork,
+                DEFAULT_SQUARE_TOLERANCE,
+                &MaybeProgress::Direct,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rename_png() {
+        let dir_name = "tester.png_art";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/artfix"), &["tester.png_art/**/*"])
+            .unwrap();
+
+        let dir_under_test = tmp.path().join(dir_name);
+        let expected_artwork = dir_under_test.join(ARTWORK_FILENAME);
+
+        assert!(
+            rename(
+                &dir_under_test,
+                &expected_artwork,
+                DEFAULT_SQUARE_TOLERANCE,
+                &MaybeProgress::Direct,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+        assert!(!dir_under_test.join("folder.png").exists());
+
+        let after = imagesize::size(&expected_artwork).unwrap();
+        assert_eq!(after.width, MAX_ARTWORK_SIZE);
+        assert_eq!(after.height, MAX_ARTWORK_SIZE);
+        assert!(!image::jpeg_needs_conversion(&expected_artwork).unwrap());
+    }
+
+    #[test]
+    fn test_convert_cmyk() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file_under_test = tmp.path().join(ARTWORK_FILENAME);
+        let mut cmyk = Vec::new();
+        jpeg_encoder::Encoder::new(&mut cmyk, 90)
+            .encode(
+                &[0, 255, 255, 0].repeat(300 * 300),
+                300,
+                300,
+                jpeg_encoder::ColorType::Cmyk,
+            )
+            .unwrap();
+        fs::write(&file_under_test, cmyk).unwrap();
+
+        assert!(image::jpeg_needs_conversion(&file_under_test).unwrap());
+        assert!(
+            resize_or_link(
+                &file_under_test,
+                tmp.path(),
+                DEFAULT_SQUARE_TOLERANCE,
+                &MaybeProgress::Direct,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+        assert!(!image::jpeg_needs_conversion(&file_under_test).unwrap());
+        assert_eq!(300, imagesize::size(&file_under_test).unwrap().width);
+    }
+
+    #[test]
+    fn test_convert_png_called_jpg() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file_under_test = tmp.path().join(ARTWORK_FILENAME);
+        fs::copy(
+            fixture!("commands/artfix/tester.png_art/folder.png"),
+            &file_under_test,
+        )
+        .unwrap();
+
+        assert!(
+            resize_or_link(
+                &file_under_test,
+                tmp.path(),
+                DEFAULT_SQUARE_TOLERANCE,
+                &MaybeProgress::Direct,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+        assert!(!image::jpeg_needs_conversion(&file_under_test).unwrap());
+        assert!(!file_under_test.is_symlink());
+    }
+
+    #[test]
+    fn test_square() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file_under_test = tmp.path().join(ARTWORK_FILENAME);
+        let mut pixels = [200, 30, 30].repeat(400 * 400);
+        pixels.extend([255, 255, 255].repeat(400 * 6));
+        let mut jpeg = Vec::new();
+        jpeg_encoder::Encoder::new(&mut jpeg, 90)
+            .encode(&pixels, 400, 406, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        fs::write(&file_under_test, jpeg).unwrap();
+
+        assert!(
+            resize_or_link(
+                &file_under_test,
+                tmp.path(),
+                DEFAULT_SQUARE_TOLERANCE,
+                &MaybeProgress::Direct,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+
+        let after = imagesize::size(&file_under_test).unwrap();
+        assert_eq!((400, 400), (after.width, after.height));
+        assert!(!file_under_test.is_symlink());
+        assert!(
+            !resize_or_link(
+                &file_under_test,
+                tmp.path(),
+                DEFAULT_SQUARE_TOLERANCE,
                 &MaybeProgress::Direct,
                 &GlobalOpts::default()
             )
@@ -0,0 +1,95 @@
+use crate::err_if_empty;
+use crate::utils::authenticity::Authenticity;
+use crate::utils::dir;
+use crate::utils::types::GlobalOpts;
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use rayon::prelude::*;
+use std::collections::BTreeSet;
+
+// Every file has to be decoded in full, so they are done in parallel, and reported in order once
+// they're all finished.
+pub fn run(files: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let files: Vec<Utf8PathBuf> = dir::media_files(&dir::expand_file_list(files, recurse)?)
+        .into_iter()
+        .filter(|f| f.extension() == Some("flac"))
+        .collect();
+    err_if_empty!(files);
+
+    let results: Vec<_> = files
+        .par_iter()
+        .map(|f| Authenticity::from_path(f))
+        .collect();
+
+    let mut ret_code = true;
+
+    for (file, result) in files.iter().zip(results) {
+        match result {
+            Ok(result) => {
+                if !report(file, &result, opts) {
+                    ret_code = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("Error checking {file}: {e}");
+                ret_code = false;
+            }
+        }
+    }
+
+    Ok(ret_code)
+}
+
+// Returns false if the file looks like something it isn't. Files which look fine are only
+// mentioned if we aren't being quiet.
+fn report(file: &Utf8Path, result: &Authenticity, opts: &GlobalOpts) -> bool {
+    let suspicions = result.suspicions();
+
+    if suspicions.is_empty() && opts.quiet {
+        return true;
+    }
+
+    println!("{}", file.to_string().bold());
+    println!("  {}", result.summary());
+
+    for suspicion in &suspicions {
+        println!("  {}", suspicion.message());
+    }
+
+    let remedies: BTreeSet<&str> = suspicions.iter().filter_map(|s| s.remedy()).collect();
+
+    for remedy in remedies {
+        println!("  suggest: {} {}", remedy, file);
+    }
+
+    println!();
+    suspicions.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let file = Utf8Path::new("/tmp/01.artist.song.flac");
+        let opts = GlobalOpts::default();
+
+        let genuine = Authenticity {
+            sample_rate: 44100,
+            bit_depth: 16,
+            effective_bit_depth: 16,
+            cutoff: None,
+        };
+
+        let padded = Authenticity {
+            sample_rate: 96000,
+            bit_depth: 24,
+            effective_bit_depth: 16,
+            cutoff: Some(22050),
+        };
+
+        assert!(report(file, &genuine, &opts));
+        assert!(!report(file, &padded, &opts));
+    }
+}
@@ -1,26 +1,30 @@
+use crate::utils::config::load_config;
 use crate::utils::dir;
-use crate::utils::external::find_binary;
+use crate::utils::external::{find_binary, run_pipeline};
 use crate::utils::metadata::AurMetadata;
+use crate::utils::profile::Profile;
 use crate::utils::string::ReplaceLast;
 use crate::utils::types::GlobalOpts;
 use crate::{err_if_empty, verbose};
 use anyhow::ensure;
 use camino::{Utf8Path, Utf8PathBuf};
 use std::fs::rename;
-use std::process::Command;
 
 pub fn run(
     files: &[Utf8PathBuf],
     leave_originals: bool,
+    profile: &str,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
-    let ffmpeg = find_binary("ffmpeg")?;
+    let profile = load_config(&opts.config)?.get_profile(profile)?;
+    profile.ensure_makes("flac")?;
+    let encoder = find_binary(profile.encoder.binary())?;
     let mut ret_code = true;
     let files = dir::media_files(&dir::pathbuf_set(files));
     err_if_empty!(files);
 
     for file in files {
-        if let Err(e) = reencode_file(&file, leave_originals, &ffmpeg, opts) {
+        if let Err(e) = reencode_file(&file, leave_originals, &profile, &encoder, opts) {
             eprintln!("Error reencoding {file}: {e}");
             ret_code = false;
         }
@@ -32,7 +36,8 @@ pub fn run(
 fn reencode_file(
     file: &Utf8Path,
     leave_original: bool,
-    ffmpeg: &Utf8Path,
+    profile: &Profile,
+    encoder: &Utf8Path,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
     let info = AurMetadata::new(file)?;
@@ -45,20 +50,13 @@ fn reencode_file(
     }
 
     let work_dir = file.parent().unwrap();
-    let output_file = work_dir.join(cdq_name(&info.filename));
+    let output_file = work_dir.join(cdq_name(&info.filename, profile.suffix()));
 
     verbose!(opts, "{} -> {}", file, output_file);
 
-    Command::new(ffmpeg)
-        .arg("-hide_banner")
-        .arg("-loglevel")
-        .arg("error")
-        .arg("-i")
-        .arg(file)
-        .arg("-af")
-        .arg("aresample=out_sample_fmt=s16:out_sample_rate=44100")
-        .arg(&output_file)
-        .output()?;
+    run_pipeline(&output_file, |tmp| {
+        vec![profile.command(encoder, &[], file, tmp)]
+    })?;
 
     if leave_original {
         Ok(true)
@@ -68,8 +66,8 @@ fn reencode_file(
     }
 }
 
-fn cdq_name(original_name: &str) -> String {
-    original_name.replace_last(".flac", "-cdq.flac")
+fn cdq_name(original_name: &str, suffix: &str) -> String {
+    original_name.replace_last(".flac", &format!("-{}.flac", suffix))
 }
 
 #[cfg(test)]
@@ -80,12 +78,16 @@ mod test {
 
     #[test]
     fn test_cdq_name() {
-        assert_eq!("01.artist.song-cdq.flac", cdq_name("01.artist.song.flac"));
+        assert_eq!(
+            "01.artist.song-cdq.flac",
+            cdq_name("01.artist.song.flac", "cdq")
+        );
     }
 
     #[test]
     fn test_cdq_reencode_overwrite() {
         let ffmpeg = find_binary("ffmpeg").unwrap();
+        let profile = Profile::builtin("cdq").unwrap();
         let leave_original = false;
         let file_name = "01.tester.hi-res.flac";
         let tmp = Utf8TempDir::new().unwrap();
@@ -96,18 +98,37 @@ mod test {
         let original_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("24-bit/96000Hz", original_info.quality.formatted);
-        assert!(reencode_file(&file_under_test, leave_original, &ffmpeg, &GlobalOpts::default()).unwrap());
+        assert!(
+            reencode_file(
+                &file_under_test,
+                leave_original,
+                &profile,
+                &ffmpeg,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
 
         let new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("16-bit/44100Hz", new_info.quality.formatted);
-        assert!(!reencode_file(&file_under_test, leave_original, &ffmpeg, &GlobalOpts::default()).unwrap());
+        assert!(
+            !reencode_file(
+                &file_under_test,
+                leave_original,
+                &profile,
+                &ffmpeg,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
         assert!(!cdq_file.exists());
     }
 
     #[test]
     fn test_cdq_reencode_leave_original() {
         let ffmpeg = find_binary("ffmpeg").unwrap();
+        let profile = Profile::builtin("cdq").unwrap();
         let leave_original = true;
         let file_name = "01.tester.hi-res.flac";
         let tmp = Utf8TempDir::new().unwrap();
@@ -118,7 +139,16 @@ mod test {
         let original_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!("24-bit/96000Hz", original_info.quality.formatted);
-        assert!(reencode_file(&file_under_test, leave_original, &ffmpeg, &GlobalOpts::default()).unwrap());
+        assert!(
+            reencode_file(
+                &file_under_test,
+                leave_original,
+                &profile,
+                &ffmpeg,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
 
         let new_original_info = AurMetadata::new(&file_under_test).unwrap();
 
@@ -133,12 +163,22 @@ mod test {
     #[test]
     fn test_cdq_mp3() {
         let ffmpeg = find_binary("ffmpeg").unwrap();
+        let profile = Profile::builtin("cdq").unwrap();
         let file_name = "02.tester.not_a_flac.mp3";
         let tmp = Utf8TempDir::new().unwrap();
         tmp.copy_from(fixture!("commands/cdq"), &[file_name])
             .unwrap();
         let file_under_test = tmp.path().join(file_name);
 
-        assert!(reencode_file(&file_under_test, true, &ffmpeg, &GlobalOpts::default()).is_err());
+        assert!(
+            reencode_file(
+                &file_under_test,
+                true,
+                &profile,
+                &ffmpeg,
+                &GlobalOpts::default()
+            )
+            .is_err()
+        );
     }
 }
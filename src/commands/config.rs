@@ -0,0 +1,109 @@
+use crate::utils::config::{Config, load_config};
+use crate::utils::dir;
+use crate::utils::types::GlobalOpts;
+use camino::{Utf8Path, Utf8PathBuf};
+
+// The path is tested as given, so give it the way the command using the list sees it. wantflac,
+// for instance, works with paths relative to the top of the hierarchy.
+pub fn test_ignore(path: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let config = load_config(&opts.config)?;
+    let matches = matching_rules(&config, path);
+
+    if matches.is_empty() {
+        println!("{} matches no ignore rule", path);
+        return Ok(false);
+    }
+
+    for (list, rule) in matches {
+        println!("{}: {}", list, rule);
+    }
+
+    Ok(true)
+}
+
+// Rules which have stopped matching anything are most likely written for the old substring
+// matching. Everything under the directory is tried as its full path, its path relative to the
+// directory, and its bare name, which between them are how the commands see things.
+pub fn unused_rules(dir: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let config = load_config(&opts.config)?;
+    let paths = paths_under(dir)?;
+    let mut ret = true;
+
+    for (list, rules) in config.ignore_lists() {
+        for rule in rules.unmatched_rules(&paths) {
+            println!("{}: {} matches nothing under {}", list, rule, dir);
+            ret = false;
+        }
+    }
+
+    Ok(ret)
+}
+
+fn paths_under(dir: &Utf8Path) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let top = &[dir.to_path_buf()];
+    let mut ret = Vec::new();
+
+    for path in dir::expand_dir_list(top, true)
+        .into_iter()
+        .chain(dir::expand_file_list(top, true)?)
+    {
+        if let Ok(relative) = path.strip_prefix(dir) {
+            ret.push(relative.to_path_buf());
+        }
+
+        if let Some(stem) = path.file_stem() {
+            ret.push(Utf8PathBuf::from(stem));
+        }
+
+        ret.push(path);
+    }
+
+    Ok(ret)
+}
+
+fn matching_rules<'a>(config: &'a Config, path: &Utf8Path) -> Vec<(String, &'a str)> {
+    config
+        .ignore_lists()
+        .into_iter()
+        .filter_map(|(name, list)| list.matching_rule(path).map(|rule| (name, rule)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_matching_rules() {
+        let config = load_config(&fixture!("config/test.toml")).unwrap();
+
+        assert_eq!(
+            vec![("ignore.wantflac.top_level".to_owned(), "audiobooks")],
+            matching_rules(
+                &config,
+                Utf8Path::new("audiobooks/albums/abc/artist.album/disc_1")
+            )
+        );
+
+        assert_eq!(
+            vec![(
+                "ignore.wantflac.albums".to_owned(),
+                "albums/abc/artist.album"
+            )],
+            matching_rules(&config, Utf8Path::new("albums/abc/artist.album"))
+        );
+
+        assert!(matching_rules(&config, Utf8Path::new("albums/abc/artist.albums")).is_empty());
+    }
+
+    #[test]
+    fn test_paths_under() {
+        let dir = fixture!("commands/wantflac/mp3");
+        let paths = paths_under(&dir).unwrap();
+
+        assert!(paths.contains(&Utf8PathBuf::from("albums/abc/artist.album_1")));
+        assert!(paths.contains(&dir.join("albums/abc/artist.album_1")));
+        assert!(paths.contains(&Utf8PathBuf::from("artist.tune")));
+    }
+}
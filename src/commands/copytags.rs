@@ -1,4 +1,5 @@
 use crate::utils::dir;
+use crate::utils::loudness::ReplayGain;
 use crate::utils::metadata::AurMetadata;
 use crate::utils::tagger::Tagger;
 use crate::utils::types::{CopytagsOptions, GlobalOpts};
@@ -45,7 +46,16 @@ fn tag_file(file: &Utf8Path, ct_opts: &CopytagsOptions, opts: &GlobalOpts) -> an
     let partner_info = AurMetadata::new(&partner_path)?;
     let partner_tags = &partner_info.tags;
 
-    if file_tags == partner_tags {
+    // Loudness is measured on the FLAC, so it only travels towards the MP3.
+    let gain = match info.filetype.as_str() {
+        "mp3" => ReplayGain::from_rawtags(&partner_info.rawtags),
+        _ => None,
+    };
+
+    let current_gain = ReplayGain::from_rawtags(&info.rawtags).map(|g| g.tags());
+    let gain_matches = gain.as_ref().is_none_or(|g| Some(g.tags()) == current_gain);
+
+    if file_tags == partner_tags && gain_matches {
         verbose!(opts, "Tags already match");
         return Ok(false);
     }
@@ -56,34 +66,17 @@ fn tag_file(file: &Utf8Path, ct_opts: &CopytagsOptions, opts: &GlobalOpts) -> an
         println!("{}", file.to_string().bold());
     }
 
-    let changes = tagger.batch_tag(partner_tags, opts.quiet)?;
+    let mut changes = tagger.batch_tag(partner_tags, opts.quiet)?;
+
+    if let Some(gain) = gain {
+        changes |= tagger.set_loudness(&gain, opts.quiet)?;
+    }
 
     Ok(changes)
 }
 
 fn find_partner(info: &AurMetadata, force: bool) -> anyhow::Result<Option<Utf8PathBuf>> {
-    let filetype = info.filetype.as_str();
-    let filename_str = &info.filename;
-
-    let newtype = match filetype {
-        "mp3" => "flac",
-        "flac" => "mp3",
-        _ => bail!("unknown filetype: {}", filetype),
-    };
-
-    let partner_path = info
-        .path
-        .components()
-        .map(|c| {
-            if c.as_str() == filetype {
-                newtype.to_owned()
-            } else if c.as_str() == filename_str.as_str() {
-                info.filename.replace(filetype, newtype)
-            } else {
-                c.to_string()
-            }
-        })
-        .collect::<Utf8PathBuf>();
+    let partner_path = partner_path(&info.path, &info.filetype)?;
 
     // Unless the user set the force option, we won't offer a partner that is older than file
 
@@ -105,11 +98,82 @@ fn find_partner(info: &AurMetadata, force: bool) -> anyhow::Result<Option<Utf8Pa
     }
 }
 
+// Where the file's twin in the other hierarchy ought to be, whether or not it exists.
+pub fn partner_path(path: &Utf8Path, filetype: &str) -> anyhow::Result<Utf8PathBuf> {
+    let newtype = match filetype {
+        "mp3" => "flac",
+        "flac" => "mp3",
+        _ => bail!("unknown filetype: {}", filetype),
+    };
+
+    let filename = path.file_name().unwrap_or_default();
+
+    Ok(path
+        .components()
+        .map(|c| {
+            if c.as_str() == filetype {
+                newtype.to_owned()
+            } else if c.as_str() == filename {
+                filename.replace(filetype, newtype)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect::<Utf8PathBuf>())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use camino_tempfile_ext::prelude::*;
     use snltest::fixture;
 
+    #[test]
+    fn test_tag_file_copies_loudness() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.child("flac").create_dir_all().unwrap();
+        tmp.child("mp3").create_dir_all().unwrap();
+        tmp.child("flac")
+            .copy_from(fixture!("commands/copytags/flac"), &["01.artist.song.flac"])
+            .unwrap();
+        tmp.child("mp3")
+            .copy_from(fixture!("commands/copytags/mp3"), &["01.artist.song.mp3"])
+            .unwrap();
+
+        let flac = tmp.path().join("flac/01.artist.song.flac");
+        let mp3 = tmp.path().join("mp3/01.artist.song.mp3");
+        let gain = ReplayGain {
+            track_gain: -3.5,
+            track_peak: 0.5,
+            album_gain: Some(-4.25),
+            album_peak: Some(0.75),
+        };
+
+        let flac_info = AurMetadata::new(&flac).unwrap();
+        assert!(
+            Tagger::new(&flac_info)
+                .unwrap()
+                .set_loudness(&gain, true)
+                .unwrap()
+        );
+
+        let ct_opts = CopytagsOptions {
+            force: true,
+            ..Default::default()
+        };
+
+        assert!(tag_file(&mp3, &ct_opts, &GlobalOpts::default()).unwrap());
+
+        let mp3_info = AurMetadata::new(&mp3).unwrap();
+        assert_eq!(Some(gain), ReplayGain::from_rawtags(&mp3_info.rawtags));
+        assert!(
+            mp3_info
+                .rawtags
+                .contains(&("replaygain_album_gain".to_owned(), "-4.25 dB".to_owned()))
+        );
+        assert!(!tag_file(&mp3, &ct_opts, &GlobalOpts::default()).unwrap());
+    }
+
     #[test]
     fn test_find_partner() {
         assert_eq!(
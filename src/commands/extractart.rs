@@ -0,0 +1,178 @@
+use crate::commands::artfix::fix_image;
+use crate::utils::config::{ARTWORK_FILENAME, ARTWORK_QUALITY, load_config};
+use crate::utils::dir;
+use crate::utils::formats::{self, EmbeddedPicture, PictureKind};
+use crate::utils::image::Image;
+use crate::utils::types::GlobalOpts;
+use crate::{err_if_empty, verbose};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs;
+
+type FoundPicture = (Utf8PathBuf, EmbeddedPicture);
+
+pub fn run(dirlist: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let dirs = dir::expand_dir_list(dirlist, recurse);
+    err_if_empty!(dirs);
+    let tolerance = load_config(&opts.config)?.get_square_tolerance();
+    let mut ret_code = true;
+
+    for dir in dirs {
+        if let Err(e) = extract_artwork(&dir, tolerance, opts) {
+            eprintln!("Error extracting artwork in {dir}: {e}");
+            ret_code = false;
+        }
+    }
+
+    Ok(ret_code)
+}
+
+// True if it wrote a cover. An existing cover is always left alone: fixing that is artfix's job.
+// The picture goes through the same squaring and resizing as artfix would give it.
+pub fn extract_artwork(dir: &Utf8Path, tolerance: f64, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let target = dir.join(ARTWORK_FILENAME);
+
+    if target.exists() {
+        verbose!(opts, "{} already exists", target);
+        return Ok(false);
+    }
+
+    let (source, picture) = match best_picture(embedded_pictures(dir)?) {
+        Some(found) => found,
+        None => {
+            verbose!(opts, "No embedded artwork in {}", dir);
+            return Ok(false);
+        }
+    };
+
+    if !opts.quiet {
+        println!("Extract: {} -> {}", source, target);
+    }
+
+    if !opts.noop {
+        let img = fix_image(Image::from_bytes(&picture.data)?, tolerance)?;
+        fs::write(&target, img.to_jpeg(ARTWORK_QUALITY)?)?;
+    }
+
+    Ok(true)
+}
+
+fn embedded_pictures(dir: &Utf8Path) -> anyhow::Result<Vec<FoundPicture>> {
+    let files: Vec<Utf8PathBuf> = dir
+        .read_dir_utf8()?
+        .filter_map(Result::ok)
+        .map(|e| e.into_path())
+        .filter(|p| p.is_file())
+        .collect();
+
+    let mut ret = Vec::new();
+
+    for file in dir::media_files(&files) {
+        if let Some(format) = formats::for_path(&file) {
+            for picture in format.pictures(&file)? {
+                ret.push((file.clone(), picture));
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+// Plenty of taggers don't say what a picture is, so if nothing claims to be the front cover, we
+// settle for a picture of unspecified type. Backs, booklets and the like are never used.
+fn best_picture(pictures: Vec<FoundPicture>) -> Option<FoundPicture> {
+    let kind = if pictures
+        .iter()
+        .any(|(_, p)| p.kind == PictureKind::FrontCover)
+    {
+        PictureKind::FrontCover
+    } else {
+        PictureKind::Unspecified
+    };
+
+    pictures
+        .into_iter()
+        .filter(|(_, p)| p.kind == kind)
+        .max_by_key(|(_, p)| (area(&p.data), p.data.len()))
+}
+
+fn area(data: &[u8]) -> usize {
+    imagesize::blob_size(data).map_or(0, |s| s.width * s.height)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::config::DEFAULT_SQUARE_TOLERANCE;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    fn picture(kind: PictureKind, side: usize) -> FoundPicture {
+        let data = Image {
+            width: side,
+            height: side,
+            color: jpeg_encoder::ColorType::Luma,
+            pixels: vec![128; side * side],
+        }
+        .to_jpeg(ARTWORK_QUALITY)
+        .unwrap();
+
+        (
+            Utf8PathBuf::from(format!("{side}.flac")),
+            EmbeddedPicture { kind, data },
+        )
+    }
+
+    #[test]
+    fn test_best_picture() {
+        let (file, _) = best_picture(vec![
+            picture(PictureKind::FrontCover, 10),
+            picture(PictureKind::Unspecified, 40),
+            picture(PictureKind::FrontCover, 20),
+            picture(PictureKind::Other, 30),
+        ])
+        .unwrap();
+
+        assert_eq!("20.flac", file);
+
+        let (file, _) = best_picture(vec![
+            picture(PictureKind::Unspecified, 40),
+            picture(PictureKind::Other, 50),
+        ])
+        .unwrap();
+
+        assert_eq!("40.flac", file);
+        assert!(best_picture(vec![picture(PictureKind::Other, 50)]).is_none());
+        assert!(best_picture(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_extract_artwork() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(
+            fixture!("commands/strip"),
+            &["01.tester.not_stripped.flac", "02.tester.not_stripped.mp3"],
+        )
+        .unwrap();
+
+        let target = tmp.path().join(ARTWORK_FILENAME);
+        let opts = GlobalOpts::default();
+
+        assert!(extract_artwork(tmp.path(), DEFAULT_SQUARE_TOLERANCE, &opts).unwrap());
+
+        let size = imagesize::size(&target).unwrap();
+        assert_eq!((50, 50), (size.width, size.height));
+        assert!(Image::load(&target).is_ok());
+        assert!(!extract_artwork(tmp.path(), DEFAULT_SQUARE_TOLERANCE, &opts).unwrap());
+    }
+
+    #[test]
+    fn test_extract_artwork_nothing_embedded() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+
+        assert!(
+            !extract_artwork(tmp.path(), DEFAULT_SQUARE_TOLERANCE, &GlobalOpts::default()).unwrap()
+        );
+        assert!(!tmp.path().join(ARTWORK_FILENAME).exists());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::err_if_empty;
+use crate::utils::config::load_config;
 use crate::utils::dir;
 use crate::utils::mp3_encoder::{self, TranscodeAction};
 use crate::utils::types::{GlobalOpts, Mp3dirOpts};
@@ -7,13 +8,13 @@ use colored::Colorize;
 
 pub fn run(
     files: &[Utf8PathBuf],
-    preset: String,
+    profile: &str,
     force: bool,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
-    let cmds = mp3_encoder::transcode_cmds()?;
+    let cmds = mp3_encoder::mp3_transcode_cmds(load_config(&opts.config)?.get_profile(profile)?)?;
     let transcode_opts = Mp3dirOpts {
-        preset,
+        profile: profile.to_owned(),
         force,
         recurse: false,
         root: Utf8PathBuf::from("/"),
@@ -43,11 +44,10 @@ fn transcode_action(file: &Utf8Path) -> Option<TranscodeAction> {
     match file.extension() {
         Some(ext) => {
             if ext == "flac" {
-                let mp3_target = file.with_extension("mp3");
-
                 let action = TranscodeAction {
                     flac_src: file.to_path_buf(),
-                    mp3_target,
+                    target: file.with_extension("mp3"),
+                    stale: false,
                 };
 
                 Some(action)
@@ -79,7 +79,8 @@ mod test {
         assert_eq!(
             TranscodeAction {
                 flac_src: flac.clone(),
-                mp3_target: mp3,
+                target: mp3,
+                stale: false,
             },
             transcode_action(&flac).unwrap()
         );
@@ -44,6 +44,7 @@ fn info_for_file(property: &str, file: &Utf8Path) -> anyhow::Result<String> {
         "bitrate" => quality.formatted,
         "time" => time.formatted,
         "time_raw" => time.raw.to_string(),
+        "encoding" => data.encoding.clone().unwrap_or_default(),
         _ => data.get_tag(property)?,
     };
 
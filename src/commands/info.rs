@@ -28,18 +28,30 @@ pub fn run(files: &[Utf8PathBuf]) -> anyhow::Result<bool> {
 }
 
 fn file_info(metadata: &AurMetadata) -> String {
-    let table_rows = vec![
+    let mut table_rows = vec![
         ("Filename", metadata.filename.clone()),
         ("Type", metadata.filetype.to_uppercase()),
         ("Bitrate", metadata.quality().formatted),
+    ];
+
+    if let Some(encoding) = &metadata.encoding {
+        table_rows.push(("Encoding", encoding.clone()));
+    }
+
+    table_rows.extend([
         ("Time", metadata.time().formatted),
         ("Artist", metadata.tags.artist.clone()),
         ("Album", metadata.tags.album.clone()),
         ("Title", metadata.tags.title.clone()),
         ("Genre", metadata.tags.genre.clone()),
         ("Track no", metadata.tags.t_num.to_string()),
-        ("Year", metadata.tags.year.to_string()),
-    ];
+    ]);
+
+    if metadata.tags.disc > 0 {
+        table_rows.push(("Disc no", metadata.tags.disc.to_string()));
+    }
+
+    table_rows.push(("Year", metadata.tags.year.to_string()));
 
     table_rows
         .iter()
@@ -1,15 +1,62 @@
-use crate::err_if_empty;
-use crate::utils::config::{Config, load_config};
-use crate::utils::helpers::MaybeProgress;
-use crate::utils::metadata::{AurMetadata, AurTags, RawTags, expected_tags, irrelevant_tags};
+use crate::commands::{albumdisc, lintdir, strip};
+use crate::utils::audio_stats::AudioStats;
+use crate::utils::authenticity::{Authenticity, Suspicion};
+use crate::utils::config::{Config, PathMatcher, default_cache_dir, load_config};
+use crate::utils::helpers::{JsonReport, MaybeProgress};
+use crate::utils::lint_cache::{Fingerprint, LintCache};
+use crate::utils::metadata::{
+    AurMetadata, AurTags, RawTags, expected_tags, irrelevant_tags, optional_tags,
+};
+use crate::utils::retitler::Retitler;
 use crate::utils::tag_validator::TagValidator;
-use crate::utils::types::GlobalOpts;
+use crate::utils::tagger::Tagger;
+use crate::utils::types::{DiscStyle, FixMode, GlobalOpts, LintOpts, OutputFormat, Severity};
 use crate::utils::words::Words;
-use crate::utils::{dir, rename};
+use crate::utils::{decoder, dir, rename};
+use crate::{err_if_empty, verbose};
+use anyhow::anyhow;
 use camino::{Utf8Path, Utf8PathBuf};
 use colored::Colorize;
 use indicatif::ProgressBar;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashSet};
+use std::io::{self, Write};
+
+const BOM: char = '\u{feff}';
+// As a fraction of full scale.
+const DC_OFFSET_LIMIT: f64 = 0.01;
+
+// The code() of every LintError, which is what [lint.rules] calls them.
+pub const RULES: [&str; 27] = [
+    "bad_capitalisation",
+    "bom_in_album",
+    "bom_in_artist",
+    "bom_in_genre",
+    "bom_in_title",
+    "clipping",
+    "dc_offset",
+    "disc_suffix_in_album",
+    "embedded_artwork",
+    "fake_stereo",
+    "lossy_source",
+    "padded_bit_depth",
+    "upsampled",
+    "in_disc_dir_without_disc_number",
+    "invalid_album",
+    "invalid_artist",
+    "invalid_genre",
+    "invalid_filename",
+    "invalid_title",
+    "invalid_track_number",
+    "invalid_year",
+    "leading_silence",
+    "disc_number_outside_disc_dir",
+    "too_short",
+    "trailing_silence",
+    "unexpected_tags",
+    "wrong_disc_number",
+];
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
@@ -18,14 +65,20 @@ enum CheckResult {
     Bad(LintError),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 enum LintError {
+    BadCapitalisation(String, String),
     BomInAlbum,
     BomInArtist,
     BomInGenre,
     BomInTitle,
+    Clipping(u32),
+    DcOffset(i32),
+    DiscSuffixInAlbum,
     EmbeddedArtwork,
+    FakeStereo,
+    Inauthentic(Suspicion),
     InDiscDirButNoDiscN,
     InvalidAlbum(String),
     InvalidArtist(String),
@@ -34,18 +87,37 @@ enum LintError {
     InvalidTitle(String),
     InvalidTNum(u32),
     InvalidYear(i32),
+    LeadingSilence(u64),
     NotInDiscDirButDiscN,
+    TooShort(u64),
+    TrailingSilence(u64),
     UnexpectedTags(Vec<String>),
+    WrongDiscN(u32),
 }
 
 impl LintError {
     pub fn message(&self) -> String {
         match self {
+            LintError::BadCapitalisation(tag, suggested) => {
+                format!(
+                    "Bad capitalisation in {} tag, should be: {}",
+                    tag, suggested
+                )
+            }
             LintError::BomInAlbum => "BOM found in album tag".to_owned(),
             LintError::BomInArtist => "BOM found in artist tag".to_owned(),
             LintError::BomInGenre => "BOM found in genre tag".to_owned(),
             LintError::BomInTitle => "BOM found in title tag".to_owned(),
+            LintError::Clipping(runs) => format!("Audio clips {} time(s)", runs),
+            LintError::DcOffset(offset) => {
+                format!("DC offset of {:.2}%", *offset as f64 / 100.0)
+            }
+            LintError::DiscSuffixInAlbum => {
+                "Album tag has a disc suffix, but disc numbers should be tags".to_owned()
+            }
             LintError::EmbeddedArtwork => "File contains embedded artwork".to_owned(),
+            LintError::FakeStereo => "Left and right channels are identical".to_owned(),
+            LintError::Inauthentic(suspicion) => suspicion.message(),
             LintError::InDiscDirButNoDiscN => {
                 "File is in a disc directory but lacks a disc number".to_owned()
             }
@@ -56,23 +128,173 @@ impl LintError {
             LintError::InvalidTitle(title) => format!("Invalid title tag: {}", title),
             LintError::InvalidTNum(tnum) => format!("Invalid track number tag: {}", tnum),
             LintError::InvalidYear(year) => format!("Invalid year tag: {}", year),
+            LintError::LeadingSilence(seconds) => {
+                format!("{}s of silence at the start", seconds)
+            }
             LintError::NotInDiscDirButDiscN => {
                 "File has a disc number but is not in a disc directory".to_owned()
             }
+            LintError::TooShort(seconds) => format!("Track is only {}s long", seconds),
+            LintError::TrailingSilence(seconds) => {
+                format!("{}s of silence at the end", seconds)
+            }
             LintError::UnexpectedTags(tags) => format!("Unexpected tags: {}", tags.join(", ")),
+            LintError::WrongDiscN(disc) => {
+                format!("Disc number tag does not match directory: {}", disc)
+            }
+        }
+    }
+
+    // Stable identifiers for machine-readable output. Don't change these: other tools match them.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintError::BadCapitalisation(_, _) => "bad_capitalisation",
+            LintError::BomInAlbum => "bom_in_album",
+            LintError::BomInArtist => "bom_in_artist",
+            LintError::BomInGenre => "bom_in_genre",
+            LintError::BomInTitle => "bom_in_title",
+            LintError::Clipping(_) => "clipping",
+            LintError::DcOffset(_) => "dc_offset",
+            LintError::DiscSuffixInAlbum => "disc_suffix_in_album",
+            LintError::EmbeddedArtwork => "embedded_artwork",
+            LintError::FakeStereo => "fake_stereo",
+            LintError::Inauthentic(suspicion) => suspicion.code(),
+            LintError::InDiscDirButNoDiscN => "in_disc_dir_without_disc_number",
+            LintError::InvalidAlbum(_) => "invalid_album",
+            LintError::InvalidArtist(_) => "invalid_artist",
+            LintError::InvalidGenre(_) => "invalid_genre",
+            LintError::InvalidFilename(_) => "invalid_filename",
+            LintError::InvalidTitle(_) => "invalid_title",
+            LintError::InvalidTNum(_) => "invalid_track_number",
+            LintError::InvalidYear(_) => "invalid_year",
+            LintError::LeadingSilence(_) => "leading_silence",
+            LintError::NotInDiscDirButDiscN => "disc_number_outside_disc_dir",
+            LintError::TooShort(_) => "too_short",
+            LintError::TrailingSilence(_) => "trailing_silence",
+            LintError::UnexpectedTags(_) => "unexpected_tags",
+            LintError::WrongDiscN(_) => "wrong_disc_number",
         }
     }
+
+    // Problems which can only be found by decoding the audio.
+    pub fn needs_decoding(&self) -> bool {
+        matches!(
+            self,
+            LintError::Clipping(_)
+                | LintError::DcOffset(_)
+                | LintError::FakeStereo
+                | LintError::Inauthentic(_)
+                | LintError::LeadingSilence(_)
+                | LintError::TooShort(_)
+                | LintError::TrailingSilence(_)
+        )
+    }
+
+    pub fn value(&self) -> Value {
+        match self {
+            LintError::BadCapitalisation(tag, suggested) => {
+                json!({ "tag": tag, "suggested": suggested })
+            }
+            LintError::InvalidAlbum(s)
+            | LintError::InvalidArtist(s)
+            | LintError::InvalidGenre(s)
+            | LintError::InvalidFilename(s)
+            | LintError::InvalidTitle(s) => json!(s),
+            LintError::Clipping(n) | LintError::InvalidTNum(n) | LintError::WrongDiscN(n) => {
+                json!(n)
+            }
+            LintError::DcOffset(n) | LintError::InvalidYear(n) => json!(n),
+            LintError::LeadingSilence(seconds)
+            | LintError::TooShort(seconds)
+            | LintError::TrailingSilence(seconds) => json!(seconds),
+            LintError::UnexpectedTags(tags) => json!(tags),
+            LintError::Inauthentic(suspicion) => suspicion.value(),
+            _ => Value::Null,
+        }
+    }
+}
+
+// The repairs which fix mode can make. They are made in this order, and renaming must come last,
+// because any of the others can change what the filename ought to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Repair {
+    TrimBom,
+    Strip,
+    AlbumDisc,
+    Retitle,
+    Rename,
 }
 
-pub fn run(files: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+impl Repair {
+    fn for_error(error: &LintError) -> Option<Self> {
+        match error {
+            LintError::BomInAlbum
+            | LintError::BomInArtist
+            | LintError::BomInGenre
+            | LintError::BomInTitle => Some(Repair::TrimBom),
+            LintError::EmbeddedArtwork | LintError::UnexpectedTags(_) => Some(Repair::Strip),
+            LintError::InDiscDirButNoDiscN
+            | LintError::DiscSuffixInAlbum
+            | LintError::WrongDiscN(_) => Some(Repair::AlbumDisc),
+            LintError::BadCapitalisation(_, _) | LintError::InvalidTitle(_) => {
+                Some(Repair::Retitle)
+            }
+            LintError::InvalidFilename(_) => Some(Repair::Rename),
+            _ => None,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Repair::TrimBom => "remove byte-order markers from tags",
+            Repair::Strip => "strip unwanted tags and artwork",
+            Repair::AlbumDisc => "set disc number from directory",
+            Repair::Retitle => "retitle",
+            Repair::Rename => "rename to match tags",
+        }
+    }
+}
+
+struct Fixer<'a> {
+    mode: FixMode,
+    words: &'a Words,
+    disc_style: DiscStyle,
+    opts: &'a GlobalOpts,
+}
+
+pub fn run(files: &[Utf8PathBuf], lint_opts: &LintOpts, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let (recurse, fix, format) = (lint_opts.recurse, lint_opts.fix, lint_opts.format);
+
+    if fix.is_some() && format != OutputFormat::Text {
+        return Err(anyhow!("--fix only works with text output"));
+    }
+
+    // Verbose output goes to stdout, where it would break a JSON stream.
+    let opts = &GlobalOpts {
+        verbose: opts.verbose && format == OutputFormat::Text,
+        ..opts.clone()
+    };
+
     let config = load_config(&opts.config)?;
+    config.check_rule_names(&known_rules())?;
     let words = Words::new(&config);
     let validator = TagValidator::new(&words, config.get_genres());
+    let disc_style = config.get_disc_style();
     let mut ret_code = true;
     let files = dir::media_files(&dir::expand_file_list(files, recurse)?);
     err_if_empty!(files);
 
-    let pb = if recurse {
+    let mut report = JsonReport::new(format);
+    let cache_file = lint_opts
+        .use_cache
+        .then(|| default_cache_dir().join("lint.json"));
+    let mut cache = LintCache::load(
+        cache_file.as_deref(),
+        config.checks_hash(),
+        &cache_variant(lint_opts),
+    );
+
+    let pb = if recurse && format == OutputFormat::Text {
         MaybeProgress::Bar(ProgressBar::new(files.len() as u64))
     } else {
         MaybeProgress::Direct
@@ -80,26 +302,206 @@ pub fn run(files: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::R
 
     for file in files {
         pb.inc(1);
-        let results = filter_results(&file, lint_file(&file, &validator, opts)?, &config);
+        let results = filter_results(
+            &file,
+            cached_lint_file(
+                &file, &validator, disc_style, lint_opts, &config, &mut cache, opts,
+            )?,
+            &config,
+        );
         let problems: Vec<_> = results.iter().filter_map(Some).collect();
-        if !problems.is_empty() {
-            ret_code = false;
-            display_problems(&file, &problems, &pb);
+
+        if problems.is_empty() {
+            continue;
+        }
+
+        if format != OutputFormat::Text {
+            report.add(json_entry(&file, &problems, &config));
+            if has_errors(&file, &problems, &config) {
+                ret_code = false;
+            }
+            continue;
+        }
+
+        display_problems(&file, &problems, &config, &pb);
+
+        let fixer = match fix {
+            Some(mode) => Fixer {
+                mode,
+                words: &words,
+                disc_style,
+                opts,
+            },
+            None => {
+                if has_errors(&file, &problems, &config) {
+                    ret_code = false;
+                }
+                continue;
+            }
+        };
+
+        match fix_file(&file, &problems, &fixer, &pb) {
+            Ok(fixed_file) if !opts.noop => {
+                let mut relinted = lint_file(&fixed_file, &validator, disc_style, opts)?;
+                relinted.extend(decoded_problems(&problems));
+                let remaining = filter_results(&fixed_file, relinted, &config);
+
+                pb.println(&format!(
+                    "  {} problem(s) before, {} after",
+                    problems.len(),
+                    remaining.len()
+                ));
+
+                for p in &remaining {
+                    if let CheckResult::Bad(problem) = p {
+                        pb.println(&problem_line(&fixed_file, problem, &config));
+                    }
+                }
+
+                pb.println("");
+
+                if has_errors(&fixed_file, &remaining.iter().collect::<Vec<_>>(), &config) {
+                    ret_code = false;
+                }
+            }
+            Ok(_) => {
+                if has_errors(&file, &problems, &config) {
+                    ret_code = false;
+                }
+            }
+            Err(e) => {
+                eprintln!("Error fixing {file}: {e}");
+                ret_code = false;
+            }
         }
     }
 
+    if let Err(e) = cache.save() {
+        eprintln!("Could not save lint cache: {e}");
+    }
+
     pb.finish();
+    report.finish();
     Ok(ret_code)
 }
 
-fn is_file_excluded(file: &Utf8Path, list: Option<&HashSet<String>>) -> bool {
-    match list {
-        Some(rules) => {
-            let file_str = file.to_string();
-            rules.iter().any(|rule| file_str.contains(rule))
+// Returns the path of the file, which renaming may have changed.
+fn fix_file(
+    file: &Utf8Path,
+    problems: &[&CheckResult],
+    fixer: &Fixer,
+    pb: &MaybeProgress,
+) -> anyhow::Result<Utf8PathBuf> {
+    let repairs: BTreeSet<Repair> = problems
+        .iter()
+        .filter_map(|p| match p {
+            CheckResult::Bad(problem) => Repair::for_error(problem),
+            CheckResult::Good => None,
+        })
+        .collect();
+
+    // Retitling only touches the tags which were complained about.
+    let retitle: BTreeSet<&str> = problems
+        .iter()
+        .filter_map(|p| match p {
+            CheckResult::Bad(LintError::BadCapitalisation(tag, _)) => Some(tag.as_str()),
+            CheckResult::Bad(LintError::InvalidTitle(_)) => Some("title"),
+            _ => None,
+        })
+        .collect();
+
+    let mut file = file.to_path_buf();
+
+    for repair in repairs {
+        if fixer.mode == FixMode::Ask && !confirm(repair.description())? {
+            continue;
+        }
+
+        if fixer.opts.noop {
+            pb.println(&format!("  would {}", repair.description()));
+        } else {
+            pb.println(&format!("  fixing: {}", repair.description()));
+            file = make_repair(repair, &file, &retitle, fixer)?;
         }
-        None => false,
     }
+
+    Ok(file)
+}
+
+fn make_repair(
+    repair: Repair,
+    file: &Utf8Path,
+    retitle: &BTreeSet<&str>,
+    fixer: &Fixer,
+) -> anyhow::Result<Utf8PathBuf> {
+    let info = AurMetadata::new(file)?;
+    let quiet = fixer.opts.quiet;
+
+    match repair {
+        Repair::TrimBom => {
+            let tagger = Tagger::new(&info)?;
+            let tags = &info.tags;
+
+            for (name, value) in [
+                ("artist", &tags.artist),
+                ("title", &tags.title),
+                ("album", &tags.album),
+                ("genre", &tags.genre),
+            ] {
+                if let Some(trimmed) = value.strip_prefix(BOM) {
+                    tagger.set_tag(name, trimmed, quiet)?;
+                }
+            }
+        }
+        Repair::Strip => {
+            strip::strip_file(file, fixer.opts)?;
+        }
+        Repair::AlbumDisc => {
+            albumdisc::fix_file(file, fixer.disc_style, fixer.opts)?;
+        }
+        Repair::Retitle => {
+            let retitler = Retitler::new(fixer.words);
+            let tagger = Tagger::new(&info)?;
+            let tags = &info.tags;
+
+            if retitle.contains("title") {
+                tagger.set_title(&retitler.retitle(&tags.title), quiet)?;
+            }
+
+            for (name, value) in [("artist", &tags.artist), ("album", &tags.album)] {
+                if !retitle.contains(name) {
+                    continue;
+                }
+
+                let retitled = retitler.retitle(value);
+
+                if !value.is_empty() && &retitled != value {
+                    tagger.set_tag(name, &retitled, quiet)?;
+                }
+            }
+        }
+        Repair::Rename => {
+            if let Some(action) = rename::rename_action_from_metadata(&info)? {
+                let dest = action.1.clone();
+                rename::rename(action, false)?;
+                return Ok(dest);
+            }
+        }
+    }
+
+    Ok(file.to_path_buf())
+}
+
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    print!("  {}? [y/N]> ", question);
+    io::stdout().flush()?;
+    let mut buffer = String::new();
+    io::stdin().read_line(&mut buffer)?;
+    Ok(buffer.trim().eq_ignore_ascii_case("y"))
+}
+
+fn is_file_excluded(file: &Utf8Path, list: Option<&PathMatcher>) -> bool {
+    list.is_some_and(|rules| rules.is_match(file))
 }
 
 fn filter_results(file: &Utf8Path, results: Vec<CheckResult>, config: &Config) -> Vec<CheckResult> {
@@ -121,32 +523,192 @@ fn filter_results(file: &Utf8Path, results: Vec<CheckResult>, config: &Config) -
             CheckResult::Bad(LintError::InvalidFilename(_)) => {
                 !is_file_excluded(file, config.get_ignore_lint_invalid_filename())
             }
+            CheckResult::Bad(LintError::BadCapitalisation(_, _)) => {
+                !is_file_excluded(file, config.get_ignore_lint_bad_capitalisation())
+            }
             _ => true,
         })
+        .filter(|r| match r {
+            CheckResult::Bad(problem) => {
+                config.get_rule_severity(problem.code(), file) != Severity::Off
+            }
+            CheckResult::Good => true,
+        })
         .collect()
 }
 
-fn display_problems(file: &Utf8Path, problems: &Vec<&CheckResult>, pb: &MaybeProgress) {
+fn display_problems(
+    file: &Utf8Path,
+    problems: &Vec<&CheckResult>,
+    config: &Config,
+    pb: &MaybeProgress,
+) {
     pb.println(&format!("{}", file.to_string().bold()));
 
     for p in problems {
         match p {
             CheckResult::Good => (),
-            CheckResult::Bad(problem) => pb.println(&format!("  {}", problem.message())),
+            CheckResult::Bad(problem) => pb.println(&problem_line(file, problem, config)),
         }
     }
 
     pb.println("");
 }
 
+fn problem_line(file: &Utf8Path, problem: &LintError, config: &Config) -> String {
+    match config.get_rule_severity(problem.code(), file) {
+        Severity::Warn => format!("  {} (warning)", problem.message()),
+        _ => format!("  {}", problem.message()),
+    }
+}
+
+fn has_errors(file: &Utf8Path, problems: &[&CheckResult], config: &Config) -> bool {
+    problems.iter().any(|p| match p {
+        CheckResult::Good => false,
+        CheckResult::Bad(problem) => {
+            config.get_rule_severity(problem.code(), file) == Severity::Error
+        }
+    })
+}
+
+fn json_entry(file: &Utf8Path, problems: &Vec<&CheckResult>, config: &Config) -> Value {
+    let problems: Vec<Value> = problems
+        .iter()
+        .filter_map(|p| match p {
+            CheckResult::Good => None,
+            CheckResult::Bad(problem) => Some(json!({
+                "code": problem.code(),
+                "severity": config.get_rule_severity(problem.code(), file),
+                "message": problem.message(),
+                "value": problem.value(),
+            })),
+        })
+        .collect();
+
+    json!({ "path": file.as_str(), "problems": problems })
+}
+
+// lint and lintdir share [lint.rules], so each has to accept the other's rules.
+pub fn known_rules() -> Vec<&'static str> {
+    RULES.into_iter().chain(lintdir::RULES).collect()
+}
+
+// Whether or not the audio was checked changes what lint finds.
+fn cache_variant(lint_opts: &LintOpts) -> String {
+    format!(
+        "authenticity={},audio={}",
+        lint_opts.authenticity, lint_opts.audio
+    )
+}
+
+fn cached_lint_file(
+    file: &Utf8Path,
+    validator: &TagValidator,
+    disc_style: DiscStyle,
+    lint_opts: &LintOpts,
+    config: &Config,
+    cache: &mut LintCache<Vec<LintError>>,
+    opts: &GlobalOpts,
+) -> anyhow::Result<Vec<CheckResult>> {
+    let path = file.canonicalize_utf8()?;
+    let fingerprint = Fingerprint::new(&[&path])?;
+
+    if let Some(problems) = cache.get(&path, &fingerprint) {
+        verbose!(opts, "{} is unchanged since it was last linted", file);
+        return Ok(problems.iter().cloned().map(CheckResult::Bad).collect());
+    }
+
+    let mut results = lint_file(file, validator, disc_style, opts)?;
+
+    if lint_opts.authenticity && file.extension() == Some("flac") {
+        results.extend(check_authenticity(file)?);
+    }
+
+    if lint_opts.audio && decoder::can_decode(file) {
+        results.extend(check_audio(file, config)?);
+    }
+
+    let problems = results
+        .iter()
+        .filter_map(|r| match r {
+            CheckResult::Bad(problem) => Some(problem.clone()),
+            CheckResult::Good => None,
+        })
+        .collect();
+
+    cache.insert(&path, fingerprint, problems);
+    Ok(results)
+}
+
+// Authenticity is only checked on demand, because it means decoding the whole file.
+fn check_authenticity(file: &Utf8Path) -> anyhow::Result<Vec<CheckResult>> {
+    Ok(Authenticity::from_path(file)?
+        .suspicions()
+        .into_iter()
+        .map(|s| CheckResult::Bad(LintError::Inauthentic(s)))
+        .collect())
+}
+
+fn check_audio(file: &Utf8Path, config: &Config) -> anyhow::Result<Vec<CheckResult>> {
+    let stats = AudioStats::from_path(file)?;
+    let max_silence = config.get_max_silence() as f64;
+    let mut problems = Vec::new();
+
+    if stats.clipped_runs > 0 {
+        problems.push(LintError::Clipping(stats.clipped_runs));
+    }
+
+    if stats.leading_silence_seconds() > max_silence {
+        problems.push(LintError::LeadingSilence(
+            stats.leading_silence_seconds() as u64
+        ));
+    }
+
+    if stats.trailing_silence_seconds() > max_silence {
+        problems.push(LintError::TrailingSilence(
+            stats.trailing_silence_seconds() as u64
+        ));
+    }
+
+    if stats.identical_channels {
+        problems.push(LintError::FakeStereo);
+    }
+
+    if stats.dc_offset.abs() > DC_OFFSET_LIMIT {
+        problems.push(LintError::DcOffset(
+            (stats.dc_offset * 10_000.0).round() as i32
+        ));
+    }
+
+    if stats.duration() < config.get_min_track_length() as f64 {
+        problems.push(LintError::TooShort(stats.duration() as u64));
+    }
+
+    Ok(problems.into_iter().map(CheckResult::Bad).collect())
+}
+
+// Fixes never touch the audio, so there's no need to decode a fixed file again.
+fn decoded_problems(problems: &[&CheckResult]) -> Vec<CheckResult> {
+    problems
+        .iter()
+        .filter_map(|p| match p {
+            CheckResult::Bad(problem) if problem.needs_decoding() => {
+                Some(CheckResult::Bad(problem.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 fn lint_file(
     file: &Utf8Path,
     validator: &TagValidator,
+    disc_style: DiscStyle,
     opts: &GlobalOpts,
 ) -> anyhow::Result<Vec<CheckResult>> {
     let info = AurMetadata::new(file)?;
 
-    let results: Vec<_> = run_checks(&info, validator, opts)
+    let results: Vec<_> = run_checks(&info, validator, disc_style, opts)
         .into_iter()
         .filter(|r| matches!(r, CheckResult::Bad(_)))
         .collect();
@@ -156,6 +718,7 @@ fn lint_file(
 fn run_checks(
     metadata: &AurMetadata,
     validator: &TagValidator,
+    disc_style: DiscStyle,
     opts: &GlobalOpts,
 ) -> Vec<CheckResult> {
     vec![
@@ -163,25 +726,42 @@ fn run_checks(
         has_no_unwanted_tags(&metadata.filetype, &metadata.rawtags),
         has_no_picture(metadata.has_picture),
         has_no_byte_order_markers(&metadata.tags),
-        has_disc_number_or_not(&metadata.path, &metadata.tags.album),
+        has_disc_number_or_not(metadata, disc_style),
     ]
     .into_iter()
     .chain(has_no_invalid_tags(metadata, validator))
+    .chain(has_good_capitalisation(metadata, validator))
     .collect()
 }
 
-fn has_disc_number_or_not(file: &Utf8Path, album_tag: &str) -> CheckResult {
-    let disc_in_name = album_tag.contains("Disc ");
-    let in_disc_dir = file
-        .parent()
-        .unwrap()
-        .file_name()
-        .unwrap()
-        .contains("disc_");
+fn has_disc_number_or_not(metadata: &AurMetadata, disc_style: DiscStyle) -> CheckResult {
+    let disc_in_name = metadata.tags.album.contains("Disc ");
+    let dir_name = metadata.path.parent().unwrap().file_name().unwrap();
+    let in_disc_dir = dir_name.contains("disc_");
+
+    let has_disc_number = match disc_style {
+        DiscStyle::AlbumSuffix => disc_in_name,
+        DiscStyle::Tag => metadata.tags.disc > 0,
+    };
+
+    if disc_style == DiscStyle::Tag {
+        let dir_number = dir_name
+            .rsplit("disc_")
+            .next()
+            .and_then(|n| n.parse::<u32>().ok());
 
-    if disc_in_name && !in_disc_dir {
+        if disc_in_name {
+            return CheckResult::Bad(LintError::DiscSuffixInAlbum);
+        }
+
+        if has_disc_number && in_disc_dir && dir_number != Some(metadata.tags.disc) {
+            return CheckResult::Bad(LintError::WrongDiscN(metadata.tags.disc));
+        }
+    }
+
+    if has_disc_number && !in_disc_dir {
         CheckResult::Bad(LintError::NotInDiscDirButDiscN)
-    } else if in_disc_dir && !disc_in_name {
+    } else if in_disc_dir && !has_disc_number {
         CheckResult::Bad(LintError::InDiscDirButNoDiscN)
     } else {
         CheckResult::Good
@@ -224,7 +804,12 @@ fn has_no_unwanted_tags(filetype: &str, rawtags: &RawTags) -> CheckResult {
 
     let expected_tags = expected_tags(filetype).unwrap();
     let irrelevant_tags = irrelevant_tags(filetype).unwrap();
-    let allowed_tags: HashSet<_> = expected_tags.union(&irrelevant_tags).cloned().collect();
+    let optional_tags = optional_tags(filetype).unwrap();
+    let allowed_tags: HashSet<_> = expected_tags
+        .union(&irrelevant_tags)
+        .chain(optional_tags.iter())
+        .cloned()
+        .collect();
     let unexpected_tags: HashSet<_> = tag_keys.difference(&allowed_tags).collect();
 
     if unexpected_tags.is_empty() {
@@ -285,6 +870,28 @@ fn has_no_invalid_tags(metadata: &AurMetadata, validator: &TagValidator) -> Vec<
     }
 }
 
+// Tags which has_no_invalid_tags or has_no_byte_order_markers have already complained about are
+// left alone: fixing them will most likely change the capitalisation anyway.
+fn has_good_capitalisation(metadata: &AurMetadata, validator: &TagValidator) -> Vec<CheckResult> {
+    let tags = &metadata.tags;
+    let mut to_check = vec![("title", &tags.title), ("artist", &tags.artist)];
+
+    if !metadata.in_tracks {
+        to_check.push(("album", &tags.album));
+    }
+
+    to_check
+        .into_iter()
+        .filter(|(name, value)| validator.validate_tag(name, value).unwrap_or(false))
+        .filter(|(_, value)| !has_bom_leader(value))
+        .filter_map(|(name, value)| {
+            validator
+                .suggest_capitalisation(value)
+                .map(|s| CheckResult::Bad(LintError::BadCapitalisation(name.to_owned(), s)))
+        })
+        .collect()
+}
+
 fn has_no_byte_order_markers(tags: &AurTags) -> CheckResult {
     if has_bom_leader(&tags.artist) {
         CheckResult::Bad(LintError::BomInArtist)
@@ -311,6 +918,8 @@ fn has_bom_leader(string: &str) -> bool {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::tagger::Tagger;
+    use camino_tempfile_ext::prelude::*;
     use snltest::fixture;
 
     #[test]
@@ -319,12 +928,133 @@ mod test {
         let words = Words::new(&config);
         let validator = TagValidator::new(&words, None);
         let file = fixture!("commands/lint/09.tester.bad_title_allowed.mp3");
-        let lint_result = lint_file(&file, &validator, &GlobalOpts::default()).unwrap();
+        let lint_result = lint_file(
+            &file,
+            &validator,
+            DiscStyle::AlbumSuffix,
+            &GlobalOpts::default(),
+        )
+        .unwrap();
         let expected_empty: Vec<CheckResult> = Vec::new();
 
         assert_eq!(expected_empty, filter_results(&file, lint_result, &config));
     }
 
+    #[test]
+    fn test_check_audio() {
+        assert_eq!(
+            vec![
+                CheckResult::Bad(LintError::Clipping(100)),
+                CheckResult::Bad(LintError::TooShort(0)),
+            ],
+            check_audio(&fixture!("info/test.flac"), &sample_config()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decoded_problems() {
+        let problems = [
+            CheckResult::Bad(LintError::FakeStereo),
+            CheckResult::Bad(LintError::EmbeddedArtwork),
+            CheckResult::Bad(LintError::Inauthentic(Suspicion::LossySource(16000))),
+        ];
+
+        assert_eq!(
+            vec![
+                CheckResult::Bad(LintError::FakeStereo),
+                CheckResult::Bad(LintError::Inauthentic(Suspicion::LossySource(16000))),
+            ],
+            decoded_problems(&problems.iter().collect::<Vec<_>>())
+        );
+    }
+
+    #[test]
+    fn test_has_good_capitalisation() {
+        let words = Words::new(&sample_config());
+        let validator = TagValidator::new(&words, None);
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/lint"), &["02.tester.lints_fine.mp3"])
+            .unwrap();
+        let file = tmp.path().join("02.tester.lints_fine.mp3");
+        let info = AurMetadata::new(&file).unwrap();
+        assert!(has_good_capitalisation(&info, &validator).is_empty());
+
+        let tagger = Tagger::new(&info).unwrap();
+        tagger
+            .set_title("The Light At The End Of The Tunnel", true)
+            .unwrap();
+        tagger.set_tag("artist", "Abba", true).unwrap();
+        tagger.set_tag("album", "Bad  Album", true).unwrap();
+
+        assert_eq!(
+            vec![
+                CheckResult::Bad(LintError::BadCapitalisation(
+                    "title".to_owned(),
+                    "The Light at the End of the Tunnel".to_owned()
+                )),
+                CheckResult::Bad(LintError::BadCapitalisation(
+                    "artist".to_owned(),
+                    "ABBA".to_owned()
+                )),
+            ],
+            has_good_capitalisation(&AurMetadata::new(&file).unwrap(), &validator)
+        );
+    }
+
+    #[test]
+    fn test_ignore_bad_capitalisation() {
+        let config = sample_config();
+        let problem = || {
+            vec![CheckResult::Bad(LintError::BadCapitalisation(
+                "title".to_owned(),
+                "Song".to_owned(),
+            ))]
+        };
+
+        assert!(
+            filter_results(
+                Utf8Path::new("/storage/flac/eps/abc/artist.ep/01.artist.song.flac"),
+                problem(),
+                &config
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            problem(),
+            filter_results(
+                Utf8Path::new("/storage/flac/albums/abc/artist.album/01.artist.song.flac"),
+                problem(),
+                &config
+            )
+        );
+    }
+
+    #[test]
+    fn test_rule_severities() {
+        let config = load_config(&fixture!("config/rules.toml")).unwrap();
+        let file = Utf8Path::new("/storage/flac/albums/abc/artist.album/01.artist.song.flac");
+        let results = filter_results(
+            file,
+            vec![
+                CheckResult::Bad(LintError::EmbeddedArtwork),
+                CheckResult::Bad(LintError::InvalidYear(0)),
+            ],
+            &config,
+        );
+
+        assert_eq!(vec![CheckResult::Bad(LintError::InvalidYear(0))], results);
+        assert!(has_errors(
+            file,
+            &results.iter().collect::<Vec<_>>(),
+            &config
+        ));
+        assert!(!has_errors(
+            file,
+            &[&CheckResult::Bad(LintError::InvalidGenre("Pop".to_owned()))],
+            &config
+        ));
+    }
+
     #[test]
     fn lint_functional_tests() {
         let words = Words::new(&sample_config());
@@ -334,6 +1064,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/01.tester.lints_fine.flac"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -344,6 +1075,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/02.tester.lints_fine.mp3"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -359,6 +1091,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/00.tester.missing_genre_track_no_year.flac"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -374,6 +1107,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/03.tester.has_bom_leader.flac"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -387,6 +1121,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/05.tester.surplus_tags.mp3"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -405,6 +1140,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/06.tester.extra_tags_and_picture.mp3"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -415,6 +1151,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/07.tester.picture.flac"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -425,6 +1162,7 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/disc_1/01.tester.no_disc_number.mp3"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
@@ -435,12 +1173,288 @@ mod test {
             lint_file(
                 &fixture!("commands/lint/08.tester.disc_number.mp3"),
                 &validator,
+                DiscStyle::AlbumSuffix,
                 &GlobalOpts::default(),
             )
             .unwrap()
         );
     }
 
+    #[test]
+    fn test_has_disc_number_or_not_tag_style() {
+        let words = Words::new(&sample_config());
+        let validator = TagValidator::new(&words, None);
+        let opts = GlobalOpts::default();
+
+        assert_eq!(
+            vec![CheckResult::Bad(LintError::DiscSuffixInAlbum)],
+            lint_file(
+                &fixture!("commands/lint/08.tester.disc_number.mp3"),
+                &validator,
+                DiscStyle::Tag,
+                &opts,
+            )
+            .unwrap()
+        );
+
+        let file_name = "01.tester.no_disc_number.mp3";
+        let tmp = Utf8TempDir::new().unwrap();
+        let disc_dir = tmp.child("disc_1");
+        disc_dir.create_dir_all().unwrap();
+        disc_dir
+            .copy_from(fixture!("commands/lint/disc_1"), &[file_name])
+            .unwrap();
+        let file = disc_dir.join(file_name);
+
+        assert_eq!(
+            vec![CheckResult::Bad(LintError::InDiscDirButNoDiscN)],
+            lint_file(&file, &validator, DiscStyle::Tag, &opts).unwrap()
+        );
+
+        let info = AurMetadata::new(&file).unwrap();
+        Tagger::new(&info).unwrap().set_disc("2", true).unwrap();
+
+        assert_eq!(
+            vec![CheckResult::Bad(LintError::WrongDiscN(2))],
+            lint_file(&file, &validator, DiscStyle::Tag, &opts).unwrap()
+        );
+
+        let info = AurMetadata::new(&file).unwrap();
+        Tagger::new(&info).unwrap().set_disc("1", true).unwrap();
+
+        assert!(
+            lint_file(&file, &validator, DiscStyle::Tag, &opts)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_repair_for_error() {
+        assert_eq!(
+            Some(Repair::Rename),
+            Repair::for_error(&LintError::InvalidFilename("x".to_owned()))
+        );
+        assert_eq!(
+            Some(Repair::Strip),
+            Repair::for_error(&LintError::EmbeddedArtwork)
+        );
+        assert_eq!(None, Repair::for_error(&LintError::InvalidYear(0)));
+        assert!(Repair::Rename > Repair::Retitle);
+    }
+
+    #[test]
+    fn test_json_entry() {
+        let problems = [
+            CheckResult::Bad(LintError::InvalidYear(1066)),
+            CheckResult::Bad(LintError::UnexpectedTags(vec![
+                "comment".to_owned(),
+                "encoder".to_owned(),
+            ])),
+            CheckResult::Bad(LintError::EmbeddedArtwork),
+        ];
+
+        let entry = json_entry(
+            Utf8Path::new("/music/x.flac"),
+            &problems.iter().collect(),
+            &sample_config(),
+        );
+
+        assert_eq!(
+            json!({
+                "path": "/music/x.flac",
+                "problems": [
+                    {
+                        "code": "invalid_year",
+                        "severity": "error",
+                        "message": "Invalid year tag: 1066",
+                        "value": 1066,
+                    },
+                    {
+                        "code": "unexpected_tags",
+                        "severity": "error",
+                        "message": "Unexpected tags: comment, encoder",
+                        "value": ["comment", "encoder"],
+                    },
+                    {
+                        "code": "embedded_artwork",
+                        "severity": "error",
+                        "message": "File contains embedded artwork",
+                        "value": null,
+                    },
+                ],
+            }),
+            entry
+        );
+    }
+
+    #[test]
+    fn test_fix_file() {
+        let config = load_config(&fixture!("config/lint_fix.toml")).unwrap();
+        let words = Words::new(&config);
+        let validator = TagValidator::new(&words, None);
+        let opts = GlobalOpts::default();
+        let fixer = Fixer {
+            mode: FixMode::Auto,
+            words: &words,
+            disc_style: DiscStyle::AlbumSuffix,
+            opts: &opts,
+        };
+
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(
+            fixture!("commands/lint"),
+            &[
+                "03.tester.has_bom_leader.flac",
+                "06.tester.extra_tags_and_picture.mp3",
+            ],
+        )
+        .unwrap();
+
+        for name in [
+            "03.tester.has_bom_leader.flac",
+            "06.tester.extra_tags_and_picture.mp3",
+        ] {
+            let file = tmp.path().join(name);
+            let problems = lint_file(&file, &validator, DiscStyle::AlbumSuffix, &opts).unwrap();
+            assert!(!problems.is_empty());
+
+            let fixed = fix_file(
+                &file,
+                &problems.iter().collect::<Vec<_>>(),
+                &fixer,
+                &MaybeProgress::Direct,
+            )
+            .unwrap();
+
+            assert!(
+                lint_file(&fixed, &validator, DiscStyle::AlbumSuffix, &opts)
+                    .unwrap()
+                    .is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn test_fix_file_only_retitles_flagged_tags() {
+        let config = sample_config();
+        let words = Words::new(&config);
+        let opts = GlobalOpts::default();
+        let fixer = Fixer {
+            mode: FixMode::Auto,
+            words: &words,
+            disc_style: DiscStyle::AlbumSuffix,
+            opts: &opts,
+        };
+
+        let name = "02.tester.lints_fine.mp3";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/lint"), &[name]).unwrap();
+        let file = tmp.path().join(name);
+        let info = AurMetadata::new(&file).unwrap();
+        let tagger = Tagger::new(&info).unwrap();
+        tagger.set_title("Song In Title Case", true).unwrap();
+        tagger.set_tag("artist", "Abba", true).unwrap();
+
+        // As if the artist's bad capitalisation had been ignored.
+        let problems = [CheckResult::Bad(LintError::BadCapitalisation(
+            "title".to_owned(),
+            "Song in Title Case".to_owned(),
+        ))];
+
+        let fixed = fix_file(
+            &file,
+            &problems.iter().collect::<Vec<_>>(),
+            &fixer,
+            &MaybeProgress::Direct,
+        )
+        .unwrap();
+
+        let tags = AurMetadata::new(&fixed).unwrap().tags;
+        assert_eq!("Song in Title Case", tags.title);
+        assert_eq!("Abba", tags.artist);
+    }
+
+    #[test]
+    fn test_fix_file_noop() {
+        let config = sample_config();
+        let words = Words::new(&config);
+        let validator = TagValidator::new(&words, None);
+        let opts = GlobalOpts {
+            noop: true,
+            ..Default::default()
+        };
+        let fixer = Fixer {
+            mode: FixMode::Auto,
+            words: &words,
+            disc_style: DiscStyle::AlbumSuffix,
+            opts: &opts,
+        };
+
+        let name = "06.tester.extra_tags_and_picture.mp3";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/lint"), &[name]).unwrap();
+        let file = tmp.path().join(name);
+        let problems = lint_file(&file, &validator, DiscStyle::AlbumSuffix, &opts).unwrap();
+
+        fix_file(
+            &file,
+            &problems.iter().collect::<Vec<_>>(),
+            &fixer,
+            &MaybeProgress::Direct,
+        )
+        .unwrap();
+
+        assert_eq!(
+            problems,
+            lint_file(&file, &validator, DiscStyle::AlbumSuffix, &opts).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rules() {
+        let problems = [
+            LintError::BadCapitalisation(String::new(), String::new()),
+            LintError::BomInAlbum,
+            LintError::BomInArtist,
+            LintError::BomInGenre,
+            LintError::BomInTitle,
+            LintError::Clipping(0),
+            LintError::DcOffset(0),
+            LintError::DiscSuffixInAlbum,
+            LintError::EmbeddedArtwork,
+            LintError::FakeStereo,
+            LintError::Inauthentic(Suspicion::LossySource(0)),
+            LintError::Inauthentic(Suspicion::PaddedBitDepth(0, 0)),
+            LintError::Inauthentic(Suspicion::Upsampled(0, 0)),
+            LintError::InDiscDirButNoDiscN,
+            LintError::InvalidAlbum(String::new()),
+            LintError::InvalidArtist(String::new()),
+            LintError::InvalidGenre(String::new()),
+            LintError::InvalidFilename(String::new()),
+            LintError::InvalidTitle(String::new()),
+            LintError::InvalidTNum(0),
+            LintError::InvalidYear(0),
+            LintError::LeadingSilence(0),
+            LintError::NotInDiscDirButDiscN,
+            LintError::TooShort(0),
+            LintError::TrailingSilence(0),
+            LintError::UnexpectedTags(Vec::new()),
+            LintError::WrongDiscN(0),
+        ];
+
+        assert_eq!(
+            RULES.to_vec(),
+            problems.iter().map(|p| p.code()).collect::<Vec<_>>()
+        );
+        assert!(
+            load_config(&fixture!("config/rules.toml"))
+                .unwrap()
+                .check_rule_names(&known_rules())
+                .is_ok()
+        );
+    }
+
     fn sample_config() -> Config {
         load_config(&fixture!("config/test.toml")).unwrap()
     }
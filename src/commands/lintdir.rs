@@ -1,11 +1,14 @@
+use crate::commands::lint;
 use crate::utils::config::{
-    ARTWORK_FILENAME, Config, MAX_ARTWORK_SIZE, MIN_ARTWORK_SIZE, load_config,
+    ARTWORK_FILENAME, Config, MAX_ARTWORK_SIZE, MIN_ARTWORK_SIZE, PathMatcher, default_cache_dir,
+    load_config,
 };
 use crate::utils::dir;
-use crate::utils::helpers::MaybeProgress;
+use crate::utils::helpers::{JsonReport, MaybeProgress};
+use crate::utils::lint_cache::{Fingerprint, LintCache};
 use crate::utils::metadata::AurMetadata;
 use crate::utils::rename::number_from_filename;
-use crate::utils::types::GlobalOpts;
+use crate::utils::types::{DiscStyle, GlobalOpts, OutputFormat, Severity};
 use crate::{err_if_empty, verbose};
 use anyhow::anyhow;
 use camino::{Utf8Path, Utf8PathBuf};
@@ -13,15 +16,39 @@ use colored::Colorize;
 use imagesize;
 use indicatif::ProgressBar;
 use regex::Regex;
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
 use std::sync::LazyLock;
 
 const ARTIST_SEPS: [&str; 6] = ["feat", "feat.", "featuring", "and", "with", "/"];
 
+// The code() of every LintDirError, which is what [lint.rules] calls them.
+pub const RULES: [&str; 13] = [
+    "bad_file",
+    "bad_file_count",
+    "cover_art_invalid",
+    "cover_art_missing",
+    "cover_art_not_square",
+    "cover_art_too_big",
+    "cover_art_too_small",
+    "discs_not_contiguous",
+    "disc_tracks_not_from_one",
+    "inconsistent_tags",
+    "invalid_dir_name",
+    "mixed_file_types",
+    "unsequenced_file",
+];
+
 static DIRNAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"^[a-z0-9][a-z\-_0-9]+\.[a-z0-9][a-z\-_[0-9]]*[a-z0-9]?$").unwrap()
 });
 
+static DISC_DIR_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^disc_(\d+)$").unwrap());
+
+static DISC_SUFFIX_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r" \(Disc \d+\)$").unwrap());
+
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 enum CheckResult {
@@ -35,7 +62,7 @@ enum Hierarchy {
     Mp3,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 enum LintDirError {
     BadFile(HashSet<String>),
@@ -45,6 +72,8 @@ enum LintDirError {
     CoverArtNotSquare,
     CoverArtTooBig,
     CoverArtTooSmall,
+    DiscsNotContiguous,
+    DiscTracksNotFromOne(Vec<u32>),
     InconsistentTags(HashSet<String>),
     InvalidDirName,
     MixedFileTypes,
@@ -65,6 +94,17 @@ impl LintDirError {
             LintDirError::CoverArtMissing => "Cover art is missing".to_owned(),
             LintDirError::CoverArtTooBig => "Cover art is too big".to_owned(),
             LintDirError::CoverArtTooSmall => "Cover art is too small".to_owned(),
+            LintDirError::DiscsNotContiguous => {
+                "Disc directories are not numbered contiguously from 1".to_owned()
+            }
+            LintDirError::DiscTracksNotFromOne(discs) => format!(
+                "Track numbers do not start at 1 on disc(s): {}",
+                discs
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
             LintDirError::InconsistentTags(tags) => {
                 let mut vec: Vec<_> = tags.iter().map(|f| f.to_owned()).collect();
                 vec.sort();
@@ -75,16 +115,64 @@ impl LintDirError {
             LintDirError::UnsequencedFile => "File numbers are not correctly sequenced".to_owned(),
         }
     }
+
+    // Stable identifiers for machine-readable output. Don't change these: other tools match them.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintDirError::BadFile(_) => "bad_file",
+            LintDirError::BadFileCount => "bad_file_count",
+            LintDirError::CoverArtInvalid(_) => "cover_art_invalid",
+            LintDirError::CoverArtMissing => "cover_art_missing",
+            LintDirError::CoverArtNotSquare => "cover_art_not_square",
+            LintDirError::CoverArtTooBig => "cover_art_too_big",
+            LintDirError::CoverArtTooSmall => "cover_art_too_small",
+            LintDirError::DiscsNotContiguous => "discs_not_contiguous",
+            LintDirError::DiscTracksNotFromOne(_) => "disc_tracks_not_from_one",
+            LintDirError::InconsistentTags(_) => "inconsistent_tags",
+            LintDirError::InvalidDirName => "invalid_dir_name",
+            LintDirError::MixedFileTypes => "mixed_file_types",
+            LintDirError::UnsequencedFile => "unsequenced_file",
+        }
+    }
+
+    pub fn value(&self) -> Value {
+        match self {
+            LintDirError::BadFile(items) | LintDirError::InconsistentTags(items) => {
+                let mut vec: Vec<_> = items.iter().collect();
+                vec.sort();
+                json!(vec)
+            }
+            LintDirError::CoverArtInvalid(err) => json!(err),
+            LintDirError::DiscTracksNotFromOne(discs) => json!(discs),
+            _ => Value::Null,
+        }
+    }
 }
 
-pub fn run(dirlist: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+pub fn run(
+    dirlist: &[Utf8PathBuf],
+    recurse: bool,
+    format: OutputFormat,
+    use_cache: bool,
+    opts: &GlobalOpts,
+) -> anyhow::Result<bool> {
+    // Verbose output goes to stdout, where it would break a JSON stream.
+    let opts = &GlobalOpts {
+        verbose: opts.verbose && format == OutputFormat::Text,
+        ..opts.clone()
+    };
     let config = load_config(&opts.config)?;
+    config.check_rule_names(&lint::known_rules())?;
     let dirs_to_list: Vec<Utf8PathBuf> = dirlist.iter().map(Utf8PathBuf::from).collect();
     let mut ret_code = true;
     let dirs = dir::expand_dir_list(&dirs_to_list, recurse);
     err_if_empty!(dirs);
 
-    let pb = if recurse {
+    let mut report = JsonReport::new(format);
+    let cache_file = use_cache.then(|| default_cache_dir().join("lintdir.json"));
+    let mut cache = LintCache::load(cache_file.as_deref(), config.checks_hash(), "lintdir");
+
+    let pb = if recurse && format == OutputFormat::Text {
         MaybeProgress::Bar(ProgressBar::new(dirs.len() as u64))
     } else {
         MaybeProgress::Direct
@@ -93,42 +181,81 @@ pub fn run(dirlist: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow:
     for dir in dirs {
         pb.inc(1);
         let dir = dir.canonicalize_utf8()?;
-        if let Some(res) = lint_dir(&dir, opts)? {
+        if let Some(res) = cached_lint_dir(&dir, config.get_disc_style(), &mut cache, opts)? {
             let results = filter_results(&dir, res, &config);
             let problems: Vec<_> = results.iter().filter_map(Some).collect();
 
             if !problems.is_empty() {
-                display_problems(&dir, &problems, &pb);
-                ret_code = false;
+                match format {
+                    OutputFormat::Text => display_problems(&dir, &problems, &config, &pb),
+                    _ => report.add(json_entry(&dir, &problems, &config)),
+                }
+
+                if has_errors(&dir, &problems, &config) {
+                    ret_code = false;
+                }
             }
         }
     }
 
+    if let Err(e) = cache.save() {
+        eprintln!("Could not save lintdir cache: {e}");
+    }
+
     pb.finish();
+    report.finish();
     Ok(ret_code)
 }
 
-fn display_problems(dir: &Utf8Path, problems: &Vec<&CheckResult>, pb: &MaybeProgress) {
+fn display_problems(
+    dir: &Utf8Path,
+    problems: &Vec<&CheckResult>,
+    config: &Config,
+    pb: &MaybeProgress,
+) {
     pb.println(&format!("{}", dir.to_string().bold()));
 
     for p in problems {
         match p {
             CheckResult::Good => (),
-            CheckResult::Bad(problem) => pb.println(&format!("  {}", problem.message())),
+            CheckResult::Bad(problem) => match config.get_rule_severity(problem.code(), dir) {
+                Severity::Warn => pb.println(&format!("  {} (warning)", problem.message())),
+                _ => pb.println(&format!("  {}", problem.message())),
+            },
         }
     }
 
     pb.println("");
 }
 
-fn is_dir_excluded(file: &Utf8Path, list: Option<&HashSet<String>>) -> bool {
-    match list {
-        Some(rules) => {
-            let file_str = file.to_string();
-            rules.iter().any(|rule| file_str.contains(rule))
+fn has_errors(dir: &Utf8Path, problems: &[&CheckResult], config: &Config) -> bool {
+    problems.iter().any(|p| match p {
+        CheckResult::Good => false,
+        CheckResult::Bad(problem) => {
+            config.get_rule_severity(problem.code(), dir) == Severity::Error
         }
-        None => false,
-    }
+    })
+}
+
+fn json_entry(dir: &Utf8Path, problems: &Vec<&CheckResult>, config: &Config) -> Value {
+    let problems: Vec<Value> = problems
+        .iter()
+        .filter_map(|p| match p {
+            CheckResult::Good => None,
+            CheckResult::Bad(problem) => Some(json!({
+                "code": problem.code(),
+                "severity": config.get_rule_severity(problem.code(), dir),
+                "message": problem.message(),
+                "value": problem.value(),
+            })),
+        })
+        .collect();
+
+    json!({ "path": dir.as_str(), "problems": problems })
+}
+
+fn is_dir_excluded(file: &Utf8Path, list: Option<&PathMatcher>) -> bool {
+    list.is_some_and(|rules| rules.is_match(file))
 }
 
 fn filter_results(dir: &Utf8Path, results: Vec<CheckResult>, config: &Config) -> Vec<CheckResult> {
@@ -143,11 +270,70 @@ fn filter_results(dir: &Utf8Path, results: Vec<CheckResult>, config: &Config) ->
             }
             _ => true,
         })
+        .filter(|r| match r {
+            CheckResult::Bad(problem) => {
+                config.get_rule_severity(problem.code(), dir) != Severity::Off
+            }
+            CheckResult::Good => true,
+        })
         .collect()
 }
 
-fn lint_dir(dir: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<Option<Vec<CheckResult>>> {
+// Results depend on every file in the directory, so any of them changing, appearing or vanishing
+// means linting again. A multi-disc album also depends on everything in its disc directories.
+fn cached_lint_dir(
+    dir: &Utf8Path,
+    disc_style: DiscStyle,
+    cache: &mut LintCache<Option<Vec<LintDirError>>>,
+    opts: &GlobalOpts,
+) -> anyhow::Result<Option<Vec<CheckResult>>> {
+    let mut files: Vec<Utf8PathBuf> = files_in_dir(dir)?.into_iter().collect();
+
+    for disc_dir in disc_dirs(dir)?.into_values() {
+        files.extend(files_in_dir(&disc_dir)?);
+        files.push(disc_dir);
+    }
+
+    let fingerprint = Fingerprint::new(&files)?;
+
+    if let Some(problems) = cache.get(dir, &fingerprint) {
+        verbose!(opts, "{} is unchanged since it was last linted", dir);
+        return Ok(problems
+            .as_ref()
+            .map(|p| p.iter().cloned().map(CheckResult::Bad).collect()));
+    }
+
+    let results = lint_dir(dir, disc_style, opts)?;
+    let problems = results.as_ref().map(|r| {
+        r.iter()
+            .filter_map(|r| match r {
+                CheckResult::Bad(problem) => Some(problem.clone()),
+                CheckResult::Good => None,
+            })
+            .collect()
+    });
+
+    cache.insert(dir, fingerprint, problems);
+    Ok(results)
+}
+
+fn lint_dir(
+    dir: &Utf8Path,
+    disc_style: DiscStyle,
+    opts: &GlobalOpts,
+) -> anyhow::Result<Option<Vec<CheckResult>>> {
     let all_files = files_in_dir(dir)?;
+    let discs = disc_dirs(dir)?;
+
+    if !discs.is_empty() {
+        verbose!(opts, "Linting {} as a multi-disc album", dir);
+        let results = run_album_checks(dir, &all_files, &discs, hierarchy(dir)?, disc_style)?
+            .into_iter()
+            .filter(|r| matches!(r, CheckResult::Bad(_)))
+            .collect();
+        return Ok(Some(results));
+    }
+
     let all_metadata = metadata_for(&all_files)?;
 
     if all_metadata.is_empty() {
@@ -160,22 +346,52 @@ fn lint_dir(dir: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<Option<Vec<Chec
 
     verbose!(opts, "Linting {}", dir);
 
+    let results: Vec<_> = run_checks(dir, &all_files, &all_metadata, hierarchy(dir)?, disc_style)
+        .into_iter()
+        .filter(|r| matches!(r, CheckResult::Bad(_)))
+        .collect();
+
+    Ok(Some(results))
+}
+
+fn hierarchy(dir: &Utf8Path) -> anyhow::Result<Hierarchy> {
     let components: Vec<_> = dir.components().map(|c| c.as_str()).collect();
 
-    let hierarchy = if components.contains(&"flac") {
-        Hierarchy::Flac
+    if components.contains(&"flac") {
+        Ok(Hierarchy::Flac)
     } else if components.contains(&"mp3") {
-        Hierarchy::Mp3
+        Ok(Hierarchy::Mp3)
     } else {
-        return Err(anyhow!("unable to determine media hierarchy from {}", dir));
-    };
+        Err(anyhow!("unable to determine media hierarchy from {}", dir))
+    }
+}
 
-    let results: Vec<_> = run_checks(dir, &all_files, &all_metadata, hierarchy)
-        .into_iter()
-        .filter(|r| matches!(r, CheckResult::Bad(_)))
-        .collect();
+fn is_disc_dir(dir: &Utf8Path) -> bool {
+    dir.file_name().is_some_and(|n| DISC_DIR_REGEX.is_match(n))
+}
 
-    Ok(Some(results))
+// Disc directories in the given directory, by disc number.
+fn disc_dirs(dir: &Utf8Path) -> anyhow::Result<BTreeMap<u32, Utf8PathBuf>> {
+    let mut ret = BTreeMap::new();
+
+    for entry in dir.read_dir_utf8()? {
+        let path = entry?.into_path();
+
+        if !path.is_dir() {
+            continue;
+        }
+
+        let number = path
+            .file_name()
+            .and_then(|n| DISC_DIR_REGEX.captures(n))
+            .and_then(|c| c[1].parse::<u32>().ok());
+
+        if let Some(number) = number {
+            ret.insert(number, path);
+        }
+    }
+
+    Ok(ret)
 }
 
 fn run_checks(
@@ -183,20 +399,61 @@ fn run_checks(
     all_files: &HashSet<Utf8PathBuf>,
     all_metadata: &[AurMetadata],
     hierarchy: Hierarchy,
+    disc_style: DiscStyle,
 ) -> Vec<CheckResult> {
     let mut checks = vec![
         is_correctly_named(dir, false),
         has_no_bad_files(dir, all_files, &hierarchy),
         has_right_file_count(all_files),
-        has_consistent_tags(dir, all_metadata),
         all_files_are_same_type(all_metadata),
     ];
 
+    // A disc's tags, and its artwork, are checked along with the rest of its album.
+    if !is_disc_dir(dir) {
+        checks.push(has_consistent_tags(dir, all_metadata, disc_style));
+
+        if hierarchy == Hierarchy::Flac {
+            checks.push(has_suitable_cover_art(dir));
+        }
+    }
+
+    checks
+}
+
+// A directory of disc_n directories is one album. Each disc is also linted on its own, for the
+// things which only concern that disc.
+fn run_album_checks(
+    dir: &Utf8Path,
+    all_files: &HashSet<Utf8PathBuf>,
+    discs: &BTreeMap<u32, Utf8PathBuf>,
+    hierarchy: Hierarchy,
+    disc_style: DiscStyle,
+) -> anyhow::Result<Vec<CheckResult>> {
+    let mut disc_files = BTreeMap::new();
+    let mut all_metadata = Vec::new();
+
+    for (number, disc_dir) in discs {
+        let files = files_in_dir(disc_dir)?;
+        all_metadata.extend(metadata_for(&files)?);
+        disc_files.insert(*number, files);
+    }
+
+    let mut checks = vec![
+        is_correctly_named(dir, true),
+        has_only_artwork(dir, all_files, &hierarchy),
+        discs_are_contiguous(discs),
+        discs_start_at_track_one(&disc_files),
+    ];
+
+    if !all_metadata.is_empty() {
+        checks.push(has_consistent_album_tags(dir, &all_metadata, disc_style));
+    }
+
     if hierarchy == Hierarchy::Flac {
         checks.push(has_suitable_cover_art(dir));
     }
 
-    checks
+    Ok(checks)
 }
 
 // An album directory should be of the form 'artist_name.album_name', but can have sub-directories.
@@ -230,7 +487,7 @@ fn has_no_bad_files(
         .map(|f| f.to_path_buf())
         .collect();
 
-    if hierarchy == &Hierarchy::Flac {
+    if hierarchy == &Hierarchy::Flac && !is_disc_dir(dir) {
         let artwork = dir.join(ARTWORK_FILENAME);
         non_media.remove(&artwork);
     }
@@ -244,6 +501,55 @@ fn has_no_bad_files(
     }
 }
 
+// Tracks belong in the disc directories, so all an album directory may hold is its artwork.
+fn has_only_artwork(
+    dir: &Utf8Path,
+    file_list: &HashSet<Utf8PathBuf>,
+    hierarchy: &Hierarchy,
+) -> CheckResult {
+    let mut stray = file_list.clone();
+
+    if hierarchy == &Hierarchy::Flac {
+        stray.remove(&dir.join(ARTWORK_FILENAME));
+    }
+
+    if stray.is_empty() {
+        CheckResult::Good
+    } else {
+        CheckResult::Bad(LintDirError::BadFile(
+            stray.iter().map(|f| f.to_string()).collect(),
+        ))
+    }
+}
+
+fn discs_are_contiguous(discs: &BTreeMap<u32, Utf8PathBuf>) -> CheckResult {
+    if discs.keys().copied().eq(1..=discs.len() as u32) {
+        CheckResult::Good
+    } else {
+        CheckResult::Bad(LintDirError::DiscsNotContiguous)
+    }
+}
+
+fn discs_start_at_track_one(disc_files: &BTreeMap<u32, HashSet<Utf8PathBuf>>) -> CheckResult {
+    let bad_discs: Vec<u32> = disc_files
+        .iter()
+        .filter(|(_, files)| {
+            dir::media_files(*files)
+                .iter()
+                .filter_map(|f| number_from_filename(f.file_name().unwrap()).map(|(_, num)| num))
+                .min()
+                != Some(1)
+        })
+        .map(|(number, _)| *number)
+        .collect();
+
+    if bad_discs.is_empty() {
+        CheckResult::Good
+    } else {
+        CheckResult::Bad(LintDirError::DiscTracksNotFromOne(bad_discs))
+    }
+}
+
 fn has_right_file_count(file_list: &HashSet<Utf8PathBuf>) -> CheckResult {
     let media = dir::media_files(file_list);
     let file_nums: Vec<u32> = media
@@ -303,23 +609,75 @@ fn inconsistencies_are_featuring(metadata: &[AurMetadata]) -> bool {
     primaries.iter().all(|m| m == ref_artist)
 }
 
-fn has_consistent_tags(dir: &Utf8Path, metadata: &[AurMetadata]) -> CheckResult {
-    let mut inconsistent_tags: HashSet<String> = HashSet::new();
+// With the suffix style, the album check covers the disc number too.
+fn has_consistent_tags(
+    dir: &Utf8Path,
+    metadata: &[AurMetadata],
+    disc_style: DiscStyle,
+) -> CheckResult {
+    let mut inconsistent_tags = inconsistent_common_tags(dir, metadata);
 
     if !metadata
         .iter()
-        .all(|m| m.tags.artist == metadata[0].tags.album)
-        && !looks_like_compilation(dir, false)
-        && !inconsistencies_are_featuring(metadata)
+        .all(|m| m.tags.album == metadata[0].tags.album)
     {
-        inconsistent_tags.insert("artist".to_owned());
+        inconsistent_tags.insert("album".to_owned());
+    }
+
+    if disc_style == DiscStyle::Tag
+        && !metadata
+            .iter()
+            .all(|m| m.tags.disc == metadata[0].tags.disc)
+    {
+        inconsistent_tags.insert("disc".to_owned());
+    }
+
+    if inconsistent_tags.is_empty() {
+        CheckResult::Good
+    } else {
+        CheckResult::Bad(LintDirError::InconsistentTags(inconsistent_tags))
+    }
+}
+
+// Across the discs of an album, disc numbers differ by design, and with the suffix style, so do
+// album tags.
+fn has_consistent_album_tags(
+    dir: &Utf8Path,
+    metadata: &[AurMetadata],
+    disc_style: DiscStyle,
+) -> CheckResult {
+    let mut inconsistent_tags = inconsistent_common_tags(dir, metadata);
+
+    let albums: HashSet<_> = metadata
+        .iter()
+        .map(|m| match disc_style {
+            DiscStyle::AlbumSuffix => DISC_SUFFIX_REGEX.replace(&m.tags.album, ""),
+            DiscStyle::Tag => m.tags.album.as_str().into(),
+        })
+        .collect();
+
+    if albums.len() > 1 {
+        inconsistent_tags.insert("album".to_owned());
     }
 
+    if inconsistent_tags.is_empty() {
+        CheckResult::Good
+    } else {
+        CheckResult::Bad(LintDirError::InconsistentTags(inconsistent_tags))
+    }
+}
+
+// Artist, year and genre should be the same on every track, however many discs there are.
+fn inconsistent_common_tags(dir: &Utf8Path, metadata: &[AurMetadata]) -> HashSet<String> {
+    let mut inconsistent_tags: HashSet<String> = HashSet::new();
+
     if !metadata
         .iter()
-        .all(|m| m.tags.album == metadata[0].tags.album)
+        .all(|m| m.tags.artist == metadata[0].tags.artist)
+        && !looks_like_compilation(dir, false)
+        && !inconsistencies_are_featuring(metadata)
     {
-        inconsistent_tags.insert("album".to_owned());
+        inconsistent_tags.insert("artist".to_owned());
     }
 
     if !metadata
@@ -336,11 +694,7 @@ fn has_consistent_tags(dir: &Utf8Path, metadata: &[AurMetadata]) -> CheckResult
         inconsistent_tags.insert("genre".to_owned());
     }
 
-    if inconsistent_tags.is_empty() {
-        CheckResult::Good
-    } else {
-        CheckResult::Bad(LintDirError::InconsistentTags(inconsistent_tags))
-    }
+    inconsistent_tags
 }
 
 fn has_suitable_cover_art(dir: &Utf8Path) -> CheckResult {
@@ -396,8 +750,108 @@ fn metadata_for(files: &HashSet<Utf8PathBuf>) -> anyhow::Result<Vec<AurMetadata>
 #[cfg(test)]
 mod test {
     use super::*;
+    use camino_tempfile_ext::prelude::*;
     use snltest::fixture;
 
+    #[test]
+    fn test_json_entry() {
+        let problems = [
+            CheckResult::Bad(LintDirError::InconsistentTags(HashSet::from([
+                "year".to_owned(),
+                "album".to_owned(),
+            ]))),
+            CheckResult::Bad(LintDirError::CoverArtTooSmall),
+        ];
+
+        assert_eq!(
+            json!({
+                "path": "/music/a.b",
+                "problems": [
+                    {
+                        "code": "inconsistent_tags",
+                        "severity": "error",
+                        "message": "Inconsistent tags: album, year",
+                        "value": ["album", "year"],
+                    },
+                    {
+                        "code": "cover_art_too_small",
+                        "severity": "warn",
+                        "message": "Cover art is too small",
+                        "value": null,
+                    },
+                ],
+            }),
+            json_entry(
+                Utf8Path::new("/music/a.b"),
+                &problems.iter().collect(),
+                &load_config(&fixture!("config/rules.toml")).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_lint_dir_multi_disc() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let album = tmp.path().join("flac/tester.multi_disc");
+        let opts = GlobalOpts::default();
+
+        for disc in ["disc_1", "disc_2"] {
+            let disc_dir = tmp.child(format!("flac/tester.multi_disc/{disc}"));
+            disc_dir.create_dir_all().unwrap();
+            disc_dir
+                .copy_from(
+                    perfect_flac_dir(),
+                    &["01.tester.perfect_1.flac", "02.tester.perfect_2.flac"],
+                )
+                .unwrap();
+        }
+
+        tmp.child("flac/tester.multi_disc")
+            .copy_from(perfect_flac_dir(), &["cover.jpg"])
+            .unwrap();
+
+        assert_eq!(
+            Some(vec![]),
+            lint_dir(&album, DiscStyle::AlbumSuffix, &opts).unwrap()
+        );
+        assert_eq!(
+            Some(vec![]),
+            lint_dir(&album.join("disc_2"), DiscStyle::AlbumSuffix, &opts).unwrap()
+        );
+
+        let disc_dir = tmp.child("flac/tester.multi_disc/disc_4");
+        disc_dir.create_dir_all().unwrap();
+        disc_dir
+            .copy_from(
+                perfect_flac_dir(),
+                &["02.tester.perfect_2.flac", "cover.jpg"],
+            )
+            .unwrap();
+        std::fs::rename(album.join("cover.jpg"), album.join("front.jpg")).unwrap();
+
+        assert_eq!(
+            Some(vec![
+                CheckResult::Bad(LintDirError::BadFile(HashSet::from([album
+                    .join("front.jpg")
+                    .to_string()]))),
+                CheckResult::Bad(LintDirError::DiscsNotContiguous),
+                CheckResult::Bad(LintDirError::DiscTracksNotFromOne(vec![4])),
+                CheckResult::Bad(LintDirError::CoverArtMissing),
+            ]),
+            lint_dir(&album, DiscStyle::AlbumSuffix, &opts).unwrap()
+        );
+
+        assert_eq!(
+            Some(vec![
+                CheckResult::Bad(LintDirError::BadFile(HashSet::from([album
+                    .join("disc_4/cover.jpg")
+                    .to_string()]))),
+                CheckResult::Bad(LintDirError::BadFileCount),
+            ]),
+            lint_dir(&album.join("disc_4"), DiscStyle::AlbumSuffix, &opts).unwrap()
+        );
+    }
+
     #[test]
     fn test_all_files_are_same_type() {
         assert_eq!(
@@ -455,16 +909,60 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_has_consistent_album_tags() {
+        let album_metadata = |name: &str| {
+            let dir = fixture!("commands/lintdir/mp3").join(name);
+            let mut files = files_in_dir(&dir.join("disc_1")).unwrap();
+            files.extend(files_in_dir(&dir.join("disc_2")).unwrap());
+            (dir, metadata_for(&files).unwrap())
+        };
+
+        let (dir, metadata) = album_metadata("tester.discs_featuring");
+        assert_eq!(
+            CheckResult::Good,
+            has_consistent_album_tags(&dir, &metadata, DiscStyle::AlbumSuffix)
+        );
+
+        let (dir, metadata) = album_metadata("tester.discs_mixed_tags");
+        assert_eq!(
+            CheckResult::Bad(LintDirError::InconsistentTags(HashSet::from([
+                "artist".to_owned(),
+                "genre".to_owned(),
+                "year".to_owned()
+            ]))),
+            has_consistent_album_tags(&dir, &metadata, DiscStyle::AlbumSuffix)
+        );
+
+        assert_eq!(
+            CheckResult::Bad(LintDirError::InconsistentTags(HashSet::from([
+                "album".to_owned(),
+                "artist".to_owned(),
+                "genre".to_owned(),
+                "year".to_owned()
+            ]))),
+            has_consistent_album_tags(&dir, &metadata, DiscStyle::Tag)
+        );
+    }
+
     #[test]
     fn test_has_consistent_tags() {
         assert_eq!(
             CheckResult::Good,
-            has_consistent_tags(&perfect_flac_dir(), &metadata_for(&perfect_flac()).unwrap())
+            has_consistent_tags(
+                &perfect_flac_dir(),
+                &metadata_for(&perfect_flac()).unwrap(),
+                DiscStyle::AlbumSuffix
+            )
         );
 
         assert_eq!(
             CheckResult::Good,
-            has_consistent_tags(&perfect_mp3_dir(), &metadata_for(&perfect_mp3()).unwrap())
+            has_consistent_tags(
+                &perfect_mp3_dir(),
+                &metadata_for(&perfect_mp3()).unwrap(),
+                DiscStyle::AlbumSuffix
+            )
         );
 
         assert_eq!(
@@ -475,7 +973,8 @@ mod test {
                     &files_in_dir(&fixture!("commands/lintdir/mp3/tester.perfect--featuring"))
                         .unwrap()
                 )
-                .unwrap()
+                .unwrap(),
+                DiscStyle::AlbumSuffix
             )
         );
 
@@ -487,7 +986,8 @@ mod test {
                     &files_in_dir(&fixture!("commands/lintdir/mp3/artist--band.split_single"))
                         .unwrap()
                 )
-                .unwrap()
+                .unwrap(),
+                DiscStyle::AlbumSuffix
             )
         );
 
@@ -498,7 +998,8 @@ mod test {
                 &metadata_for(
                     &files_in_dir(&fixture!("commands/lintdir/mp3/various.compilation")).unwrap()
                 )
-                .unwrap()
+                .unwrap(),
+                DiscStyle::AlbumSuffix
             )
         );
 
@@ -512,7 +1013,8 @@ mod test {
                     &files_in_dir(&fixture!("commands/lintdir/flac/tester.different_album"))
                         .unwrap()
                 )
-                .unwrap()
+                .unwrap(),
+                DiscStyle::AlbumSuffix
             )
         );
 
@@ -530,7 +1032,8 @@ mod test {
                     ))
                     .unwrap()
                 )
-                .unwrap()
+                .unwrap(),
+                DiscStyle::AlbumSuffix
             )
         );
     }
@@ -655,6 +1158,30 @@ mod test {
         fixture!("commands/lintdir/mp3/tester.perfect")
     }
 
+    #[test]
+    fn test_rules() {
+        let problems = [
+            LintDirError::BadFile(HashSet::new()),
+            LintDirError::BadFileCount,
+            LintDirError::CoverArtInvalid(String::new()),
+            LintDirError::CoverArtMissing,
+            LintDirError::CoverArtNotSquare,
+            LintDirError::CoverArtTooBig,
+            LintDirError::CoverArtTooSmall,
+            LintDirError::DiscsNotContiguous,
+            LintDirError::DiscTracksNotFromOne(Vec::new()),
+            LintDirError::InconsistentTags(HashSet::new()),
+            LintDirError::InvalidDirName,
+            LintDirError::MixedFileTypes,
+            LintDirError::UnsequencedFile,
+        ];
+
+        assert_eq!(
+            RULES.to_vec(),
+            problems.iter().map(|p| p.code()).collect::<Vec<_>>()
+        );
+    }
+
     fn perfect_mp3() -> HashSet<Utf8PathBuf> {
         files_in_dir(&perfect_mp3_dir()).unwrap()
     }
@@ -0,0 +1,161 @@
+use crate::err_if_empty;
+use crate::utils::loudness::{Loudness, ReplayGain};
+use crate::utils::metadata::{AurMetadata, in_tracks};
+use crate::utils::tagger::Tagger;
+use crate::utils::types::GlobalOpts;
+use crate::utils::{decoder, dir};
+use anyhow::anyhow;
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+
+// Album gain is worked out from all the given files in each directory, so give it whole albums.
+// Loose tracks don't get album gain.
+pub fn run(files: &[Utf8PathBuf], recurse: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let files: Vec<Utf8PathBuf> = dir::media_files(&dir::expand_file_list(files, recurse)?)
+        .into_iter()
+        .filter(|f| decoder::can_decode(f))
+        .collect();
+    err_if_empty!(files);
+
+    let mut ret_code = true;
+
+    for (dir, files) in files_by_dir(&files) {
+        if let Err(e) = tag_album(&files, !in_tracks(&files[0]), opts) {
+            eprintln!("Error measuring {dir}: {e}");
+            ret_code = false;
+        }
+    }
+
+    Ok(ret_code)
+}
+
+fn files_by_dir(files: &[Utf8PathBuf]) -> BTreeMap<Utf8PathBuf, Vec<Utf8PathBuf>> {
+    let mut ret: BTreeMap<Utf8PathBuf, Vec<Utf8PathBuf>> = BTreeMap::new();
+
+    for file in files {
+        let dir = file.parent().unwrap_or(Utf8Path::new(".")).to_path_buf();
+        ret.entry(dir).or_default().push(file.clone());
+    }
+
+    ret
+}
+
+fn tag_album(files: &[Utf8PathBuf], with_album: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let tracks = files
+        .par_iter()
+        .map(|f| Loudness::from_path(f))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let album = with_album.then(|| Loudness::combine(&tracks));
+    let mut changes = false;
+
+    for (file, track) in files.iter().zip(tracks.iter()) {
+        let gain = match ReplayGain::new(track, album.as_ref()) {
+            Some(gain) => gain,
+            None => return Err(anyhow!("{} is too short or quiet to measure", file)),
+        };
+
+        if tag_file(file, &gain, opts)? {
+            changes = true;
+        }
+    }
+
+    Ok(changes)
+}
+
+fn tag_file(file: &Utf8Path, gain: &ReplayGain, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    if !opts.quiet {
+        println!("{}", file.to_string().bold());
+    }
+
+    if opts.noop {
+        for (name, value) in gain.tags() {
+            println!("{:>16} -> {}", name, value);
+        }
+        return Ok(false);
+    }
+
+    let info = AurMetadata::new(file)?;
+    Tagger::new(&info)?.set_loudness(gain, opts.quiet)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_files_by_dir() {
+        let files = vec![
+            Utf8PathBuf::from("/a/b/01.x.y.flac"),
+            Utf8PathBuf::from("/a/c/01.x.y.flac"),
+            Utf8PathBuf::from("/a/b/02.x.y.flac"),
+        ];
+
+        let grouped = files_by_dir(&files);
+
+        assert_eq!(2, grouped.len());
+        assert_eq!(2, grouped[Utf8Path::new("/a/b")].len());
+    }
+
+    #[test]
+    fn test_tag_file() {
+        let file_name = "test.flac";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file_name]).unwrap();
+        let file_under_test = tmp.path().join(file_name);
+
+        let gain = ReplayGain {
+            track_gain: 2.0,
+            track_peak: 0.25,
+            album_gain: None,
+            album_peak: None,
+        };
+
+        assert!(tag_file(&file_under_test, &gain, &GlobalOpts::default()).unwrap());
+        assert!(!tag_file(&file_under_test, &gain, &GlobalOpts::default()).unwrap());
+
+        let info = AurMetadata::new(&file_under_test).unwrap();
+
+        assert!(
+            info.rawtags
+                .contains(&("replaygain_track_gain".to_owned(), "2.00 dB".to_owned()))
+        );
+        assert_eq!(Some(gain), ReplayGain::from_rawtags(&info.rawtags));
+    }
+
+    #[test]
+    fn test_tag_file_replaces_lower_case_tags() {
+        let file_name = "test.flac";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file_name]).unwrap();
+        let file_under_test = tmp.path().join(file_name);
+
+        let mut tag = metaflac::Tag::read_from_path(&file_under_test).unwrap();
+        tag.set_vorbis("replaygain_track_gain", vec!["-1.00 dB"]);
+        tag.save().unwrap();
+
+        let gain = ReplayGain {
+            track_gain: 2.0,
+            track_peak: 0.25,
+            album_gain: None,
+            album_peak: None,
+        };
+
+        assert!(tag_file(&file_under_test, &gain, &GlobalOpts::default()).unwrap());
+
+        let info = AurMetadata::new(&file_under_test).unwrap();
+
+        assert_eq!(
+            vec!["2.00 dB"],
+            info.rawtags
+                .iter()
+                .filter(|(k, _)| k == "replaygain_track_gain")
+                .map(|(_, v)| v.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+}
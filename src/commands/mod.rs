@@ -1,20 +1,26 @@
 pub mod albumdisc;
+pub mod artdupes;
 pub mod artfix;
+pub mod authenticity;
 pub mod cdq;
+pub mod config;
 pub mod copytags;
 pub mod dupes;
+pub mod extractart;
 pub mod flac2mp3;
 pub mod get;
 pub mod info;
 pub mod itag;
 pub mod lint;
 pub mod lintdir;
+pub mod loudness;
 pub mod ls;
 pub mod mp3dir;
 pub mod name2num;
 pub mod name2tag;
 pub mod namecheck;
 pub mod num2name;
+pub mod pairs;
 pub mod reencode;
 pub mod renumber;
 pub mod retitle;
@@ -1,26 +1,30 @@
 use crate::err_if_empty;
+use crate::utils::config::load_config;
 use crate::utils::dir;
 use crate::utils::helpers::check_hierarchy;
-use crate::utils::mp3_encoder::{TranscodeCmds, mp3_dir_from, sync_dir, transcode_cmds};
+use crate::utils::mp3_encoder::{TranscodeCmds, mp3_transcode_cmds, sync_dir, target_dir_from};
+use crate::utils::sync_state::SyncState;
 use crate::utils::types::{GlobalOpts, Mp3dirOpts};
 use camino::{Utf8Path, Utf8PathBuf};
 
 pub fn run(
     dirlist: &[Utf8PathBuf],
     cmd_opts: &Mp3dirOpts,
+    state_file: &Utf8Path,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
-    let cmds = transcode_cmds()?;
+    let cmds = mp3_transcode_cmds(load_config(&opts.config)?.get_profile(&cmd_opts.profile)?)?;
     let root = cmd_opts.root.canonicalize_utf8()?;
     let mut ret_code = false;
 
     check_hierarchy(&root)?;
     let dirs = dir::expand_dir_list(dirlist, cmd_opts.recurse);
     err_if_empty!(dirs);
+    let mut state = SyncState::load(state_file);
 
     for dir in dirs {
         if let Ok(flac_dir) = dir.canonicalize_utf8() {
-            if let Err(e) = mp3dir(&flac_dir, &cmds, cmd_opts, opts) {
+            if let Err(e) = mp3dir(&flac_dir, &root, &cmds, cmd_opts, &mut state, opts) {
                 eprintln!("Error transcoding in {dir}: {e}");
                 ret_code = false;
             }
@@ -30,19 +34,30 @@ pub fn run(
         }
     }
 
+    if !opts.noop {
+        state.save()?;
+    }
+
     Ok(ret_code)
 }
 
 fn mp3dir(
     flac_dir: &Utf8Path,
+    root: &Utf8Path,
     cmds: &TranscodeCmds,
     cmd_opts: &Mp3dirOpts,
+    state: &mut SyncState,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
     let mut synced = 0;
-    let mp3_dir = mp3_dir_from(flac_dir, cmd_opts);
+    let mp3_dir = target_dir_from(
+        flac_dir,
+        root,
+        "mp3",
+        cmd_opts.suffix.then(|| cmds.profile.suffix()),
+    )?;
 
-    if sync_dir(flac_dir, &mp3_dir, cmds, cmd_opts, opts)? {
+    if sync_dir(flac_dir, &mp3_dir, cmds, cmd_opts, state, opts)? {
         synced += 1;
     }
 
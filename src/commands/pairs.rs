@@ -0,0 +1,353 @@
+use crate::commands::copytags::partner_path;
+use crate::utils::config::{PathMatcher, load_config};
+use crate::utils::dir;
+use crate::utils::helpers;
+use crate::utils::metadata::{AurMetadata, AurTime};
+use crate::utils::types::GlobalOpts;
+use crate::verbose;
+use camino::{Utf8Path, Utf8PathBuf};
+use colored::Colorize;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+const COMPARED_TAGS: [&str; 7] = ["artist", "album", "title", "t_num", "disc", "year", "genre"];
+
+#[derive(Debug)]
+#[cfg_attr(test, derive(PartialEq, Eq))]
+enum PairError {
+    DurationMismatch(u64, u64),
+    Mp3Older,
+    NoFlac,
+    NoMp3,
+    TagDrift(String, String, String),
+    UnreadableMp3,
+}
+
+impl PairError {
+    pub fn message(&self) -> String {
+        match self {
+            PairError::DurationMismatch(flac, mp3) => format!(
+                "Durations differ: FLAC is {}, MP3 is {}",
+                AurTime::from_seconds(*flac).formatted,
+                AurTime::from_seconds(*mp3).formatted
+            ),
+            PairError::Mp3Older => "MP3 is older than FLAC".to_owned(),
+            PairError::NoFlac => "No FLAC".to_owned(),
+            PairError::NoMp3 => "No MP3".to_owned(),
+            PairError::TagDrift(tag, flac, mp3) => {
+                format!(
+                    "{} tag differs: FLAC has '{}', MP3 has '{}'",
+                    tag, flac, mp3
+                )
+            }
+            PairError::UnreadableMp3 => "MP3 audio cannot be read".to_owned(),
+        }
+    }
+}
+
+// Everything is read-only. copytags and syncflac fix what this finds. Anything syncflac is told
+// to ignore is skipped, in either tree, because it isn't expected to have a partner.
+pub fn run(root: &Utf8Path, tolerance: u64, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let root = root.canonicalize_utf8()?;
+    helpers::check_hierarchy(&root)?;
+    let config = load_config(&opts.config)?;
+    let ignore_list = config.get_syncflac_list();
+
+    let mut ret_code = true;
+
+    for flac in tree_files(&root, "flac", ignore_list)? {
+        verbose!(opts, "Checking {}", flac);
+
+        match check_pair(&flac, tolerance) {
+            Ok(problems) if problems.is_empty() => (),
+            Ok(problems) => {
+                display_problems(&flac, &problems);
+                ret_code = false;
+            }
+            Err(e) => {
+                eprintln!("Error checking {flac}: {e}");
+                ret_code = false;
+            }
+        }
+    }
+
+    // Pairs were checked from the FLAC side, so all that's left is MP3s with no FLAC.
+    for mp3 in tree_files(&root, "mp3", ignore_list)? {
+        verbose!(opts, "Checking {}", mp3);
+
+        if !partner_path(&mp3, "mp3")?.exists() {
+            display_problems(&mp3, &[PairError::NoFlac]);
+            ret_code = false;
+        }
+    }
+
+    Ok(ret_code)
+}
+
+// Ignore rules are written for the FLAC tree, so an MP3 is also skipped if its FLAC would be.
+fn tree_files(
+    root: &Utf8Path,
+    filetype: &str,
+    ignore_list: Option<&PathMatcher>,
+) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let files = dir::media_files(&dir::expand_file_list(
+        std::slice::from_ref(&root.join(filetype)),
+        true,
+    )?);
+
+    let mut ret = Vec::new();
+
+    for file in files
+        .into_iter()
+        .filter(|f| f.extension() == Some(filetype))
+    {
+        let ignored = match ignore_list {
+            Some(list) => list.is_match(&file) || list.is_match(&partner_path(&file, filetype)?),
+            None => false,
+        };
+
+        if !ignored {
+            ret.push(file);
+        }
+    }
+
+    Ok(ret)
+}
+
+fn display_problems(file: &Utf8Path, problems: &[PairError]) {
+    println!("{}", file.to_string().bold());
+
+    for problem in problems {
+        println!("  {}", problem.message());
+    }
+
+    println!();
+}
+
+fn check_pair(flac: &Utf8Path, tolerance: u64) -> anyhow::Result<Vec<PairError>> {
+    let mp3 = partner_path(flac, "flac")?;
+
+    if !mp3.exists() {
+        return Ok(vec![PairError::NoMp3]);
+    }
+
+    let flac_info = AurMetadata::new(flac)?;
+    let mp3_info = AurMetadata::new(&mp3)?;
+    let mut ret = tag_drift(&flac_info, &mp3_info)?;
+
+    // An unknown duration is zero, which would make a nonsense comparison.
+    if mp3_info.time.is_unknown() {
+        ret.push(PairError::UnreadableMp3);
+    } else if !flac_info.time.is_unknown()
+        && durations_differ(flac_info.time.raw, mp3_info.time.raw, tolerance)
+    {
+        ret.push(PairError::DurationMismatch(
+            flac_info.time.raw,
+            mp3_info.time.raw,
+        ));
+    }
+
+    if fs::metadata(&mp3)?.mtime() < fs::metadata(flac)?.mtime() {
+        ret.push(PairError::Mp3Older);
+    }
+
+    Ok(ret)
+}
+
+fn tag_drift(flac: &AurMetadata, mp3: &AurMetadata) -> anyhow::Result<Vec<PairError>> {
+    let mut ret = Vec::new();
+
+    for tag in COMPARED_TAGS {
+        let flac_value = flac.get_tag(tag)?;
+        let mp3_value = mp3.get_tag(tag)?;
+
+        if flac_value != mp3_value {
+            ret.push(PairError::TagDrift(tag.to_owned(), flac_value, mp3_value));
+        }
+    }
+
+    Ok(ret)
+}
+
+fn durations_differ(flac: u64, mp3: u64, tolerance: u64) -> bool {
+    flac.abs_diff(mp3) > tolerance
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+
+    fn pair_tree(
+        flac_dir: Utf8PathBuf,
+        flac: &str,
+        mp3_dir: Utf8PathBuf,
+        mp3: &str,
+    ) -> Utf8TempDir {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.child("flac").create_dir_all().unwrap();
+        tmp.child("mp3").create_dir_all().unwrap();
+        tmp.child("flac").copy_from(flac_dir, &[flac]).unwrap();
+        tmp.child("mp3").copy_from(mp3_dir, &[mp3]).unwrap();
+        set_age(&tmp.path().join("flac").join(flac), 3600);
+        set_age(&tmp.path().join("mp3").join(mp3), 0);
+        tmp
+    }
+
+    fn set_age(file: &Utf8Path, seconds: u64) {
+        File::options()
+            .write(true)
+            .open(file)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(seconds))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_check_pair_matching() {
+        let tmp = pair_tree(
+            fixture!("commands/syncflac/flac/albums/abc/already.synced"),
+            "01.already.synced.flac",
+            fixture!("commands/syncflac/mp3/albums/abc/already.synced"),
+            "01.already.synced.mp3",
+        );
+
+        assert!(
+            check_pair(&tmp.path().join("flac/01.already.synced.flac"), 2)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_check_pair_drift() {
+        let tmp = pair_tree(
+            fixture!("commands/copytags/flac"),
+            "01.artist.song.flac",
+            fixture!("commands/copytags/mp3"),
+            "01.artist.song.mp3",
+        );
+
+        let flac = tmp.path().join("flac/01.artist.song.flac");
+        set_age(&tmp.path().join("mp3/01.artist.song.mp3"), 7200);
+
+        assert_eq!(
+            vec![
+                PairError::TagDrift(
+                    "album".to_owned(),
+                    "Copytags Test".to_owned(),
+                    "Wrong Album".to_owned()
+                ),
+                PairError::TagDrift(
+                    "title".to_owned(),
+                    "Right Title".to_owned(),
+                    "Wrong Title".to_owned()
+                ),
+                PairError::TagDrift("t_num".to_owned(), "1".to_owned(), "0".to_owned()),
+                PairError::TagDrift("year".to_owned(), "2021".to_owned(), "0".to_owned()),
+                PairError::Mp3Older,
+            ],
+            check_pair(&flac, 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_pair_unreadable_mp3() {
+        let tmp = pair_tree(
+            fixture!("commands/syncflac/flac/albums/abc/already.synced"),
+            "01.already.synced.flac",
+            fixture!("commands/syncflac/mp3/albums/abc/already.synced"),
+            "01.already.synced.mp3",
+        );
+
+        let mp3 = tmp.path().join("mp3/01.already.synced.mp3");
+        let tag = id3::Tag::read_from_path(&mp3).unwrap();
+        fs::write(&mp3, [0u8; 1024]).unwrap();
+        tag.write_to_path(&mp3, id3::Version::Id3v24).unwrap();
+
+        assert_eq!(
+            vec![PairError::UnreadableMp3],
+            check_pair(&tmp.path().join("flac/01.already.synced.flac"), 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_pair_no_mp3() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.child("flac")
+            .copy_from(fixture!("commands/copytags/flac"), &["01.artist.song.flac"])
+            .unwrap();
+
+        assert_eq!(
+            vec![PairError::NoMp3],
+            check_pair(&tmp.path().join("flac/01.artist.song.flac"), 2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tree_files() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let mp3 = fixture!("commands/copytags/mp3");
+        let files = &["01.artist.song.mp3"];
+        tmp.child("flac").create_dir_all().unwrap();
+
+        for dir in ["albums/abc/artist.album", "eps/abc/artist.ep"] {
+            tmp.child("mp3").child(dir).create_dir_all().unwrap();
+            tmp.child("mp3").child(dir).copy_from(&mp3, files).unwrap();
+        }
+
+        let config = load_config(&fixture!("config/pairs.toml")).unwrap();
+        let ignore_list = config.get_syncflac_list();
+        let album_mp3 = tmp
+            .path()
+            .join("mp3/albums/abc/artist.album/01.artist.song.mp3");
+
+        assert_eq!(
+            vec![album_mp3],
+            tree_files(tmp.path(), "mp3", ignore_list).unwrap()
+        );
+        assert_eq!(2, tree_files(tmp.path(), "mp3", None).unwrap().len());
+        assert!(
+            tree_files(tmp.path(), "flac", ignore_list)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_run_finds_mp3_with_no_flac() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.child("flac").create_dir_all().unwrap();
+        tmp.child("mp3")
+            .copy_from(fixture!("commands/copytags/mp3"), &["01.artist.song.mp3"])
+            .unwrap();
+
+        let opts = GlobalOpts {
+            config: fixture!("config/pairs.toml"),
+            ..Default::default()
+        };
+
+        assert!(!run(tmp.path(), 2, &opts).unwrap());
+    }
+
+    #[test]
+    fn test_durations_differ() {
+        assert!(!durations_differ(200, 200, 0));
+        assert!(!durations_differ(200, 202, 2));
+        assert!(!durations_differ(202, 200, 2));
+        assert!(durations_differ(200, 203, 2));
+        assert!(durations_differ(203, 200, 2));
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            "Durations differ: FLAC is 00:03:20, MP3 is 00:03:05",
+            PairError::DurationMismatch(200, 185).message()
+        );
+        assert_eq!("No FLAC", PairError::NoFlac.message());
+    }
+}
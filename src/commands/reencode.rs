@@ -1,53 +1,82 @@
+use crate::utils::config::load_config;
 use crate::utils::dir::{media_files, pathbuf_set};
-use crate::utils::external::find_binary;
-use anyhow::{anyhow, ensure, Context};
+use crate::utils::external::{find_binary, run_pipeline};
+use crate::utils::profile::{Encoder, Profile, REENCODE_FLAC_PROFILE, REENCODE_MP3_PROFILE};
+use crate::utils::types::GlobalOpts;
+use anyhow::{Context, anyhow};
 use camino::{Utf8Path, Utf8PathBuf};
 use rayon::prelude::*;
+use std::collections::HashMap;
 use std::fs;
-use std::process::Command;
 
-struct ReencodeCmds {
-    ffmpeg: Utf8PathBuf,
-    lame: Utf8PathBuf,
-}
+// Keyed by the extension of the files each profile makes, which must be the same as the
+// extension of the files it reencodes.
+type ReencodeProfiles = HashMap<String, Profile>;
+
+pub fn run(
+    files: &[Utf8PathBuf],
+    keep_originals: bool,
+    profile: Option<&str>,
+    opts: &GlobalOpts,
+) -> anyhow::Result<bool> {
+    let config = load_config(&opts.config)?;
 
-pub fn run(files: &[Utf8PathBuf], keep_originals: bool) -> anyhow::Result<bool> {
-    let cmds = ReencodeCmds {
-        lame: find_binary("lame")?,
-        ffmpeg: find_binary("ffmpeg")?,
+    let names = match profile {
+        Some(name) => vec![name],
+        None => vec![REENCODE_FLAC_PROFILE, REENCODE_MP3_PROFILE],
     };
 
-    media_files(&pathbuf_set(files))
+    let mut profiles = ReencodeProfiles::new();
+
+    for name in names {
+        let profile = config.get_profile(name)?;
+        profiles.insert(profile.extension.clone(), profile);
+    }
+
+    let files = media_files(&pathbuf_set(files));
+
+    for file in &files {
+        profile_for(file, &profiles)?;
+    }
+
+    let mut cmds: HashMap<Encoder, Utf8PathBuf> = HashMap::new();
+
+    for profile in profiles.values() {
+        cmds.insert(profile.encoder, find_binary(profile.encoder.binary())?);
+    }
+
+    files
         .par_iter()
-        .try_for_each(|f| reencode_file(f, keep_originals, &cmds))?;
+        .try_for_each(|f| reencode_file(f, keep_originals, &profiles, &cmds))?;
 
     Ok(true)
 }
 
-fn reencode_file(file: &Utf8Path, keep_originals: bool, cmds: &ReencodeCmds) -> anyhow::Result<()> {
-    println!("{}", file);
-
+fn profile_for<'a>(file: &Utf8Path, profiles: &'a ReencodeProfiles) -> anyhow::Result<&'a Profile> {
     let ext = file
         .extension()
         .context(format!("cannot extract extension for {}", file))?;
 
-    let stem = file
-        .file_stem()
-        .context(format!("cannot extract file stem for {}", file))?;
-
-    let dir = file
-        .parent()
-        .context(format!("cannot get directory for {}", file))?;
+    profiles
+        .get(ext)
+        .ok_or_else(|| anyhow!("cannot reencode {}: unexpected filetype: {}", file, ext))
+}
 
-    let target_file = dir.join(format!("{}.reencoded.{}", stem, ext));
+fn reencode_file(
+    file: &Utf8Path,
+    keep_originals: bool,
+    profiles: &ReencodeProfiles,
+    cmds: &HashMap<Encoder, Utf8PathBuf>,
+) -> anyhow::Result<()> {
+    println!("{}", file);
 
-    let success = match ext {
-        "flac" => reencode_flac(file, &target_file, &cmds.ffmpeg)?,
-        "mp3" => reencode_mp3(file, &target_file, &cmds.lame)?,
-        _ => return Err(anyhow!("unexpected filetype: {}", ext)),
-    };
+    let profile = profile_for(file, profiles)?;
+    let target_file = target_for(file, profile)?;
 
-    ensure!(success, "REENCODE FAILED");
+    run_pipeline(&target_file, |tmp| {
+        vec![profile.command(&cmds[&profile.encoder], &[], file, tmp)]
+    })
+    .context(format!("cannot reencode {}", file))?;
 
     if keep_originals {
         Ok(())
@@ -56,40 +85,60 @@ fn reencode_file(file: &Utf8Path, keep_originals: bool, cmds: &ReencodeCmds) ->
     }
 }
 
-fn reencode_flac(
-    src_file: &Utf8Path,
-    target_file: &Utf8Path,
-    ffmpeg: &Utf8Path,
-) -> anyhow::Result<bool> {
-    let result = Command::new(ffmpeg)
-        .arg("-hide_banner")
-        .arg("-loglevel")
-        .arg("error")
-        .arg("-y")
-        .arg("-i")
-        .arg(src_file)
-        .arg("-compression_level")
-        .arg("9")
-        .arg(target_file)
-        .status()?;
-
-    Ok(result.success())
+fn target_for(file: &Utf8Path, profile: &Profile) -> anyhow::Result<Utf8PathBuf> {
+    let stem = file
+        .file_stem()
+        .context(format!("cannot extract file stem for {}", file))?;
+
+    let dir = file
+        .parent()
+        .context(format!("cannot get directory for {}", file))?;
+
+    Ok(dir.join(format!(
+        "{}.{}.{}",
+        stem,
+        profile.suffix(),
+        profile.extension
+    )))
 }
 
-fn reencode_mp3(
-    src_file: &Utf8Path,
-    target_file: &Utf8Path,
-    lame: &Utf8Path,
-) -> anyhow::Result<bool> {
-    let result = Command::new(lame)
-        .arg("-q2")
-        .arg("--vbr-new")
-        .arg("--preset")
-        .arg("128")
-        .arg("--silent")
-        .arg(src_file)
-        .arg(target_file)
-        .status()?;
-
-    Ok(result.success())
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn default_profiles() -> ReencodeProfiles {
+        [REENCODE_FLAC_PROFILE, REENCODE_MP3_PROFILE]
+            .iter()
+            .map(|name| {
+                let profile = Profile::builtin(name).unwrap();
+                (profile.extension.clone(), profile)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_profile_for() {
+        let profiles = default_profiles();
+
+        assert_eq!(
+            REENCODE_MP3_PROFILE,
+            profile_for(Utf8Path::new("/a/01.b.c.mp3"), &profiles)
+                .unwrap()
+                .name
+        );
+        assert!(profile_for(Utf8Path::new("/a/01.b.c.ogg"), &profiles).is_err());
+        assert!(profile_for(Utf8Path::new("/a/01.b.c"), &profiles).is_err());
+    }
+
+    #[test]
+    fn test_target_for() {
+        assert_eq!(
+            Utf8PathBuf::from("/a/01.b.c.reencoded.flac"),
+            target_for(
+                Utf8Path::new("/a/01.b.c.flac"),
+                &Profile::builtin(REENCODE_FLAC_PROFILE).unwrap()
+            )
+            .unwrap()
+        );
+    }
 }
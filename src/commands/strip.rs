@@ -1,18 +1,41 @@
+use crate::commands::extractart::extract_artwork;
 use crate::err_if_empty;
+use crate::utils::config::load_config;
 use crate::utils::dir;
 use crate::utils::metadata::AurMetadata;
-use crate::utils::metadata::expected_tags;
+use crate::utils::metadata::{expected_tags, optional_tags};
 use crate::utils::tagger::Tagger;
+use crate::utils::types::GlobalOpts;
 use camino::{Utf8Path, Utf8PathBuf};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
-pub fn run(files: &[Utf8PathBuf]) -> anyhow::Result<bool> {
+pub fn run(files: &[Utf8PathBuf], extract_art: bool, opts: &GlobalOpts) -> anyhow::Result<bool> {
     let mut ret_code = true;
     let files = dir::media_files(&dir::pathbuf_set(files));
     err_if_empty!(files);
 
+    // If we can't save the artwork in a directory, we don't throw it away.
+    let mut failed_dirs: BTreeSet<Utf8PathBuf> = BTreeSet::new();
+
+    if extract_art {
+        let tolerance = load_config(&opts.config)?.get_square_tolerance();
+        let dirs: BTreeSet<Utf8PathBuf> = files.iter().map(|f| parent_dir(f)).collect();
+
+        for dir in dirs {
+            if let Err(e) = extract_artwork(&dir, tolerance, opts) {
+                eprintln!("Error extracting artwork in {dir}: {e}");
+                failed_dirs.insert(dir);
+                ret_code = false;
+            }
+        }
+    }
+
     for file in files {
-        if let Err(e) = strip_file(&file) {
+        if failed_dirs.contains(&parent_dir(&file)) {
+            continue;
+        }
+
+        if let Err(e) = strip_file(&file, opts) {
             eprintln!("Error stripping {file}: {e}");
             ret_code = false;
         }
@@ -21,17 +44,26 @@ pub fn run(files: &[Utf8PathBuf]) -> anyhow::Result<bool> {
     Ok(ret_code)
 }
 
-fn strip_file(file: &Utf8Path) -> anyhow::Result<bool> {
+// With --noop, says what it would do, and does nothing.
+pub fn strip_file(file: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<bool> {
     let info = AurMetadata::new(file)?;
     let tagger = Tagger::new(&info)?;
-    remove_artwork(&info, &tagger)?;
-    remove_tags(&info, &tagger)
+    remove_artwork(&info, &tagger, opts)?;
+    remove_tags(&info, &tagger, opts)
+}
+
+fn parent_dir(file: &Utf8Path) -> Utf8PathBuf {
+    match file.parent() {
+        Some(dir) if !dir.as_str().is_empty() => dir.to_path_buf(),
+        _ => Utf8PathBuf::from("."),
+    }
 }
 
-fn remove_tags(info: &AurMetadata, tagger: &Tagger) -> anyhow::Result<bool> {
-    let expected_tags = expected_tags(&info.filetype)?;
+fn remove_tags(info: &AurMetadata, tagger: &Tagger, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    let mut wanted_tags = expected_tags(&info.filetype)?;
+    wanted_tags.extend(optional_tags(&info.filetype)?);
     let rawtag_keys: HashSet<String> = info.rawtags.iter().map(|(k, _v)| k).cloned().collect();
-    let mut to_remove: Vec<String> = rawtag_keys.difference(&expected_tags).cloned().collect();
+    let mut to_remove: Vec<String> = rawtag_keys.difference(&wanted_tags).cloned().collect();
     to_remove.sort();
 
     println!(
@@ -44,21 +76,31 @@ fn remove_tags(info: &AurMetadata, tagger: &Tagger) -> anyhow::Result<bool> {
             .join(", ")
     );
 
+    if opts.noop {
+        return Ok(false);
+    }
+
     tagger.remove_tags(&to_remove)
 }
 
-fn remove_artwork(info: &AurMetadata, tagger: &Tagger) -> anyhow::Result<bool> {
-    if info.has_picture {
-        println!("Strip: {} :: embedded artwork", info.path);
-        tagger.remove_artwork()
-    } else {
+fn remove_artwork(info: &AurMetadata, tagger: &Tagger, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    if !info.has_picture {
+        return Ok(false);
+    }
+
+    println!("Strip: {} :: embedded artwork", info.path);
+
+    if opts.noop {
         Ok(false)
+    } else {
+        tagger.remove_artwork()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::config::ARTWORK_FILENAME;
     use camino_tempfile_ext::prelude::*;
     use snltest::fixture;
 
@@ -72,12 +114,12 @@ mod test {
         let original_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!(9, original_info.rawtags.len());
-        assert!(strip_file(&file_under_test).unwrap());
+        assert!(strip_file(&file_under_test, &GlobalOpts::default()).unwrap());
 
         let new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!(6, new_info.rawtags.len());
-        assert!(!strip_file(&file_under_test).unwrap());
+        assert!(!strip_file(&file_under_test, &GlobalOpts::default()).unwrap());
 
         let new_new_info = AurMetadata::new(&file_under_test).unwrap();
 
@@ -94,15 +136,76 @@ mod test {
         let original_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!(12, original_info.rawtags.len());
-        assert!(strip_file(&file_under_test).unwrap());
+        assert!(strip_file(&file_under_test, &GlobalOpts::default()).unwrap());
 
         let new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!(6, new_info.rawtags.len());
-        assert!(!strip_file(&file_under_test).unwrap());
+        assert!(!strip_file(&file_under_test, &GlobalOpts::default()).unwrap());
 
         let new_new_info = AurMetadata::new(&file_under_test).unwrap();
 
         assert_eq!(6, new_new_info.rawtags.len());
     }
+
+    #[test]
+    fn test_strip_extract_art() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(
+            fixture!("commands/strip"),
+            &["01.tester.not_stripped.flac", "02.tester.not_stripped.mp3"],
+        )
+        .unwrap();
+
+        let files = vec![
+            tmp.path().join("01.tester.not_stripped.flac"),
+            tmp.path().join("02.tester.not_stripped.mp3"),
+        ];
+
+        let opts = GlobalOpts {
+            config: fixture!("config/test.toml"),
+            ..Default::default()
+        };
+
+        assert!(run(&files, true, &opts).unwrap());
+        assert!(tmp.path().join(ARTWORK_FILENAME).exists());
+
+        for file in files {
+            assert!(!AurMetadata::new(&file).unwrap().has_picture);
+        }
+    }
+
+    #[test]
+    fn test_strip_extract_art_noop() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/strip"), &["01.tester.not_stripped.flac"])
+            .unwrap();
+
+        let file = tmp.path().join("01.tester.not_stripped.flac");
+
+        let opts = GlobalOpts {
+            config: fixture!("config/test.toml"),
+            noop: true,
+            ..Default::default()
+        };
+
+        assert!(run(std::slice::from_ref(&file), true, &opts).unwrap());
+        assert!(!tmp.path().join(ARTWORK_FILENAME).exists());
+
+        let info = AurMetadata::new(&file).unwrap();
+        assert!(info.has_picture);
+        assert_eq!(9, info.rawtags.len());
+    }
+
+    #[test]
+    fn test_parent_dir() {
+        assert_eq!(
+            Utf8PathBuf::from("/a/b"),
+            parent_dir(Utf8Path::new("/a/b/01.c.d.flac"))
+        );
+        assert_eq!(
+            Utf8PathBuf::from("."),
+            parent_dir(Utf8Path::new("01.c.d.flac"))
+        );
+    }
 }
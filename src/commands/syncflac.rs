@@ -1,49 +1,184 @@
 use crate::utils::config::{self, Config};
 use crate::utils::dir;
+use crate::utils::formats;
 use crate::utils::helpers;
-use crate::utils::mp3_encoder::{self, TranscodeCmds};
+use crate::utils::mp3_encoder::{self, SyncTarget};
+use crate::utils::profile::Profile;
+use crate::utils::sync_state::SyncState;
 use crate::utils::types::{GlobalOpts, Mp3dirOpts};
-use camino::{Utf8Path, Utf8PathBuf};
+use anyhow::ensure;
+use camino::{Utf8Component, Utf8Path};
 
-pub fn run(root_dir: &Utf8Path, bitrate: &str, opts: &GlobalOpts) -> anyhow::Result<bool> {
+pub fn run(
+    root_dir: &Utf8Path,
+    profile: &str,
+    state_file: &Utf8Path,
+    opts: &GlobalOpts,
+) -> anyhow::Result<bool> {
     let root_dir = root_dir.canonicalize_utf8()?;
-    let cmds = mp3_encoder::transcode_cmds()?;
     let conf = config::load_config(&opts.config)?;
+    let targets = target_profiles(&conf, profile)?
+        .into_iter()
+        .map(|(tree, profile)| {
+            Ok(SyncTarget {
+                tree,
+                cmds: mp3_encoder::transcode_cmds(profile)?,
+            })
+        })
+        .collect::<anyhow::Result<Vec<SyncTarget>>>()?;
+
     helpers::check_hierarchy(&root_dir)?;
-    syncflac(root_dir.join("flac"), bitrate, &conf, &cmds, opts)?;
+    syncflac(&root_dir, &targets, &conf, state_file, opts)?;
     Ok(true)
 }
 
+// mp3/ always comes first, made with the given profile. Any mirrors in the config follow it, and
+// are created if need be.
+fn target_profiles(conf: &Config, profile: &str) -> anyhow::Result<Vec<(String, Profile)>> {
+    let mp3_profile = conf.get_profile(profile)?;
+    mp3_profile.ensure_makes("mp3")?;
+
+    let mut ret = vec![("mp3".to_owned(), mp3_profile)];
+
+    for mirror in conf.get_syncflac_mirrors() {
+        ensure!(
+            is_tree_name(&mirror.dir) && !ret.iter().any(|(tree, _)| tree == &mirror.dir),
+            "bad syncflac mirror directory: '{}'",
+            mirror.dir
+        );
+
+        let profile = conf.get_profile(&mirror.profile)?;
+
+        // Everything we make is tagged, so it had better be something we know how to tag.
+        ensure!(
+            formats::by_name(&profile.extension).is_some(),
+            "profile '{}' makes {} files, which cannot be tagged",
+            profile.name,
+            profile.extension
+        );
+
+        ret.push((mirror.dir.clone(), profile));
+    }
+
+    Ok(ret)
+}
+
+fn is_tree_name(dir: &str) -> bool {
+    let mut components = Utf8Path::new(dir).components();
+
+    dir != "flac"
+        && matches!(components.next(), Some(Utf8Component::Normal(_)))
+        && components.next().is_none()
+}
+
 fn syncflac(
-    flac_root: Utf8PathBuf,
-    preset: &str,
+    root_dir: &Utf8Path,
+    targets: &[SyncTarget],
     conf: &Config,
-    cmds: &TranscodeCmds,
+    state_file: &Utf8Path,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
+    let flac_root = root_dir.join("flac");
     let mut dir_list = dir::expand_dir_list(std::slice::from_ref(&flac_root), true);
 
     if let Some(ignore_list) = conf.get_syncflac_list() {
-        dir_list.retain(|d| !ignore_list.iter().any(|s| d.starts_with(s)));
+        dir_list.retain(|d| !ignore_list.is_match(d));
     }
 
     let mut synced = 0;
+    let mut state = SyncState::load(state_file);
 
     let cmd_opts = Mp3dirOpts {
-        preset: preset.to_owned(),
+        profile: targets[0].cmds.profile.name.clone(),
         force: false,
         recurse: true,
-        root: flac_root,
+        root: root_dir.to_path_buf(),
         suffix: false,
     };
 
+    // Moves go first, so nothing is encoded which we already have.
+    for target in targets {
+        let moves = mp3_encoder::make_move_list(
+            root_dir,
+            &target.tree,
+            &target.cmds.profile.extension,
+            &dir_list,
+            &mut state,
+        )?;
+
+        for action in moves {
+            if mp3_encoder::move_file(&action, &root_dir.join(&target.tree), &mut state, opts)? {
+                synced += 1;
+            }
+        }
+    }
+
     for flac_dir in dir_list.iter() {
-        let mp3_dir = mp3_encoder::mp3_dir_from(flac_dir, &cmd_opts);
+        for target in targets {
+            let target_dir = mp3_encoder::target_dir_from(flac_dir, root_dir, &target.tree, None)?;
 
-        if mp3_encoder::sync_dir(flac_dir, &mp3_dir, cmds, &cmd_opts, opts)? {
-            synced += 1;
+            if mp3_encoder::sync_dir(
+                flac_dir,
+                &target_dir,
+                &target.cmds,
+                &cmd_opts,
+                &mut state,
+                opts,
+            )? {
+                synced += 1;
+            }
         }
     }
 
+    if !opts.noop {
+        state.save()?;
+    }
+
     Ok(synced > 0)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_target_profiles() {
+        let conf = config::load_config(&fixture!("config/test.toml")).unwrap();
+        let targets = target_profiles(&conf, "standard").unwrap();
+
+        assert_eq!(
+            vec![("mp3", "standard"), ("opus", "opus")],
+            targets
+                .iter()
+                .map(|(tree, profile)| (tree.as_str(), profile.name.as_str()))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(target_profiles(&conf, "opus").is_err());
+        assert!(target_profiles(&conf, "nonsense").is_err());
+
+        let conf = config::load_config(&fixture!("config/bad_mirror.toml")).unwrap();
+        assert_eq!(
+            "bad syncflac mirror directory: 'flac'",
+            target_profiles(&conf, "extreme").unwrap_err().to_string()
+        );
+
+        let conf = config::load_config(&fixture!("config/untaggable_mirror.toml")).unwrap();
+        assert_eq!(
+            "profile 'aac' makes aac files, which cannot be tagged",
+            target_profiles(&conf, "extreme").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_is_tree_name() {
+        assert!(is_tree_name("opus"));
+        assert!(is_tree_name("aac"));
+        assert!(!is_tree_name("flac"));
+        assert!(!is_tree_name("portable/opus"));
+        assert!(!is_tree_name("/storage/opus"));
+        assert!(!is_tree_name(".."));
+        assert!(!is_tree_name(""));
+    }
+}
@@ -1,10 +1,12 @@
+use crate::utils::config::load_config;
 use crate::utils::dir;
-use crate::utils::external::find_binary;
+use crate::utils::external::{find_binary, run_pipeline};
+use crate::utils::profile::{Encoder, Profile};
 use crate::utils::types::{GlobalOpts, TranscodeOptions};
 use crate::{err_if_empty, verbose};
+use anyhow::ensure;
 use camino::{Utf8Path, Utf8PathBuf};
 use std::fs;
-use std::process::Command;
 
 pub fn run(
     files: &[Utf8PathBuf],
@@ -12,13 +14,28 @@ pub fn run(
     cmd_opts: &TranscodeOptions,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
-    let ffmpeg = find_binary("ffmpeg")?;
+    let profile = match &cmd_opts.profile {
+        Some(name) => {
+            let profile = load_config(&opts.config)?.get_profile(name)?;
+            profile.ensure_makes(format)?;
+            // LAME can't read FLACs, or most of what else we might be given.
+            ensure!(
+                profile.encoder == Encoder::Ffmpeg,
+                "profile '{}' uses LAME: transcode needs an ffmpeg profile",
+                name
+            );
+            profile
+        }
+        None => Profile::ffmpeg(format),
+    };
+
+    let encoder = find_binary(profile.encoder.binary())?;
     let mut ret_code = true;
     let files = dir::pathbuf_set(files);
     err_if_empty!(files);
 
     for file in files {
-        match transcode_file(&file, format, cmd_opts, opts, &ffmpeg) {
+        match transcode_file(&file, &profile, cmd_opts, opts, &encoder) {
             Ok(success) => {
                 if !success {
                     eprintln!("Failed to transcode {file}");
@@ -38,12 +55,12 @@ pub fn run(
 
 fn transcode_file(
     file: &Utf8Path,
-    format: &str,
+    profile: &Profile,
     cmd_opts: &TranscodeOptions,
     opts: &GlobalOpts,
-    ffmpeg: &Utf8Path,
+    encoder: &Utf8Path,
 ) -> anyhow::Result<bool> {
-    let target_file = file.with_extension(format);
+    let target_file = file.with_extension(&profile.extension);
 
     if target_file.exists() && !cmd_opts.force {
         verbose!(opts, "target '{}' exists. Use -f to overwrite", target_file);
@@ -52,20 +69,15 @@ fn transcode_file(
 
     println!("{} -> {}", file, target_file);
 
-    let result = Command::new(ffmpeg)
-        .arg("-hide_banner")
-        .arg("-loglevel")
-        .arg("error")
-        .arg("-y")
-        .arg("-i")
-        .arg(file)
-        .arg(target_file)
-        .status()?;
+    run_pipeline(&target_file, |tmp| {
+        vec![profile.command(encoder, &[], file, tmp)]
+    })?;
 
+    // Only once we know we have something to replace it with.
     if cmd_opts.remove_originals {
         verbose!(opts, "Removing {}", file);
         fs::remove_file(file)?;
     }
 
-    Ok(result.success())
+    Ok(true)
 }
@@ -1,4 +1,4 @@
-use crate::utils::config;
+use crate::utils::config::{self, PathMatcher};
 use crate::utils::dir;
 use crate::utils::helpers;
 use crate::utils::types::{GlobalOpts, WantsList};
@@ -18,7 +18,7 @@ pub fn run(root: &Utf8Path, tracks: bool, opts: &GlobalOpts) -> anyhow::Result<b
         )
     } else {
         filter_by_config(
-            filter_by_top_level(
+            filter_by_config(
                 find_missing_albums(&root)?,
                 config.get_wantflac_ignore_top_level(),
             ),
@@ -30,23 +30,16 @@ pub fn run(root: &Utf8Path, tracks: bool, opts: &GlobalOpts) -> anyhow::Result<b
     Ok(true)
 }
 
-fn filter_by_top_level(list: WantsList, config_list: Option<&WantsList>) -> WantsList {
+fn filter_by_config(list: WantsList, config_list: Option<&PathMatcher>) -> WantsList {
     match config_list {
         Some(config_list) => list
             .into_iter()
-            .filter(|list_element| !config_list.iter().any(|p| list_element.starts_with(p)))
+            .filter(|list_element| !config_list.is_match(Utf8Path::new(list_element)))
             .collect(),
         None => list,
     }
 }
 
-fn filter_by_config(list: WantsList, config_list: Option<&WantsList>) -> WantsList {
-    match config_list {
-        Some(config_list) => list.difference(config_list).cloned().collect(),
-        None => list,
-    }
-}
-
 fn print_output(wants_list: WantsList) {
     let mut list_vec: Vec<&String> = wants_list.iter().collect();
     list_vec.sort();
@@ -159,7 +152,7 @@ mod test {
 
         assert_eq!(
             expected,
-            filter_by_top_level(
+            filter_by_config(
                 find_missing_albums(&fixture!("commands/wantflac")).unwrap(),
                 config.get_wantflac_ignore_top_level()
             )
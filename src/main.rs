@@ -3,7 +3,11 @@ use camino::Utf8PathBuf;
 use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::generate;
 use clap_complete::shells::{Bash, Fish, Zsh};
-use utils::types::{CopytagsOptions, GlobalOpts, Mp3dirOpts, RenumberDirection, TranscodeOptions};
+use utils::profile::{CDQ_PROFILE, DEFAULT_MP3_PROFILE};
+use utils::types::{
+    CopytagsOptions, FixMode, GlobalOpts, LintOpts, Mp3dirOpts, OutputFormat, RenumberDirection,
+    TranscodeOptions,
+};
 mod commands;
 mod utils;
 
@@ -28,13 +32,22 @@ struct Cli {
 
 #[derive(Debug, Subcommand)]
 enum Commands {
-    /// If the given files are in a disc_n directory, add (Disc n) to the album tag
+    /// If the given files are in a disc_n directory, record the disc number in the style the
+    /// config selects: either add (Disc n) to the album tag, or set the disc number tag
     Albumdisc {
         /// One or more media files
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
     },
-    /// Renames and resizes artwork in the given directories
+    /// Finds albums which share the same, or nearly the same, cover art
+    Artdupes {
+        /// How many bits two image hashes may differ by and still be a match
+        #[arg(short, long, default_value_t = 6)]
+        distance: u32,
+        /// Root of the FLAC hierarchy
+        root_dir: Utf8PathBuf,
+    },
+    /// Renames, converts and resizes artwork in the given directories
     Artfix {
         /// Recurse
         #[arg(short, long)]
@@ -46,15 +59,33 @@ enum Commands {
         #[arg(required = true)]
         directories: Vec<Utf8PathBuf>,
     },
+    /// Looks for FLACs made from lossy sources, and "hi-res" FLACs which aren't
+    Authenticity {
+        /// Recurse
+        #[arg(short, long)]
+        recurse: bool,
+        /// Files and/or directories to check
+        #[arg(required = true)]
+        files: Vec<Utf8PathBuf>,
+    },
     /// Re-encodes "hi-res" FLACs at CD quality
     Cdq {
-        /// Leave the original files. New files will have -cdq before their suffix
+        /// Leave the original files. New files will have -cdq, or the profile's suffix, before
+        /// their suffix
         #[arg(short, long)]
         leave: bool,
+        /// Encoding profile making FLACs
+        #[arg(short, long, default_value = CDQ_PROFILE)]
+        profile: String,
         /// One or more media files
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
     },
+    /// Helpers for working with the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
     /// Generate shell completions
     Completions {
         /// Generate completion code for the given shell: bash, fish, or zsh
@@ -75,11 +106,21 @@ enum Commands {
     },
     /// Finds files in tracks/ which could be duplicates of tracks in albums/ or eps/
     Dupes { root_dir: Utf8PathBuf },
+    /// Saves the best embedded front cover in each directory as cover.jpg, if there isn't one
+    Extractart {
+        /// Recurse
+        #[arg(short, long)]
+        recurse: bool,
+        /// Directories to process
+        #[arg(required = true)]
+        directories: Vec<Utf8PathBuf>,
+    },
     /// Convert one or more FLACs to MP3s
     Flac2mp3 {
-        /// LAME MP3 preset: "medium", "standard", "extreme", "insane"
-        #[arg(short, long, default_value = "extreme")]
-        preset: String,
+        /// Encoding profile making MP3s: a LAME preset ("medium", "standard", "extreme",
+        /// "insane"), or one from the config file
+        #[arg(short, long, alias = "preset", default_value = DEFAULT_MP3_PROFILE)]
+        profile: String,
         /// Overwrite existing files
         #[arg(short, long)]
         force: bool,
@@ -117,6 +158,28 @@ enum Commands {
         /// Recurse
         #[arg(short, long)]
         recurse: bool,
+        /// Repair what can be repaired. --fix=ask asks first
+        #[arg(
+            long,
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "auto"
+        )]
+        fix: Option<FixMode>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+        /// Check everything, ignoring and not updating results cached by earlier runs
+        #[arg(long)]
+        no_cache: bool,
+        /// Also decode FLACs to check they are what they claim to be. This is slow
+        #[arg(long)]
+        authenticity: bool,
+        /// Also decode files to look for clipping, silence, fake stereo, DC offset and very short
+        /// tracks. This is slow
+        #[arg(long)]
+        audio: bool,
         /// Files and/or directories to check
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
@@ -126,10 +189,25 @@ enum Commands {
         /// Recurse
         #[arg(short, long)]
         recurse: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: OutputFormat,
+        /// Check everything, ignoring and not updating results cached by earlier runs
+        #[arg(long)]
+        no_cache: bool,
         /// Directories to check
         #[arg(required = true)]
         directories: Vec<Utf8PathBuf>,
     },
+    /// Measures the loudness of FLACs and MP3s, and writes ReplayGain tags
+    Loudness {
+        /// Recurse
+        #[arg(short, long)]
+        recurse: bool,
+        /// One or more media files. Album gain is measured over those in each directory
+        #[arg(required = true)]
+        files: Vec<Utf8PathBuf>,
+    },
     /// Shows tag information about files in the given directory, one file per line
     Ls {
         /// Don't format as a table
@@ -143,10 +221,11 @@ enum Commands {
     },
     /// Transcode a FLAC directory an equivalent point in the MP3 hierarchy
     Mp3dir {
-        /// LAME MP3 preset: "medium", "standard", "extreme", "insane"
-        #[arg(short, long, default_value = "extreme")]
-        preset: String,
-        /// Suffix on the new directory name with the preset
+        /// Encoding profile making MP3s: a LAME preset ("medium", "standard", "extreme",
+        /// "insane"), or one from the config file
+        #[arg(short, long, alias = "preset", default_value = DEFAULT_MP3_PROFILE)]
+        profile: String,
+        /// Suffix on the new directory name with the profile's suffix
         #[arg(short = 'x', long)]
         suffix: bool,
         /// Overwrite existing files
@@ -158,6 +237,9 @@ enum Commands {
         /// Root directory for media files, containing flac/ and mp3/
         #[arg(short = 'R', long, default_value = "/storage")]
         root: Utf8PathBuf,
+        /// File recording what each transcoded file was made from
+        #[arg(long, default_value_t = utils::sync_state::default_location())]
+        state_file: Utf8PathBuf,
         /// One or more directories
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
@@ -185,11 +267,25 @@ enum Commands {
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
     },
+    /// Checks every FLAC has an MP3 with matching tags and duration, at least as new as the FLAC,
+    /// and every MP3 has a FLAC
+    Pairs {
+        /// Root directory for media files, containing flac/ and mp3/
+        #[arg(short = 'R', long, default_value = "/storage")]
+        root: Utf8PathBuf,
+        /// Allowed difference in duration, in seconds
+        #[arg(short, long, default_value_t = 2)]
+        tolerance: u64,
+    },
     /// Uses ffmpeg to reencode files
     Reencode {
         /// Keep the original files after reencoding
         #[arg(short, long)]
         keep_originals: bool,
+        /// Encoding profile, which must make the same type of file as it is given. By default,
+        /// "reencode_flac" or "reencode_mp3"
+        #[arg(short, long)]
+        profile: Option<String>,
         /// One or more media files
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
@@ -235,18 +331,26 @@ enum Commands {
     },
     /// Remove embedded images and unwanted tags from the given file(s)
     Strip {
+        /// Save embedded artwork as cover.jpg, if there isn't one, before removing it
+        #[arg(long)]
+        extract_art: bool,
         /// One or more media files
         #[arg(required = true)]
         files: Vec<Utf8PathBuf>,
     },
-    /// Ensure we have an MP3 for every FLAC. Assumes parallel flac/ and mp3/ trees
+    /// Ensure we have an MP3 for every FLAC. Assumes parallel flac/ and mp3/ trees. Mirrors in
+    /// the config file, like opus/, are kept in step too
     Syncflac {
-        /// LAME MP3 preset: "medium", "standard", "extreme", "insane"
-        #[arg(short, long, default_value = "extreme")]
-        preset: String,
+        /// Encoding profile making MP3s: a LAME preset ("medium", "standard", "extreme",
+        /// "insane"), or one from the config file
+        #[arg(short, long, alias = "preset", default_value = DEFAULT_MP3_PROFILE)]
+        profile: String,
         /// Root directory for media files, containing flac/ and mp3/
         #[arg(short = 'R', long, default_value = "/storage")]
         root: Utf8PathBuf,
+        /// File recording what each transcoded file was made from
+        #[arg(long, default_value_t = utils::sync_state::default_location())]
+        state_file: Utf8PathBuf,
     },
     /// Rename the file(s) according to its tags
     Tag2name {
@@ -286,6 +390,9 @@ enum Commands {
         /// Remove the original files after transcoding
         #[arg(short = 'R', long = "remove")]
         remove_originals: bool,
+        /// ffmpeg encoding profile, which must make the desired format. By default, ffmpeg chooses
+        #[arg(short, long)]
+        profile: Option<String>,
         /// The desired format
         format: String,
         /// One or more media files
@@ -313,6 +420,18 @@ enum Commands {
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// Shows which ignore rules, if any, match the given path
+    TestIgnore {
+        /// Instead, list the rules which match nothing under the given directory
+        #[arg(long)]
+        unused: bool,
+        /// Path to test, as the command using the list would see it
+        path: Utf8PathBuf,
+    },
+}
+
 fn handle_error(err: anyhow::Error) {
     if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
         eprintln!("ERROR: (I/O) : {}", io_err);
@@ -333,12 +452,31 @@ fn main() {
     };
     let result = match cli.command {
         Commands::Albumdisc { files } => commands::albumdisc::run(&files, &global_opts),
+        Commands::Artdupes { distance, root_dir } => {
+            commands::artdupes::run(&root_dir, distance, &global_opts)
+        }
         Commands::Artfix {
             recurse,
             linkdir,
             directories,
         } => commands::artfix::run(&directories, recurse, linkdir, &global_opts),
-        Commands::Cdq { files, leave } => commands::cdq::run(&files, leave, &global_opts),
+        Commands::Authenticity { recurse, files } => {
+            commands::authenticity::run(&files, recurse, &global_opts)
+        }
+        Commands::Cdq {
+            files,
+            leave,
+            profile,
+        } => commands::cdq::run(&files, leave, &profile, &global_opts),
+        Commands::Config { command } => match command {
+            ConfigCommands::TestIgnore { unused, path } => {
+                if unused {
+                    commands::config::unused_rules(&path, &global_opts)
+                } else {
+                    commands::config::test_ignore(&path, &global_opts)
+                }
+            }
+        },
         Commands::Completions { shell } => {
             match shell.as_str() {
                 "bash" => {
@@ -363,11 +501,15 @@ fn main() {
             files,
         } => commands::copytags::run(&files, &CopytagsOptions { recurse, force }, &global_opts),
         Commands::Dupes { root_dir } => commands::dupes::run(&root_dir),
+        Commands::Extractart {
+            recurse,
+            directories,
+        } => commands::extractart::run(&directories, recurse, &global_opts),
         Commands::Flac2mp3 {
-            preset,
+            profile,
             files,
             force,
-        } => commands::flac2mp3::run(&files, preset, force, &global_opts),
+        } => commands::flac2mp3::run(&files, &profile, force, &global_opts),
         Commands::Get {
             property,
             files,
@@ -375,42 +517,70 @@ fn main() {
         } => commands::get::run(&property, &files, short),
         Commands::Info { files } => commands::info::run(&files),
         Commands::Itag { files, tag } => commands::itag::run(&files, &tag, &global_opts),
-        Commands::Lint { recurse, files } => commands::lint::run(&files, recurse, &global_opts),
+        Commands::Lint {
+            recurse,
+            fix,
+            format,
+            no_cache,
+            authenticity,
+            audio,
+            files,
+        } => commands::lint::run(
+            &files,
+            &LintOpts {
+                recurse,
+                fix,
+                format,
+                use_cache: !no_cache,
+                authenticity,
+                audio,
+            },
+            &global_opts,
+        ),
         Commands::Lintdir {
             recurse,
+            format,
+            no_cache,
             directories,
-        } => commands::lintdir::run(&directories, recurse, &global_opts),
+        } => commands::lintdir::run(&directories, recurse, format, !no_cache, &global_opts),
+        Commands::Loudness { recurse, files } => {
+            commands::loudness::run(&files, recurse, &global_opts)
+        }
         Commands::Ls {
             long,
             recurse,
             directories,
         } => commands::ls::run(&directories, recurse, long),
         Commands::Mp3dir {
-            preset,
+            profile,
             files,
             force,
             recurse,
             root,
             suffix,
+            state_file,
         } => commands::mp3dir::run(
             &files,
             &Mp3dirOpts {
-                preset,
+                profile,
                 force,
                 recurse,
                 root,
                 suffix,
             },
+            &state_file,
             &global_opts,
         ),
         Commands::Name2num { files } => commands::name2num::run(&files, &global_opts),
         Commands::Name2tag { files, force } => commands::name2tag::run(&files, force, &global_opts),
         Commands::Namecheck { root_dir } => commands::namecheck::run(&root_dir, &global_opts),
         Commands::Num2name { files } => commands::num2name::run(&files, &global_opts),
+        Commands::Pairs { root, tolerance } => commands::pairs::run(&root, tolerance, &global_opts),
         Commands::Reencode {
             files,
             keep_originals,
-        } => commands::reencode::run(&files, keep_originals),
+            profile,
+        } => commands::reencode::run(&files, keep_originals, profile.as_deref(), &global_opts),
         Commands::Renumber {
             direction,
             delta,
@@ -422,10 +592,14 @@ fn main() {
         }
         Commands::Sort { files } => commands::sort::run(&files, &global_opts),
         Commands::Split { files } => commands::split::run(&files),
-        Commands::Strip { files } => commands::strip::run(&files),
-        Commands::Syncflac { preset, root } => {
-            commands::syncflac::run(&root, &preset, &global_opts)
+        Commands::Strip { extract_art, files } => {
+            commands::strip::run(&files, extract_art, &global_opts)
         }
+        Commands::Syncflac {
+            profile,
+            root,
+            state_file,
+        } => commands::syncflac::run(&root, &profile, &state_file, &global_opts),
         Commands::Tagsub {
             tag,
             find,
@@ -439,6 +613,7 @@ fn main() {
             format,
             force,
             remove_originals,
+            profile,
             files,
         } => commands::transcode::run(
             &files,
@@ -446,6 +621,7 @@ fn main() {
             &TranscodeOptions {
                 remove_originals,
                 force,
+                profile,
             },
             &global_opts,
         ),
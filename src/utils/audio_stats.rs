@@ -0,0 +1,233 @@
+use crate::utils::decoder;
+use anyhow::anyhow;
+use camino::Utf8Path;
+
+// Decoded samples are scaled to -1.0..1.0. Anything quieter than -60dBFS counts as silence, and
+// three or more consecutive samples at full scale are a clip.
+const SILENCE_LEVEL: f32 = 0.001;
+const CLIP_LEVEL: f32 = 0.999;
+const CLIP_RUN: u32 = 3;
+
+/// Facts about the audio in a file, found by decoding all of it. Judging whether they are
+/// problems is left to the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioStats {
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub frames: u64,
+    pub clipped_runs: u32,
+    pub leading_silence: u64,
+    pub trailing_silence: u64,
+    pub identical_channels: bool,
+    pub dc_offset: f64,
+}
+
+impl AudioStats {
+    /// Decodes the whole of the given file.
+    pub fn from_path(file: &Utf8Path) -> anyhow::Result<Self> {
+        let mut scanner: Option<Scanner> = None;
+        let mut sample_rate = 0;
+
+        decoder::decode::<f32, _>(file, |spec, samples| {
+            sample_rate = spec.rate;
+            scanner
+                .get_or_insert_with(|| Scanner::new(spec.channels.count()))
+                .add_interleaved(samples);
+        })?;
+
+        match scanner {
+            Some(scanner) if sample_rate > 0 => Ok(scanner.finish(sample_rate)),
+            _ => Err(anyhow!("no audio in {}", file)),
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frames as f64 / self.sample_rate as f64
+    }
+
+    pub fn leading_silence_seconds(&self) -> f64 {
+        self.leading_silence as f64 / self.sample_rate as f64
+    }
+
+    pub fn trailing_silence_seconds(&self) -> f64 {
+        self.trailing_silence as f64 / self.sample_rate as f64
+    }
+}
+
+struct Scanner {
+    channels: usize,
+    frames: u64,
+    first_sound: Option<u64>,
+    last_sound: Option<u64>,
+    clip_lengths: Vec<u32>,
+    clipped_runs: u32,
+    identical_channels: bool,
+    sums: Vec<f64>,
+}
+
+impl Scanner {
+    fn new(channels: usize) -> Self {
+        let channels = channels.max(1);
+
+        Self {
+            channels,
+            frames: 0,
+            first_sound: None,
+            last_sound: None,
+            clip_lengths: vec![0; channels],
+            clipped_runs: 0,
+            identical_channels: true,
+            sums: vec![0.0; channels],
+        }
+    }
+
+    fn add_interleaved(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            let mut sound = false;
+
+            for (channel, sample) in frame.iter().enumerate() {
+                let level = sample.abs();
+
+                if level >= SILENCE_LEVEL {
+                    sound = true;
+                }
+
+                // A run is counted once, when it gets long enough to be a clip.
+                if level >= CLIP_LEVEL {
+                    self.clip_lengths[channel] += 1;
+
+                    if self.clip_lengths[channel] == CLIP_RUN {
+                        self.clipped_runs += 1;
+                    }
+                } else {
+                    self.clip_lengths[channel] = 0;
+                }
+
+                self.sums[channel] += *sample as f64;
+            }
+
+            if frame.iter().any(|s| *s != frame[0]) {
+                self.identical_channels = false;
+            }
+
+            if sound {
+                self.first_sound.get_or_insert(self.frames);
+                self.last_sound = Some(self.frames);
+            }
+
+            self.frames += 1;
+        }
+    }
+
+    // Silence can't be identical channels: there's nothing to compare.
+    fn finish(self, sample_rate: u32) -> AudioStats {
+        let (leading_silence, trailing_silence) = match (self.first_sound, self.last_sound) {
+            (Some(first), Some(last)) => (first, self.frames - last - 1),
+            _ => (self.frames, self.frames),
+        };
+
+        let dc_offset = if self.frames == 0 {
+            0.0
+        } else {
+            self.sums
+                .iter()
+                .map(|sum| sum / self.frames as f64)
+                .fold(
+                    0.0,
+                    |acc: f64, mean| {
+                        if mean.abs() > acc.abs() { mean } else { acc }
+                    },
+                )
+        };
+
+        AudioStats {
+            sample_rate,
+            channels: self.channels,
+            frames: self.frames,
+            clipped_runs: self.clipped_runs,
+            leading_silence,
+            trailing_silence,
+            identical_channels: self.channels > 1
+                && self.identical_channels
+                && self.first_sound.is_some(),
+            dc_offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+    use std::f32::consts::PI;
+
+    fn scan(channels: usize, samples: &[f32]) -> AudioStats {
+        let mut scanner = Scanner::new(channels);
+        scanner.add_interleaved(samples);
+        scanner.finish(1000)
+    }
+
+    // Never quite zero, so never silent.
+    fn tone(frames: usize, amplitude: f32) -> Vec<f32> {
+        (0..frames)
+            .map(|i| amplitude * (2.0 * PI * i as f32 / 50.0).cos())
+            .collect()
+    }
+
+    #[test]
+    fn test_silence() {
+        let mut samples = vec![0.0; 2000];
+        samples.extend(tone(1000, 0.5));
+        samples.extend(vec![0.0005; 3000]);
+
+        let stats = scan(1, &samples);
+        assert_eq!(6000, stats.frames);
+        assert_eq!(2000, stats.leading_silence);
+        assert_eq!(2.0, stats.leading_silence_seconds());
+        assert_eq!(3.0, stats.trailing_silence_seconds());
+        assert_eq!(6.0, stats.duration());
+
+        let stats = scan(1, &[0.0; 500]);
+        assert_eq!(500, stats.leading_silence);
+        assert_eq!(500, stats.trailing_silence);
+    }
+
+    #[test]
+    fn test_clipping() {
+        let samples = [
+            0.5, 1.0, 1.0, 0.5, 1.0, 1.0, 1.0, 1.0, 0.2, -1.0, -1.0, -1.0,
+        ];
+        assert_eq!(2, scan(1, &samples).clipped_runs);
+        assert_eq!(0, scan(1, &tone(1000, 0.9)).clipped_runs);
+    }
+
+    #[test]
+    fn test_identical_channels() {
+        let mono: Vec<f32> = tone(1000, 0.5).into_iter().flat_map(|s| [s, s]).collect();
+        assert!(scan(2, &mono).identical_channels);
+
+        let stereo: Vec<f32> = tone(1000, 0.5)
+            .into_iter()
+            .flat_map(|s| [s, s * 0.9])
+            .collect();
+        assert!(!scan(2, &stereo).identical_channels);
+        assert!(!scan(2, &[0.0; 2000]).identical_channels);
+        assert!(!scan(1, &tone(1000, 0.5)).identical_channels);
+    }
+
+    #[test]
+    fn test_dc_offset() {
+        assert!(scan(1, &tone(1000, 0.5)).dc_offset.abs() < 0.0001);
+
+        let shifted: Vec<f32> = tone(1000, 0.5).into_iter().map(|s| s - 0.1).collect();
+        assert!((scan(1, &shifted).dc_offset + 0.1).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_from_path() {
+        let stats = AudioStats::from_path(&fixture!("info/test.flac")).unwrap();
+        assert_eq!(44100, stats.sample_rate);
+        assert_eq!(1, stats.channels);
+        assert!(AudioStats::from_path(&fixture!("info/bad_file.flac")).is_err());
+    }
+}
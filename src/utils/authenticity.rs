@@ -0,0 +1,447 @@
+use crate::utils::decoder;
+use anyhow::anyhow;
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::f64::consts::PI;
+
+// Lossy encoders throw away everything above a cutoff frequency, and turning the result back
+// into FLAC doesn't put it back. So a FLAC made from an MP3 has a cliff in its spectrum, usually
+// around 16 or 19kHz, where a real recording tails off gradually. Upsampling leaves the same sort
+// of cliff at the old Nyquist frequency. Padding 16-bit audio out to 24 leaves the bottom eight
+// bits of every sample at zero.
+
+const FFT_SIZE: usize = 4096;
+const BAND_WIDTH: f64 = 250.0;
+// A cliff is a band at least this many dB louder than every band in the span above it. The span
+// starts a little way up, because no filter cuts off instantly.
+const CLIFF_DEPTH: f64 = 30.0;
+const CLIFF_GAP: usize = 2;
+const CLIFF_SPAN: usize = 4;
+// Nothing cuts off lower than this, not even a 64kbps MP3. Below it, a cliff is just the music.
+const LOWEST_CUTOFF: f64 = 10_000.0;
+const LOSSY_CUTOFF: u32 = 20_000;
+const UPSAMPLED_CUTOFF: u32 = 24_500;
+const SILENCE: f64 = 1e-30;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Suspicion {
+    LossySource(u32),
+    PaddedBitDepth(u32, u32),
+    Upsampled(u32, u32),
+}
+
+impl Suspicion {
+    // These double as lint rule names.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Suspicion::LossySource(_) => "lossy_source",
+            Suspicion::PaddedBitDepth(_, _) => "padded_bit_depth",
+            Suspicion::Upsampled(_, _) => "upsampled",
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Suspicion::LossySource(cutoff) => {
+                format!(
+                    "Spectral cutoff at {} suggests a lossy source",
+                    khz(*cutoff)
+                )
+            }
+            Suspicion::PaddedBitDepth(bit_depth, effective) => format!(
+                "{}-bit audio only uses {} bits, so looks padded",
+                bit_depth, effective
+            ),
+            Suspicion::Upsampled(sample_rate, cutoff) => format!(
+                "{}Hz audio has nothing above {}, so looks upsampled",
+                sample_rate,
+                khz(*cutoff)
+            ),
+        }
+    }
+
+    pub fn value(&self) -> Value {
+        match self {
+            Suspicion::LossySource(cutoff) => json!({ "cutoff": cutoff }),
+            Suspicion::PaddedBitDepth(bit_depth, effective) => {
+                json!({ "bit_depth": bit_depth, "effective_bit_depth": effective })
+            }
+            Suspicion::Upsampled(sample_rate, cutoff) => {
+                json!({ "sample_rate": sample_rate, "cutoff": cutoff })
+            }
+        }
+    }
+
+    // A padded or upsampled file is a CD-quality file pretending not to be, and cdq turns it back
+    // into one. Nothing can be done about a lossy source.
+    pub fn remedy(&self) -> Option<&'static str> {
+        match self {
+            Suspicion::LossySource(_) => None,
+            Suspicion::PaddedBitDepth(_, _) | Suspicion::Upsampled(_, _) => Some("aur cdq"),
+        }
+    }
+}
+
+/// What the audio in a FLAC really contains. The cutoff is where the spectrum falls off a cliff,
+/// if it does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authenticity {
+    pub sample_rate: u32,
+    pub bit_depth: u32,
+    pub effective_bit_depth: u32,
+    pub cutoff: Option<u32>,
+}
+
+impl Authenticity {
+    /// Decodes the whole of the given file.
+    pub fn from_path(file: &Utf8Path) -> anyhow::Result<Self> {
+        if file.extension() != Some("flac") {
+            return Err(anyhow!("only FLACs can be checked for authenticity"));
+        }
+
+        let mut analyser: Option<Analyser> = None;
+
+        let params = decoder::decode::<i32, _>(file, |spec, samples| {
+            analyser
+                .get_or_insert_with(|| Analyser::new(spec.channels.count()))
+                .add_interleaved(samples);
+        })?;
+
+        match (analyser, params.sample_rate) {
+            (Some(analyser), Some(sample_rate)) => {
+                Ok(analyser.finish(sample_rate, params.bits_per_sample.unwrap_or(16)))
+            }
+            _ => Err(anyhow!("no audio in {}", file)),
+        }
+    }
+
+    pub fn suspicions(&self) -> Vec<Suspicion> {
+        let mut ret = Vec::new();
+
+        if let Some(cutoff) = self.cutoff {
+            if cutoff < LOSSY_CUTOFF {
+                ret.push(Suspicion::LossySource(cutoff));
+            }
+
+            if self.sample_rate > 48_000 && cutoff <= UPSAMPLED_CUTOFF {
+                ret.push(Suspicion::Upsampled(self.sample_rate, cutoff));
+            }
+        }
+
+        if self.bit_depth > 16 && (1..=16).contains(&self.effective_bit_depth) {
+            ret.push(Suspicion::PaddedBitDepth(
+                self.bit_depth,
+                self.effective_bit_depth,
+            ));
+        }
+
+        ret
+    }
+
+    pub fn summary(&self) -> String {
+        let cutoff = match self.cutoff {
+            Some(cutoff) => khz(cutoff),
+            None => "none".to_owned(),
+        };
+
+        format!(
+            "{}-bit/{}Hz, cutoff: {}, effective bit depth: {}",
+            self.bit_depth, self.sample_rate, cutoff, self.effective_bit_depth
+        )
+    }
+}
+
+// Decoded samples fill the whole of an i32, whatever the bit depth, so any bit which is never set
+// is padding. The spectrum is of the channels mixed down to mono, averaged over the whole file.
+struct Analyser {
+    channels: usize,
+    used_bits: u32,
+    window: Vec<f64>,
+    twiddles: Vec<(f64, f64)>,
+    frame: Vec<f64>,
+    power: Vec<f64>,
+    frames: usize,
+}
+
+impl Analyser {
+    fn new(channels: usize) -> Self {
+        Self {
+            channels: channels.max(1),
+            used_bits: 0,
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / FFT_SIZE as f64).cos())
+                .collect(),
+            twiddles: (0..FFT_SIZE / 2)
+                .map(|k| {
+                    let angle = -2.0 * PI * k as f64 / FFT_SIZE as f64;
+                    (angle.cos(), angle.sin())
+                })
+                .collect(),
+            frame: Vec::with_capacity(FFT_SIZE),
+            power: vec![0.0; FFT_SIZE / 2],
+            frames: 0,
+        }
+    }
+
+    fn add_interleaved(&mut self, samples: &[i32]) {
+        let scale = self.channels as f64 * -(i32::MIN as f64);
+
+        for frame in samples.chunks_exact(self.channels) {
+            let mut sum = 0.0;
+
+            for sample in frame {
+                self.used_bits |= *sample as u32;
+                sum += *sample as f64;
+            }
+
+            self.frame.push(sum / scale);
+
+            if self.frame.len() == FFT_SIZE {
+                self.add_frame();
+            }
+        }
+    }
+
+    fn add_frame(&mut self) {
+        let mut buf: Vec<(f64, f64)> = self
+            .frame
+            .iter()
+            .zip(self.window.iter())
+            .map(|(s, w)| (s * w, 0.0))
+            .collect();
+
+        fft(&mut buf, &self.twiddles);
+
+        for (power, (re, im)) in self.power.iter_mut().zip(buf.iter()) {
+            *power += re * re + im * im;
+        }
+
+        self.frames += 1;
+        self.frame.clear();
+    }
+
+    fn finish(self, sample_rate: u32, bit_depth: u32) -> Authenticity {
+        let effective_bit_depth = if self.used_bits == 0 {
+            0
+        } else {
+            (32 - self.used_bits.trailing_zeros()).min(bit_depth)
+        };
+
+        let cutoff = if self.frames == 0 {
+            None
+        } else {
+            let (levels, band_width) = band_levels(&self.power, sample_rate);
+            find_cutoff(&levels, band_width)
+        };
+
+        Authenticity {
+            sample_rate,
+            bit_depth,
+            effective_bit_depth,
+            cutoff,
+        }
+    }
+}
+
+// The spectrum in dB, in bands of about BAND_WIDTH Hz. Returns the real width of a band.
+fn band_levels(power: &[f64], sample_rate: u32) -> (Vec<f64>, f64) {
+    let bin_width = sample_rate as f64 / FFT_SIZE as f64;
+    let bins_per_band = (BAND_WIDTH / bin_width).round().max(1.0) as usize;
+
+    let levels = power
+        .chunks(bins_per_band)
+        .map(|band| {
+            let mean = band.iter().sum::<f64>() / band.len() as f64;
+            10.0 * (mean + SILENCE).log10()
+        })
+        .collect();
+
+    (levels, bins_per_band as f64 * bin_width)
+}
+
+// Working down from the top, the first band which stands high above the bands a little way above
+// it. Returns the frequency at the top of that band.
+fn find_cutoff(levels: &[f64], band_width: f64) -> Option<u32> {
+    let highest = levels.len().checked_sub(CLIFF_GAP + CLIFF_SPAN + 1)?;
+
+    (0..=highest)
+        .rev()
+        .take_while(|i| (*i + 1) as f64 * band_width >= LOWEST_CUTOFF)
+        .find(|&i| {
+            let above = levels[i + CLIFF_GAP + 1..=i + CLIFF_GAP + CLIFF_SPAN]
+                .iter()
+                .copied()
+                .fold(f64::MIN, f64::max);
+            levels[i] - above >= CLIFF_DEPTH
+        })
+        .map(|i| ((i + 1) as f64 * band_width).round() as u32)
+}
+
+// In-place radix-2 FFT. The twiddle factors are for a transform of the full length of buf.
+fn fft(buf: &mut [(f64, f64)], twiddles: &[(f64, f64)]) {
+    let n = buf.len();
+    let mut j = 0;
+
+    for i in 1..n {
+        let mut bit = n >> 1;
+
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+
+        j |= bit;
+
+        if i < j {
+            buf.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+
+    while len <= n {
+        let stride = n / len;
+
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (wr, wi) = twiddles[k * stride];
+                let (ur, ui) = buf[start + k];
+                let (xr, xi) = buf[start + k + len / 2];
+                let (vr, vi) = (xr * wr - xi * wi, xr * wi + xi * wr);
+                buf[start + k] = (ur + vr, ui + vi);
+                buf[start + k + len / 2] = (ur - vr, ui - vi);
+            }
+        }
+
+        len <<= 1;
+    }
+}
+
+fn khz(hz: u32) -> String {
+    format!("{:.1}kHz", hz as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    // A tone every 200Hz, up to the given frequency, quantised to the given number of bits.
+    fn tones(sample_rate: u32, highest: f64, bits: u32) -> Vec<i32> {
+        let frequencies: Vec<f64> = (1..)
+            .map(|i| i as f64 * 200.0)
+            .take_while(|f| *f <= highest)
+            .collect();
+        let amplitude = 0.5 / frequencies.len() as f64;
+        let step = 1i64 << (32 - bits);
+
+        (0..FFT_SIZE * 8)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                let s: f64 = frequencies
+                    .iter()
+                    .enumerate()
+                    .map(|(n, f)| amplitude * (2.0 * PI * f * t + n as f64).sin())
+                    .sum();
+                let raw = (s * -(i32::MIN as f64)) as i64;
+                (raw / step * step) as i32
+            })
+            .collect()
+    }
+
+    fn analyse(samples: &[i32], sample_rate: u32, bit_depth: u32) -> Authenticity {
+        let mut analyser = Analyser::new(1);
+        analyser.add_interleaved(samples);
+        analyser.finish(sample_rate, bit_depth)
+    }
+
+    #[test]
+    fn test_fft() {
+        let twiddles: Vec<(f64, f64)> = (0..4)
+            .map(|k| {
+                let angle = -2.0 * PI * k as f64 / 8.0;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+        let mut buf: Vec<(f64, f64)> = (0..8)
+            .map(|i| ((2.0 * PI * i as f64 / 8.0).cos(), 0.0))
+            .collect();
+
+        fft(&mut buf, &twiddles);
+
+        let magnitudes: Vec<i64> = buf
+            .iter()
+            .map(|(re, im)| (re * re + im * im).sqrt().round() as i64)
+            .collect();
+
+        assert_eq!(vec![0, 4, 0, 0, 0, 0, 0, 4], magnitudes);
+    }
+
+    #[test]
+    fn test_full_bandwidth() {
+        let result = analyse(&tones(44100, 21800.0, 16), 44100, 16);
+        assert_eq!(None, result.cutoff);
+        assert_eq!(16, result.effective_bit_depth);
+        assert!(result.suspicions().is_empty());
+    }
+
+    #[test]
+    fn test_lossy_source() {
+        let result = analyse(&tones(44100, 16000.0, 16), 44100, 16);
+        let cutoff = result.cutoff.unwrap();
+        assert!((16000..16500).contains(&cutoff));
+        assert_eq!(vec![Suspicion::LossySource(cutoff)], result.suspicions());
+    }
+
+    #[test]
+    fn test_upsampled() {
+        let result = analyse(&tones(96000, 21800.0, 24), 96000, 24);
+        let cutoff = result.cutoff.unwrap();
+        assert!((21800..22500).contains(&cutoff));
+        assert_eq!(
+            vec![Suspicion::Upsampled(96000, cutoff)],
+            result.suspicions()
+        );
+    }
+
+    #[test]
+    fn test_padded_bit_depth() {
+        let result = analyse(&tones(44100, 21800.0, 16), 44100, 24);
+        assert_eq!(16, result.effective_bit_depth);
+        assert_eq!(vec![Suspicion::PaddedBitDepth(24, 16)], result.suspicions());
+        assert_eq!(Some("aur cdq"), result.suspicions()[0].remedy());
+
+        let result = analyse(&tones(44100, 21800.0, 24), 44100, 24);
+        assert_eq!(24, result.effective_bit_depth);
+        assert!(result.suspicions().is_empty());
+    }
+
+    #[test]
+    fn test_silence() {
+        let result = analyse(&[0; FFT_SIZE * 2], 44100, 16);
+        assert_eq!(None, result.cutoff);
+        assert_eq!(0, result.effective_bit_depth);
+        assert!(result.suspicions().is_empty());
+    }
+
+    #[test]
+    fn test_from_path() {
+        let result = Authenticity::from_path(&fixture!("info/test.flac")).unwrap();
+        assert_eq!(44100, result.sample_rate);
+        assert_eq!(16, result.bit_depth);
+        assert!(Authenticity::from_path(&fixture!("info/test.mp3")).is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            "Spectral cutoff at 16.1kHz suggests a lossy source",
+            Suspicion::LossySource(16125).message()
+        );
+        assert_eq!(
+            "96000Hz audio has nothing above 22.0kHz, so looks upsampled",
+            Suspicion::Upsampled(96000, 22000).message()
+        );
+    }
+}
@@ -1,7 +1,11 @@
-use crate::utils::types::{Genres, WantsList};
+use crate::utils::lint_cache::StableHash;
+use crate::utils::profile::{Profile, unknown_profile};
+use crate::utils::types::{DiscStyle, Genres, Severity};
 use anyhow::anyhow;
-use camino::{Utf8Path, Utf8PathBuf};
-use serde::Deserialize;
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 
@@ -9,8 +13,13 @@ pub const ARTWORK_FILENAME: &str = "cover.jpg";
 pub const MAX_ARTWORK_SIZE: usize = 750;
 pub const MIN_ARTWORK_SIZE: usize = 350;
 pub const ARTWORK_QUALITY: u8 = 75;
+pub const DEFAULT_MAX_SILENCE: u64 = 10;
+pub const DEFAULT_MIN_TRACK_LENGTH: u64 = 5;
+pub const DEFAULT_SQUARE_TOLERANCE: f64 = 3.0;
+
+// Marks an ignore rule as a regular expression rather than a glob.
+const REGEX_PREFIX: &str = "re:";
 
-type TagList = HashSet<String>;
 type WordList = HashSet<String>;
 
 #[derive(Deserialize, Debug)]
@@ -18,21 +27,65 @@ pub struct Config {
     ignore: Option<Ignore>,
     words: Option<Words>,
     genres: Option<Genres>,
+    disc_style: Option<DiscStyle>,
+    lint: Option<Lint>,
+    artfix: Option<Artfix>,
+    profiles: Option<HashMap<String, Profile>>,
+    syncflac: Option<Syncflac>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Syncflac {
+    mirrors: Option<Vec<Mirror>>,
+}
+
+// A lossy hierarchy, like opus/, which syncflac keeps in step with flac/ as well as mp3/. `dir` is
+// its name under the root, and its files are made by the named profile.
+#[derive(Deserialize, Debug)]
+pub struct Mirror {
+    pub dir: String,
+    pub profile: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Artfix {
+    square_tolerance: Option<f64>,
+}
+
+// Rules are named by the codes lint and lintdir use in their JSON output.
+#[derive(Deserialize, Debug)]
+pub struct Lint {
+    rules: Option<HashMap<String, Severity>>,
+    overrides: Option<Vec<RuleOverride>>,
+    max_silence: Option<u64>,
+    min_track_length: Option<u64>,
+}
+
+// Applies to any path matched by one of the given rules. Where overrides disagree, the last one
+// wins.
+#[derive(Deserialize, Debug)]
+pub struct RuleOverride {
+    paths: PathMatcher,
+    rules: HashMap<String, Severity>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Ignore {
+    artdupes: Option<PathMatcher>,
     lint: Option<LintErrs>,
     lintdir: Option<LintDirErrs>,
-    syncflac: Option<HashSet<String>>,
+    syncflac: Option<PathMatcher>,
     wantflac: Option<WantFlac>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct WantFlac {
-    albums: Option<WantsList>,
-    top_level: Option<WantsList>,
-    tracks: Option<WantsList>,
+    #[serde(default, deserialize_with = "whole_paths")]
+    albums: Option<PathMatcher>,
+    #[serde(default, deserialize_with = "leading_paths")]
+    top_level: Option<PathMatcher>,
+    #[serde(default, deserialize_with = "whole_paths")]
+    tracks: Option<PathMatcher>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -45,17 +98,172 @@ pub struct Words {
 
 #[derive(Deserialize, Debug)]
 pub struct LintErrs {
-    pub invalid_album_tag: Option<TagList>,
-    pub invalid_artist_tag: Option<TagList>,
-    pub invalid_title_tag: Option<TagList>,
-    pub invalid_year_tag: Option<TagList>,
-    pub invalid_filename: Option<HashSet<String>>,
+    pub bad_capitalisation: Option<PathMatcher>,
+    pub invalid_album_tag: Option<PathMatcher>,
+    pub invalid_artist_tag: Option<PathMatcher>,
+    pub invalid_title_tag: Option<PathMatcher>,
+    pub invalid_year_tag: Option<PathMatcher>,
+    pub invalid_filename: Option<PathMatcher>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct LintDirErrs {
-    pub bad_file_count: Option<HashSet<String>>,
-    pub inconsistent_tags: Option<HashSet<String>>,
+    pub bad_file_count: Option<PathMatcher>,
+    pub inconsistent_tags: Option<PathMatcher>,
+}
+
+// Every ignore list in the config is one of these. A rule is a glob unless it starts "re:", in
+// which case it is a regex, anchored at both ends and matched against the whole path. Globs match
+// any run of whole path components, so "eps" matches "flac/eps/band.ep/01.a.b.flac" but not
+// "flac/peps". A glob beginning with "/" must match from the root. wantflac's lists are anchored
+// more tightly: see Anchor.
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "Vec<String>")]
+pub struct PathMatcher {
+    rules: Vec<PathRule>,
+    anchor: Anchor,
+}
+
+// Which runs of components a glob may match. wantflac has always compared whole paths, or for
+// top_level, the start of them, and matching part-way along would hide too much.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    Anywhere,
+    Start,
+    Whole,
+}
+
+#[derive(Debug)]
+struct PathRule {
+    source: String,
+    kind: RuleKind,
+}
+
+#[derive(Debug)]
+enum RuleKind {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl TryFrom<Vec<String>> for PathMatcher {
+    type Error = anyhow::Error;
+
+    fn try_from(sources: Vec<String>) -> anyhow::Result<Self> {
+        let rules = sources
+            .iter()
+            .map(|s| PathRule::new(s))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self {
+            rules,
+            anchor: Anchor::Anywhere,
+        })
+    }
+}
+
+fn whole_paths<'de, D: Deserializer<'de>>(d: D) -> Result<Option<PathMatcher>, D::Error> {
+    Ok(Option::<PathMatcher>::deserialize(d)?.map(|m| PathMatcher {
+        anchor: Anchor::Whole,
+        ..m
+    }))
+}
+
+fn leading_paths<'de, D: Deserializer<'de>>(d: D) -> Result<Option<PathMatcher>, D::Error> {
+    Ok(Option::<PathMatcher>::deserialize(d)?.map(|m| PathMatcher {
+        anchor: Anchor::Start,
+        ..m
+    }))
+}
+
+impl PathMatcher {
+    pub fn is_match(&self, path: &Utf8Path) -> bool {
+        self.matching_rule(path).is_some()
+    }
+
+    // The first rule which matches, as it was written in the config.
+    pub fn matching_rule(&self, path: &Utf8Path) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|r| r.is_match(path, self.anchor))
+            .map(|r| r.source.as_str())
+    }
+
+    // Rules, as written in the config, which match none of the given paths.
+    pub fn unmatched_rules(&self, paths: &[Utf8PathBuf]) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|r| !paths.iter().any(|p| r.is_match(p, self.anchor)))
+            .map(|r| r.source.as_str())
+            .collect()
+    }
+}
+
+impl PathRule {
+    fn new(source: &str) -> anyhow::Result<Self> {
+        let kind = match source.strip_prefix(REGEX_PREFIX) {
+            Some(rx) => RuleKind::Regex(
+                Regex::new(&format!("^(?:{})$", rx))
+                    .map_err(|e| anyhow!("bad ignore rule '{}': {}", source, e))?,
+            ),
+            None => RuleKind::Glob(
+                Pattern::new(source.trim_end_matches('/'))
+                    .map_err(|e| anyhow!("bad ignore rule '{}': {}", source, e))?,
+            ),
+        };
+
+        Ok(Self {
+            source: source.to_owned(),
+            kind,
+        })
+    }
+
+    fn is_match(&self, path: &Utf8Path, anchor: Anchor) -> bool {
+        match &self.kind {
+            RuleKind::Regex(rx) => rx.is_match(path.as_str()),
+            RuleKind::Glob(pattern) => {
+                let options = MatchOptions {
+                    require_literal_separator: true,
+                    ..MatchOptions::default()
+                };
+
+                component_runs(path, self.source.starts_with('/'), anchor)
+                    .iter()
+                    .any(|run| pattern.matches_with(run, options))
+            }
+        }
+    }
+}
+
+fn component_runs(path: &Utf8Path, from_root: bool, anchor: Anchor) -> Vec<String> {
+    let parts: Vec<&str> = path
+        .components()
+        .filter_map(|c| match c {
+            Utf8Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect();
+
+    let starts = if from_root || anchor != Anchor::Anywhere {
+        0..1
+    } else {
+        0..parts.len()
+    };
+
+    let mut ret = Vec::new();
+
+    for start in starts {
+        let first_end = if anchor == Anchor::Whole {
+            parts.len()
+        } else {
+            start + 1
+        };
+
+        for end in first_end..=parts.len() {
+            let run = parts[start..end].join("/");
+            ret.push(if from_root { format!("/{}", run) } else { run });
+        }
+    }
+
+    ret
 }
 
 fn home_dir() -> Utf8PathBuf {
@@ -70,6 +278,19 @@ pub fn default_linkdir() -> Utf8PathBuf {
     home_dir().join("work").join("artfix")
 }
 
+pub fn default_cache_dir() -> Utf8PathBuf {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => Utf8PathBuf::from(dir).join("aur"),
+        _ => home_dir().join(".cache").join("aur"),
+    }
+}
+
+fn sorted<T: Ord + Clone>(items: Option<impl IntoIterator<Item = T>>) -> Vec<T> {
+    let mut ret: Vec<T> = items.into_iter().flatten().collect();
+    ret.sort();
+    ret
+}
+
 // If the user specifies a file and it doesn't exist, that's an error. If they don't, and the
 // default file doesn't exist, that's fine, and we return an empty config.
 //
@@ -85,21 +306,21 @@ pub fn load_config(file: &Utf8Path) -> anyhow::Result<Config> {
 }
 
 impl Config {
-    pub fn get_wantflac_ignore_tracks(&self) -> Option<&WantsList> {
+    pub fn get_wantflac_ignore_tracks(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.wantflac.as_ref())
             .and_then(|wantflac| wantflac.tracks.as_ref())
     }
 
-    pub fn get_wantflac_ignore_albums(&self) -> Option<&WantsList> {
+    pub fn get_wantflac_ignore_albums(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.wantflac.as_ref())
             .and_then(|wantflac| wantflac.albums.as_ref())
     }
 
-    pub fn get_wantflac_ignore_top_level(&self) -> Option<&WantsList> {
+    pub fn get_wantflac_ignore_top_level(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.wantflac.as_ref())
@@ -126,55 +347,75 @@ impl Config {
             .and_then(|words| words.ignore_case.as_ref())
     }
 
-    pub fn get_syncflac_list(&self) -> Option<&HashSet<String>> {
+    pub fn get_artdupes_list(&self) -> Option<&PathMatcher> {
+        self.ignore
+            .as_ref()
+            .and_then(|ignore| ignore.artdupes.as_ref())
+    }
+
+    pub fn get_syncflac_list(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.syncflac.as_ref())
     }
 
-    pub fn get_ignore_lint_invalid_album(&self) -> Option<&HashSet<String>> {
+    pub fn get_syncflac_mirrors(&self) -> &[Mirror] {
+        self.syncflac
+            .as_ref()
+            .and_then(|syncflac| syncflac.mirrors.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn get_ignore_lint_bad_capitalisation(&self) -> Option<&PathMatcher> {
+        self.ignore
+            .as_ref()
+            .and_then(|ignore| ignore.lint.as_ref())
+            .and_then(|lint| lint.bad_capitalisation.as_ref())
+    }
+
+    pub fn get_ignore_lint_invalid_album(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lint.as_ref())
             .and_then(|lint| lint.invalid_album_tag.as_ref())
     }
 
-    pub fn get_ignore_lint_invalid_title(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lint_invalid_title(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lint.as_ref())
             .and_then(|lint| lint.invalid_title_tag.as_ref())
     }
 
-    pub fn get_ignore_lint_invalid_artist(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lint_invalid_artist(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lint.as_ref())
             .and_then(|lint| lint.invalid_artist_tag.as_ref())
     }
 
-    pub fn get_ignore_lint_invalid_year(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lint_invalid_year(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lint.as_ref())
             .and_then(|lint| lint.invalid_year_tag.as_ref())
     }
 
-    pub fn get_ignore_lint_invalid_filename(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lint_invalid_filename(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lint.as_ref())
             .and_then(|lint| lint.invalid_filename.as_ref())
     }
 
-    pub fn get_ignore_lintdir_bad_file_count(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lintdir_bad_file_count(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lintdir.as_ref())
             .and_then(|lintdir| lintdir.bad_file_count.as_ref())
     }
 
-    pub fn get_ignore_lintdir_inconsistent_tags(&self) -> Option<&HashSet<String>> {
+    pub fn get_ignore_lintdir_inconsistent_tags(&self) -> Option<&PathMatcher> {
         self.ignore
             .as_ref()
             .and_then(|ignore| ignore.lintdir.as_ref())
@@ -184,13 +425,205 @@ impl Config {
     pub fn get_genres(&self) -> Option<&Genres> {
         self.genres.as_ref()
     }
+
+    pub fn get_disc_style(&self) -> DiscStyle {
+        self.disc_style.unwrap_or_default()
+    }
+
+    // Seconds of silence allowed at either end of a track, before lint --audio complains.
+    pub fn get_max_silence(&self) -> u64 {
+        self.lint
+            .as_ref()
+            .and_then(|lint| lint.max_silence)
+            .unwrap_or(DEFAULT_MAX_SILENCE)
+    }
+
+    // In seconds. Anything shorter is probably a mistake.
+    pub fn get_min_track_length(&self) -> u64 {
+        self.lint
+            .as_ref()
+            .and_then(|lint| lint.min_track_length)
+            .unwrap_or(DEFAULT_MIN_TRACK_LENGTH)
+    }
+
+    // As a percentage of the shorter side. Artwork closer to square than this is squared by
+    // artfix, rather than being left for a human.
+    pub fn get_square_tolerance(&self) -> f64 {
+        self.artfix
+            .as_ref()
+            .and_then(|artfix| artfix.square_tolerance)
+            .unwrap_or(DEFAULT_SQUARE_TOLERANCE)
+    }
+
+    // A profile in the config file replaces the built-in one of the same name.
+    pub fn get_profile(&self, name: &str) -> anyhow::Result<Profile> {
+        let configured = self
+            .profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(name))
+            .map(|profile| {
+                let mut profile = profile.clone();
+                profile.name = name.to_owned();
+                profile
+            });
+
+        let profile = configured
+            .or_else(|| Profile::builtin(name))
+            .ok_or_else(|| unknown_profile(name))?;
+
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    // Changes whenever something which affects what lint and lintdir find does, so their cached
+    // results can be thrown away. Ignore lists and severities are applied after the cache, so they
+    // don't count.
+    pub fn checks_hash(&self) -> u64 {
+        let mut hash = StableHash::default();
+        hash.add(env!("CARGO_PKG_VERSION"));
+
+        for (name, words) in [
+            ("all_caps", sorted(self.get_words_all_caps())),
+            ("no_caps", sorted(self.get_words_no_caps())),
+            ("ignore_case", sorted(self.get_words_ignore_case())),
+            ("genres", sorted(self.get_genres())),
+        ] {
+            hash.add(name);
+
+            for word in words {
+                hash.add(word);
+            }
+        }
+
+        hash.add("expand");
+
+        for (from, to) in sorted(self.get_words_expand()) {
+            hash.add(from).add(to);
+        }
+
+        hash.add(format!("{:?}", self.get_disc_style()))
+            .add(self.get_max_silence().to_string())
+            .add(self.get_min_track_length().to_string())
+            .finish()
+    }
+
+    // Every ignore list, named as it is in the config file.
+    pub fn ignore_lists(&self) -> Vec<(String, &PathMatcher)> {
+        let lists = [
+            ("ignore.artdupes", self.get_artdupes_list()),
+            (
+                "ignore.lint.bad_capitalisation",
+                self.get_ignore_lint_bad_capitalisation(),
+            ),
+            (
+                "ignore.lint.invalid_album_tag",
+                self.get_ignore_lint_invalid_album(),
+            ),
+            (
+                "ignore.lint.invalid_artist_tag",
+                self.get_ignore_lint_invalid_artist(),
+            ),
+            (
+                "ignore.lint.invalid_title_tag",
+                self.get_ignore_lint_invalid_title(),
+            ),
+            (
+                "ignore.lint.invalid_year_tag",
+                self.get_ignore_lint_invalid_year(),
+            ),
+            (
+                "ignore.lint.invalid_filename",
+                self.get_ignore_lint_invalid_filename(),
+            ),
+            (
+                "ignore.lintdir.bad_file_count",
+                self.get_ignore_lintdir_bad_file_count(),
+            ),
+            (
+                "ignore.lintdir.inconsistent_tags",
+                self.get_ignore_lintdir_inconsistent_tags(),
+            ),
+            ("ignore.syncflac", self.get_syncflac_list()),
+            ("ignore.wantflac.albums", self.get_wantflac_ignore_albums()),
+            (
+                "ignore.wantflac.top_level",
+                self.get_wantflac_ignore_top_level(),
+            ),
+            ("ignore.wantflac.tracks", self.get_wantflac_ignore_tracks()),
+        ];
+
+        let mut ret: Vec<(String, &PathMatcher)> = lists
+            .into_iter()
+            .filter_map(|(name, list)| list.map(|l| (name.to_owned(), l)))
+            .collect();
+
+        if let Some(overrides) = self.lint.as_ref().and_then(|l| l.overrides.as_ref()) {
+            for (i, o) in overrides.iter().enumerate() {
+                ret.push((format!("lint.overrides[{}].paths", i), &o.paths));
+            }
+        }
+
+        ret
+    }
+
+    // Rules are looked up by name, so a misspelt one would otherwise be quietly ignored.
+    pub fn check_rule_names(&self, known: &[&str]) -> anyhow::Result<()> {
+        let lint = match self.lint.as_ref() {
+            Some(lint) => lint,
+            None => return Ok(()),
+        };
+
+        let mut named: Vec<(String, &String)> = lint
+            .rules
+            .iter()
+            .flatten()
+            .map(|(rule, _)| ("lint.rules".to_owned(), rule))
+            .collect();
+
+        for (i, o) in lint.overrides.iter().flatten().enumerate() {
+            named.extend(
+                o.rules
+                    .keys()
+                    .map(|rule| (format!("lint.overrides[{}].rules", i), rule)),
+            );
+        }
+
+        named.sort();
+
+        match named
+            .iter()
+            .find(|(_, rule)| !known.contains(&rule.as_str()))
+        {
+            Some((section, rule)) => Err(anyhow!("unknown rule '{}' in {}", rule, section)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn get_rule_severity(&self, rule: &str, path: &Utf8Path) -> Severity {
+        let lint = match self.lint.as_ref() {
+            Some(lint) => lint,
+            None => return Severity::default(),
+        };
+
+        let overridden = lint.overrides.iter().flatten().rev().find_map(|o| {
+            if o.paths.is_match(path) {
+                o.rules.get(rule).copied()
+            } else {
+                None
+            }
+        });
+
+        overridden
+            .or_else(|| lint.rules.as_ref().and_then(|r| r.get(rule).copied()))
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::utils::profile::Encoder;
     use snltest::fixture;
-    use std::collections::BTreeSet;
 
     fn sample_config() -> Config {
         load_config(&fixture!("config/test.toml")).unwrap()
@@ -205,18 +638,48 @@ mod test {
     fn test_wantflac() {
         let config = sample_config();
 
-        assert_eq!(
-            &BTreeSet::from(["singer.song".to_owned()]),
-            config.get_wantflac_ignore_tracks().unwrap()
+        assert!(
+            config
+                .get_wantflac_ignore_tracks()
+                .unwrap()
+                .is_match(Utf8Path::new("singer.song"))
         );
 
-        assert_eq!(
-            &BTreeSet::from(["albums/abc/artist.album".to_owned()]),
-            config.get_wantflac_ignore_albums().unwrap()
+        assert!(
+            config
+                .get_wantflac_ignore_albums()
+                .unwrap()
+                .is_match(Utf8Path::new("albums/abc/artist.album"))
+        );
+
+        let no_config = load_config(&fixture!("config/empty.toml")).unwrap();
+        assert!(no_config.get_wantflac_ignore_tracks().is_none());
+    }
+
+    #[test]
+    fn test_artdupes() {
+        assert!(
+            sample_config()
+                .get_artdupes_list()
+                .unwrap()
+                .is_match(Utf8Path::new("/flac/albums/abc/band.reissue/cover.jpg"))
         );
 
         let no_config = load_config(&fixture!("config/empty.toml")).unwrap();
-        assert_eq!(None, no_config.get_wantflac_ignore_tracks());
+        assert!(no_config.get_artdupes_list().is_none());
+    }
+
+    #[test]
+    fn test_syncflac_mirrors() {
+        let config = sample_config();
+        let mirrors = config.get_syncflac_mirrors();
+
+        assert_eq!(1, mirrors.len());
+        assert_eq!("opus", mirrors[0].dir);
+        assert_eq!("opus", mirrors[0].profile);
+
+        let no_config = load_config(&fixture!("config/empty.toml")).unwrap();
+        assert!(no_config.get_syncflac_mirrors().is_empty());
     }
 
     #[test]
@@ -245,11 +708,108 @@ mod test {
     fn test_ignore_lint() {
         let config = sample_config();
         assert_eq!(
-            &HashSet::from(["The R&B of Membership".to_owned()]),
-            config.get_ignore_lint_invalid_album().unwrap()
+            Some("The R&B of Membership"),
+            config
+                .get_ignore_lint_invalid_album()
+                .unwrap()
+                .matching_rule(Utf8Path::new("/music/The R&B of Membership/01.a.b.flac"))
+        );
+
+        assert!(config.get_ignore_lint_invalid_artist().is_none());
+    }
+
+    #[test]
+    fn test_path_matcher() {
+        let matcher = PathMatcher::try_from(vec![
+            "eps".to_owned(),
+            "albums/*/various.*".to_owned(),
+            "/storage/flac/new/".to_owned(),
+            r"re:.*/disc_\d+".to_owned(),
+        ])
+        .unwrap();
+
+        let matches = |p: &str| matcher.matching_rule(Utf8Path::new(p));
+
+        assert_eq!(Some("eps"), matches("/storage/flac/eps/band.ep"));
+        assert_eq!(Some("eps"), matches("eps"));
+        assert_eq!(None, matches("/storage/flac/peps/band.ep"));
+        assert_eq!(None, matches("/storage/flac/albums/abc/band.eps"));
+        assert_eq!(
+            Some("albums/*/various.*"),
+            matches("/storage/mp3/albums/tuv/various.compilation/01.a.b.mp3")
+        );
+        assert_eq!(None, matches("/storage/mp3/albums/tuv/band.various"));
+        assert_eq!(None, matches("albums/tuv/x/various.compilation"));
+        assert_eq!(
+            Some("/storage/flac/new/"),
+            matches("/storage/flac/new/band.album")
+        );
+        assert_eq!(None, matches("/backup/storage/flac/new/band.album"));
+        assert_eq!(
+            Some(r"re:.*/disc_\d+"),
+            matches("/storage/flac/albums/abc/band.album/disc_2")
+        );
+        assert_eq!(None, matches("/storage/flac/albums/abc/band.album/disc_2x"));
+    }
+
+    #[test]
+    fn test_path_matcher_anchored() {
+        let config = sample_config();
+        let albums = config.get_wantflac_ignore_albums().unwrap();
+        let top_level = config.get_wantflac_ignore_top_level().unwrap();
+        let tracks = config.get_wantflac_ignore_tracks().unwrap();
+
+        assert!(albums.is_match(Utf8Path::new("albums/abc/artist.album")));
+        assert!(!albums.is_match(Utf8Path::new("albums/abc/artist.album/disc_1")));
+        assert!(!albums.is_match(Utf8Path::new("x/albums/abc/artist.album")));
+        assert!(top_level.is_match(Utf8Path::new("audiobooks/writer")));
+        assert!(!top_level.is_match(Utf8Path::new("albums/audiobooks")));
+        assert!(tracks.is_match(Utf8Path::new("singer.song")));
+        assert!(config.get_artdupes_list().unwrap().is_match(Utf8Path::new(
+            "/storage/flac/albums/abc/band.reissue/disc_1"
+        )));
+    }
+
+    #[test]
+    fn test_unmatched_rules() {
+        let matcher =
+            PathMatcher::try_from(vec!["eps".to_owned(), "albums/*/gone.*".to_owned()]).unwrap();
+
+        assert_eq!(
+            vec!["albums/*/gone.*"],
+            matcher.unmatched_rules(&[
+                Utf8PathBuf::from("/storage/flac/eps/band.ep"),
+                Utf8PathBuf::from("/storage/flac/albums/abc/band.album"),
+            ])
         );
+    }
+
+    #[test]
+    fn test_path_matcher_bad_rule() {
+        assert!(PathMatcher::try_from(vec!["albums/[abc".to_owned()]).is_err());
+        assert!(PathMatcher::try_from(vec!["re:(".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_ignore_lists() {
+        let names: Vec<String> = sample_config()
+            .ignore_lists()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
 
-        assert_eq!(None, config.get_ignore_lint_invalid_artist());
+        assert_eq!(
+            vec![
+                "ignore.artdupes",
+                "ignore.lint.bad_capitalisation",
+                "ignore.lint.invalid_album_tag",
+                "ignore.lint.invalid_title_tag",
+                "ignore.wantflac.albums",
+                "ignore.wantflac.top_level",
+                "ignore.wantflac.tracks",
+            ],
+            names
+        );
     }
 
     #[test]
@@ -265,4 +825,153 @@ mod test {
             config.get_genres().unwrap()
         );
     }
+
+    #[test]
+    fn test_check_rule_names() {
+        let config = load_config(&fixture!("config/rules.toml")).unwrap();
+        let known = [
+            "bad_file_count",
+            "cover_art_too_small",
+            "embedded_artwork",
+            "invalid_filename",
+            "invalid_genre",
+        ];
+
+        assert!(config.check_rule_names(&known).is_ok());
+        assert!(sample_config().check_rule_names(&[]).is_ok());
+        assert_eq!(
+            "unknown rule 'cover_art_too_small' in lint.rules",
+            config
+                .check_rule_names(&[
+                    "bad_file_count",
+                    "embedded_artwork",
+                    "invalid_filename",
+                    "invalid_genre"
+                ])
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            "unknown rule 'bad_file_count' in lint.overrides[0].rules",
+            config
+                .check_rule_names(&known[1..])
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_get_rule_severity() {
+        let config = load_config(&fixture!("config/rules.toml")).unwrap();
+        let album = Utf8Path::new("/storage/flac/albums/abc/artist.album");
+        let ep = Utf8Path::new("/storage/flac/eps/artist.ep");
+        let single = Utf8Path::new("/storage/flac/eps/artist.single");
+
+        assert_eq!(
+            Severity::Warn,
+            config.get_rule_severity("cover_art_too_small", album)
+        );
+        assert_eq!(
+            Severity::Off,
+            config.get_rule_severity("embedded_artwork", album)
+        );
+        assert_eq!(
+            Severity::Error,
+            config.get_rule_severity("invalid_filename", album)
+        );
+        assert_eq!(
+            Severity::Off,
+            config.get_rule_severity("bad_file_count", ep)
+        );
+        assert_eq!(
+            Severity::Warn,
+            config.get_rule_severity("cover_art_too_small", ep)
+        );
+        assert_eq!(
+            Severity::Warn,
+            config.get_rule_severity("bad_file_count", single)
+        );
+        assert_eq!(
+            Severity::Error,
+            sample_config().get_rule_severity("cover_art_too_small", album)
+        );
+    }
+
+    #[test]
+    fn test_checks_hash() {
+        let config = sample_config();
+
+        assert_eq!(config.checks_hash(), sample_config().checks_hash());
+        assert_ne!(
+            config.checks_hash(),
+            load_config(&fixture!("config/empty.toml"))
+                .unwrap()
+                .checks_hash()
+        );
+        assert_eq!(
+            load_config(&fixture!("config/empty.toml"))
+                .unwrap()
+                .checks_hash(),
+            load_config(&fixture!("config/rules.toml"))
+                .unwrap()
+                .checks_hash()
+        );
+    }
+
+    #[test]
+    fn test_audio_limits() {
+        let config = sample_config();
+        assert_eq!(30, config.get_max_silence());
+        assert_eq!(DEFAULT_MIN_TRACK_LENGTH, config.get_min_track_length());
+
+        let config = load_config(&fixture!("config/empty.toml")).unwrap();
+        assert_eq!(DEFAULT_MAX_SILENCE, config.get_max_silence());
+    }
+
+    #[test]
+    fn test_square_tolerance() {
+        assert_eq!(1.5, sample_config().get_square_tolerance());
+
+        let config = load_config(&fixture!("config/empty.toml")).unwrap();
+        assert_eq!(DEFAULT_SQUARE_TOLERANCE, config.get_square_tolerance());
+    }
+
+    #[test]
+    fn test_get_profile() {
+        let config = sample_config();
+        let profile = config.get_profile("opus").unwrap();
+
+        assert_eq!("opus", profile.name);
+        assert_eq!(Encoder::Ffmpeg, profile.encoder);
+        assert_eq!(vec!["-c:a", "libopus", "-b:a", "128k"], profile.args);
+        assert_eq!("opus", profile.extension);
+        assert_eq!("128k", profile.suffix());
+
+        let profile = config.get_profile("medium").unwrap();
+        assert_eq!(vec!["-q2", "--preset", "medium"], profile.args);
+        assert_eq!("medium", profile.suffix());
+
+        assert_eq!(
+            Profile::builtin("insane").unwrap(),
+            config.get_profile("insane").unwrap()
+        );
+        assert_eq!(
+            "unknown encoding profile 'loud'",
+            config.get_profile("loud").unwrap_err().to_string()
+        );
+
+        let config = load_config(&fixture!("config/bad_profile.toml")).unwrap();
+        assert!(config.get_profile("lame_flac").is_err());
+    }
+
+    #[test]
+    fn test_get_disc_style() {
+        assert_eq!(DiscStyle::AlbumSuffix, sample_config().get_disc_style());
+        assert_eq!(
+            DiscStyle::Tag,
+            load_config(&fixture!("config/disc_tags.toml"))
+                .unwrap()
+                .get_disc_style()
+        );
+    }
 }
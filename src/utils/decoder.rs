@@ -0,0 +1,79 @@
+use anyhow::anyhow;
+use camino::Utf8Path;
+use std::fs::File;
+use std::io::ErrorKind;
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{CodecParameters, DecoderOptions};
+use symphonia::core::conv::ConvertibleSample;
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// The only formats symphonia is built to decode.
+const DECODABLE_TYPES: [&str; 2] = ["flac", "mp3"];
+
+pub fn can_decode(file: &Utf8Path) -> bool {
+    file.extension()
+        .is_some_and(|e| DECODABLE_TYPES.contains(&e))
+}
+
+/// Decodes the whole of the given file natively, handing each block of interleaved samples to
+/// `each_block` as it goes. Returns what the container says about the audio.
+pub fn decode<S, F>(file: &Utf8Path, mut each_block: F) -> anyhow::Result<CodecParameters>
+where
+    S: ConvertibleSample,
+    F: FnMut(SignalSpec, &[S]),
+{
+    let mss = MediaSourceStream::new(Box::new(File::open(file)?), Default::default());
+    let mut hint = Hint::new();
+
+    if let Some(suffix) = file.extension() {
+        hint.with_extension(suffix);
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut reader = probed.format;
+
+    let track = match reader.default_track() {
+        Some(track) => track,
+        None => return Err(anyhow!("no audio in {}", file)),
+    };
+
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let mut decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        // A damaged frame is no reason to give up on the rest of the file.
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        let mut buf = SampleBuffer::<S>::new(decoded.capacity() as u64, spec);
+        buf.copy_interleaved_ref(decoded);
+        each_block(spec, buf.samples());
+    }
+
+    Ok(params)
+}
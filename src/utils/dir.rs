@@ -1,3 +1,4 @@
+use crate::utils::formats;
 use camino::{Utf8Path, Utf8PathBuf};
 use std::collections::BTreeSet;
 
@@ -9,16 +10,10 @@ pub fn media_files<T>(flist: &T) -> T
 where
     T: IntoIterator<Item = Utf8PathBuf> + FromIterator<Utf8PathBuf> + Clone,
 {
-    let flac_ext = "flac";
-    let mp3_ext = "mp3";
-
     flist
         .clone()
         .into_iter()
-        .filter(|f| match f.extension() {
-            Some(ext) => ext == flac_ext || ext == mp3_ext,
-            None => false,
-        })
+        .filter(|f| formats::for_path(f).is_some())
         .collect()
 }
 
@@ -102,6 +97,9 @@ mod tests {
             "/mp3/album/02.singer.song_02.mp3".into(),
             "/mp3/album/03.singer.song_03.mp3".into(),
             "/mp3/album/something_that_should_not_be_there".into(),
+            "/opus/album/01.singer.song_01.opus".into(),
+            "/opus/album/02.singer.song_02.ogg".into(),
+            "/m4a/album/01.singer.song_01.m4a".into(),
         ];
 
         let expected: Vec<Utf8PathBuf> = vec![
@@ -110,6 +108,9 @@ mod tests {
             "/mp3/album/01.singer.song_01.mp3".into(),
             "/mp3/album/02.singer.song_02.mp3".into(),
             "/mp3/album/03.singer.song_03.mp3".into(),
+            "/opus/album/01.singer.song_01.opus".into(),
+            "/opus/album/02.singer.song_02.ogg".into(),
+            "/m4a/album/01.singer.song_01.m4a".into(),
         ];
 
         assert_eq_unordered!(expected, media_files(&input));
@@ -1,5 +1,15 @@
-use anyhow::anyhow;
-use camino::Utf8PathBuf;
+use anyhow::{Context, anyhow, ensure};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, Once};
+use std::thread::{self, JoinHandle};
+
+// Output which is still being written, removed if we're interrupted.
+static IN_FLIGHT: Mutex<Vec<Utf8PathBuf>> = Mutex::new(Vec::new());
+static INTERRUPT_HANDLER: Once = Once::new();
 
 pub fn find_binary(name: &str) -> anyhow::Result<Utf8PathBuf> {
     for dir in [
@@ -18,3 +28,237 @@ pub fn find_binary(name: &str) -> anyhow::Result<Utf8PathBuf> {
 
     Err(anyhow!("Failed to find {name} binary"))
 }
+
+// Runs the commands `build` gives us, each reading the standard output of the one before. The
+// last one must write to the path `build` is given, which is a temporary file next to `output`,
+// with the same extension, because ffmpeg cares. Only if every command succeeds does it become
+// `output`. Otherwise, or if we're interrupted, it's removed, and the error has whatever the
+// commands which failed said on stderr.
+pub fn run_pipeline<F>(output: &Utf8Path, build: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&Utf8Path) -> Vec<Command>,
+{
+    let dir = match output.parent() {
+        Some(dir) if !dir.as_str().is_empty() => dir,
+        _ => Utf8Path::new("."),
+    };
+
+    let suffix = output
+        .extension()
+        .map_or(String::new(), |e| format!(".{e}"));
+
+    // Left to itself, tempfile makes files only we can read. A new file should get the usual
+    // permissions, less the umask, and one we replace should keep its own.
+    let tmp = tempfile::Builder::new()
+        .prefix(".aur-")
+        .suffix(&suffix)
+        .permissions(fs::Permissions::from_mode(0o666))
+        .tempfile_in(dir)
+        .context(format!("cannot create temporary file in {}", dir))?;
+
+    if let Ok(metadata) = fs::metadata(output) {
+        tmp.as_file().set_permissions(metadata.permissions())?;
+    }
+
+    let tmp_path = Utf8PathBuf::from_path_buf(tmp.path().to_path_buf())
+        .map_err(|p| anyhow!("non-UTF-8 path: {}", p.display()))?;
+
+    let _guard = InFlight::new(&tmp_path);
+    let mut cmds = build(&tmp_path);
+    ensure!(!cmds.is_empty(), "nothing to make {}", output);
+    let last = cmds.len().saturating_sub(1);
+    let mut running: Vec<(String, Child, JoinHandle<String>)> = Vec::new();
+    let mut previous_stdout = None;
+
+    for (i, cmd) in cmds.iter_mut().enumerate() {
+        let name = cmd.get_program().to_string_lossy().into_owned();
+
+        if let Some(stdout) = previous_stdout.take() {
+            cmd.stdin(Stdio::from(stdout));
+        }
+
+        cmd.stdout(if i == last {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .stderr(Stdio::piped());
+
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                for (_, mut child, _) in running {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                return Err(anyhow!("cannot run {}: {}", name, e));
+            }
+        };
+
+        previous_stdout = child.stdout.take();
+        let mut stderr = child.stderr.take();
+
+        // Read as we go, or a chatty command could fill the pipe and block.
+        let reader = thread::spawn(move || {
+            let mut ret = String::new();
+            if let Some(stderr) = stderr.as_mut() {
+                let _ = stderr.read_to_string(&mut ret);
+            }
+            ret
+        });
+
+        running.push((name, child, reader));
+    }
+
+    let mut failures = Vec::new();
+
+    for (name, mut child, reader) in running {
+        let status = child.wait()?;
+        let stderr = reader.join().unwrap_or_default();
+
+        if !status.success() {
+            match stderr.trim() {
+                "" => failures.push(format!("{} failed ({})", name, status)),
+                msg => failures.push(format!("{} failed ({}): {}", name, status, msg)),
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        return Err(anyhow!(failures.join("; ")));
+    }
+
+    tmp.persist(output)
+        .context(format!("cannot write {}", output))?;
+    Ok(())
+}
+
+struct InFlight(Utf8PathBuf);
+
+impl InFlight {
+    fn new(file: &Utf8Path) -> Self {
+        INTERRUPT_HANDLER.call_once(|| {
+            // If something else has claimed the signal, we're no worse off than we were.
+            let _ = ctrlc::set_handler(|| {
+                if let Ok(files) = IN_FLIGHT.lock() {
+                    for file in files.iter() {
+                        let _ = fs::remove_file(file);
+                    }
+                }
+                std::process::exit(130);
+            });
+        });
+
+        if let Ok(mut files) = IN_FLIGHT.lock() {
+            files.push(file.to_path_buf());
+        }
+
+        Self(file.to_path_buf())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut files) = IN_FLIGHT.lock() {
+            files.retain(|f| f != &self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+
+    fn sh(script: &str, output: Option<&Utf8Path>) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script);
+
+        if let Some(output) = output {
+            cmd.arg(output);
+        }
+
+        cmd
+    }
+
+    fn dir_contents(dir: &Utf8Path) -> Vec<String> {
+        let mut ret: Vec<String> = dir
+            .read_dir_utf8()
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_owned())
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn test_run_pipeline() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let output = tmp.path().join("out.txt");
+
+        run_pipeline(&output, |target| {
+            assert_eq!(Some("txt"), target.extension());
+            assert_eq!(Some(tmp.path()), target.parent());
+            vec![
+                sh("printf hello", None),
+                sh("tr a-z A-Z > \"$0\"", Some(target)),
+            ]
+        })
+        .unwrap();
+
+        assert_eq!("HELLO", fs::read_to_string(&output).unwrap());
+        assert_eq!(vec!["out.txt"], dir_contents(tmp.path()));
+    }
+
+    #[test]
+    fn test_run_pipeline_permissions() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let output = tmp.path().join("out.txt");
+        let mode = |file: &Utf8Path| fs::metadata(file).unwrap().permissions().mode() & 0o777;
+        let write = |file: &Utf8Path| {
+            run_pipeline(file, |target| vec![sh("printf x > \"$0\"", Some(target))]).unwrap()
+        };
+
+        // Compare with a file made the ordinary way, so the umask doesn't matter.
+        let plain = tmp.path().join("plain.txt");
+        fs::write(&plain, "x").unwrap();
+        write(&output);
+        assert_eq!(mode(&plain), mode(&output));
+
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o640)).unwrap();
+        write(&output);
+        assert_eq!(0o640, mode(&output));
+    }
+
+    #[test]
+    fn test_run_pipeline_failure() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let output = tmp.path().join("out.txt");
+
+        let err = run_pipeline(&output, |target| {
+            vec![sh(
+                "printf partial > \"$0\"; echo broken >&2; exit 3",
+                Some(target),
+            )]
+        })
+        .unwrap_err();
+
+        assert_eq!("sh failed (exit status: 3): broken", err.to_string());
+        assert!(dir_contents(tmp.path()).is_empty());
+
+        // A failure anywhere in the pipeline counts, even if the last command is happy.
+        let err = run_pipeline(&output, |target| {
+            vec![sh("exit 1", None), sh("cat > \"$0\"", Some(target))]
+        })
+        .unwrap_err();
+
+        assert_eq!("sh failed (exit status: 1)", err.to_string());
+        assert!(dir_contents(tmp.path()).is_empty());
+
+        let missing = tmp.path().join("no-such-command");
+        let err = run_pipeline(&output, |_| vec![Command::new(&missing)]).unwrap_err();
+
+        assert!(err.to_string().starts_with("cannot run "));
+        assert!(dir_contents(tmp.path()).is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+use super::{
+    EmbeddedPicture, FormatData, MediaFormat, PictureKind, disc_number, string_set,
+    with_replaygain_tags,
+};
+use crate::utils::loudness::{REPLAYGAIN_TAGS, ReplayGain};
+use crate::utils::metadata::{AurQuality, AurTags, AurTime, RawTags, UNDEFINED};
+use anyhow::anyhow;
+use camino::Utf8Path;
+use metaflac::Tag as FlacTag;
+use metaflac::block::PictureType;
+use std::collections::HashSet;
+
+#[derive(Debug)]
+pub struct Flac;
+
+impl MediaFormat for Flac {
+    fn name(&self) -> &'static str {
+        "flac"
+    }
+
+    fn read(&self, file: &Utf8Path) -> anyhow::Result<FormatData> {
+        let raw_info = FlacTag::read_from_path(file)?;
+
+        Ok(FormatData {
+            tags: tags(&raw_info),
+            quality: quality(&raw_info),
+            time: time(&raw_info),
+            rawtags: rawtags(&raw_info),
+            has_picture: raw_info.pictures().next().is_some(),
+            encoding: None,
+        })
+    }
+
+    fn set_tag(&self, file: &Utf8Path, tag_name: &str, value: &str) -> anyhow::Result<bool> {
+        let mut tag = FlacTag::read_from_path(file)?;
+        let val = vec![value];
+
+        match tag_name {
+            "artist" => tag.set_vorbis("artist".to_owned(), val),
+            "album" => tag.set_vorbis("album".to_owned(), val),
+            "title" => tag.set_vorbis("title".to_owned(), val),
+            "t_num" => tag.set_vorbis("tracknumber".to_owned(), val),
+            "disc" => tag.set_vorbis("discnumber".to_owned(), val),
+            "year" => tag.set_vorbis("date".to_owned(), val),
+            "genre" => tag.set_vorbis("genre".to_owned(), val),
+            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
+        }
+        tag.save()?;
+        Ok(true)
+    }
+
+    fn remove_tags(&self, file: &Utf8Path, tags: &[String]) -> anyhow::Result<bool> {
+        let mut tagger = FlacTag::read_from_path(file)?;
+        let mut ret = false;
+
+        for tag_name in tags {
+            let values: Vec<String> = tagger
+                .get_vorbis(tag_name)
+                .map(|t| t.map(|v| v.to_owned()).collect())
+                .unwrap_or_default();
+
+            for v in values {
+                tagger.remove_vorbis_pair(tag_name, &v);
+                ret = true;
+            }
+        }
+
+        if ret {
+            tagger.save()?;
+        }
+        Ok(ret)
+    }
+
+    fn remove_artwork(&self, file: &Utf8Path) -> anyhow::Result<bool> {
+        let mut tagger = FlacTag::read_from_path(file)?;
+        tagger.remove_picture_type(PictureType::CoverFront);
+        tagger.remove_picture_type(PictureType::CoverBack);
+        tagger.remove_picture_type(PictureType::Media);
+        tagger.remove_picture_type(PictureType::Other);
+        tagger.remove_picture_type(PictureType::Icon);
+        tagger.remove_picture_type(PictureType::Media);
+        tagger.remove_picture_type(PictureType::Leaflet);
+        tagger.remove_picture_type(PictureType::Artist);
+        tagger.save()?;
+        Ok(true)
+    }
+
+    fn pictures(&self, file: &Utf8Path) -> anyhow::Result<Vec<EmbeddedPicture>> {
+        let tagger = FlacTag::read_from_path(file)?;
+
+        Ok(tagger
+            .pictures()
+            .map(|p| EmbeddedPicture {
+                kind: match p.picture_type {
+                    PictureType::CoverFront => PictureKind::FrontCover,
+                    PictureType::Other => PictureKind::Unspecified,
+                    _ => PictureKind::Other,
+                },
+                data: p.data.clone(),
+            })
+            .collect())
+    }
+
+    fn expected_tags(&self) -> HashSet<String> {
+        string_set(&["artist", "album", "title", "tracknumber", "genre", "date"])
+    }
+
+    fn irrelevant_tags(&self) -> HashSet<String> {
+        string_set(&["encoder", "blank"])
+    }
+
+    fn optional_tags(&self) -> HashSet<String> {
+        with_replaygain_tags(&["discnumber"])
+    }
+
+    fn set_loudness(&self, file: &Utf8Path, gain: &ReplayGain) -> anyhow::Result<bool> {
+        let mut tag = FlacTag::read_from_path(file)?;
+
+        // metaflac is case-sensitive about comment names, but nothing which reads them is.
+        let old_names: Vec<String> = tag
+            .vorbis_comments()
+            .map(|c| {
+                c.comments
+                    .keys()
+                    .filter(|k| REPLAYGAIN_TAGS.iter().any(|n| k.eq_ignore_ascii_case(n)))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for name in old_names {
+            tag.remove_vorbis(&name);
+        }
+
+        for (name, value) in gain.tags() {
+            tag.set_vorbis(name.to_uppercase(), vec![value]);
+        }
+
+        tag.save()?;
+        Ok(true)
+    }
+}
+
+fn tags(raw_info: &FlacTag) -> AurTags {
+    let comments = raw_info.vorbis_comments();
+
+    AurTags {
+        artist: first_or_default(comments.and_then(|c| c.artist())),
+        album: first_or_default(comments.and_then(|c| c.album())),
+        title: first_or_default(comments.and_then(|c| c.title())),
+        t_num: comments.and_then(|c| c.track()).unwrap_or(0),
+        disc: comments
+            .and_then(|c| c.get("DISCNUMBER"))
+            .and_then(|v| v.first())
+            .map_or(0, |d| disc_number(d)),
+        year: first_or_default(comments.and_then(|c| c.get("DATE")))
+            .parse::<i32>()
+            .unwrap_or(0),
+        genre: first_or_default(comments.and_then(|c| c.genre())),
+    }
+}
+
+fn quality(raw_info: &FlacTag) -> AurQuality {
+    match raw_info.get_streaminfo() {
+        Some(info) => AurQuality::lossless(info.bits_per_sample, info.sample_rate),
+        None => AurQuality::unknown(),
+    }
+}
+
+fn time(raw_info: &FlacTag) -> AurTime {
+    match raw_info.get_streaminfo() {
+        Some(info) => {
+            let duration: u64 = if info.sample_rate > 0 {
+                info.total_samples / info.sample_rate as u64
+            } else {
+                0
+            };
+
+            AurTime::from_seconds(duration)
+        }
+        None => AurTime::unknown(),
+    }
+}
+
+fn rawtags(raw_info: &FlacTag) -> RawTags {
+    match raw_info.vorbis_comments() {
+        Some(vorbis_comments) => vorbis_comments
+            .comments
+            .clone()
+            .into_iter()
+            .map(|(tag, val)| (tag.to_lowercase(), val.join(",")))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn first_or_default(option: Option<&Vec<String>>) -> String {
+    option
+        .and_then(|vec| vec.first())
+        .unwrap_or(&UNDEFINED.to_owned())
+        .clone()
+}
@@ -0,0 +1,171 @@
+use crate::utils::loudness::{REPLAYGAIN_TAGS, ReplayGain};
+use crate::utils::metadata::{AurQuality, AurTags, AurTime, RawTags};
+use anyhow::anyhow;
+use camino::Utf8Path;
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+mod flac;
+mod mp3;
+mod mp4;
+mod ogg;
+
+pub use flac::Flac;
+pub use mp3::Mp3;
+pub use mp4::Mp4;
+pub use ogg::Ogg;
+
+// Every format aur understands. Adding a new one means writing a type which implements
+// MediaFormat, and putting it in here.
+static FORMATS: [&dyn MediaFormat; 5] = [&Flac, &Mp3, &Ogg("ogg"), &Ogg("opus"), &Mp4];
+
+/// Everything we get from reading a file once.
+pub struct FormatData {
+    pub tags: AurTags,
+    pub quality: AurQuality,
+    pub time: AurTime,
+    pub rawtags: RawTags,
+    pub has_picture: bool,
+    /// How the audio was encoded, if the format records it.
+    pub encoding: Option<String>,
+}
+
+/// A picture embedded in a media file, as it was stored.
+#[derive(Debug)]
+pub struct EmbeddedPicture {
+    pub kind: PictureKind,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PictureKind {
+    FrontCover,
+    Unspecified,
+    Other,
+}
+
+/// A type of media file which aur can read and tag. Tag names passed to `set_tag()` are aur's
+/// own: artist, album, title, t_num, disc, year and genre. All other tag names are raw, as returned in
+/// `FormatData::rawtags`.
+pub trait MediaFormat: Debug + Sync {
+    /// Used as `AurMetadata::filetype`, and also the file suffix.
+    fn name(&self) -> &'static str;
+    fn read(&self, file: &Utf8Path) -> anyhow::Result<FormatData>;
+    fn set_tag(&self, file: &Utf8Path, tag_name: &str, value: &str) -> anyhow::Result<bool>;
+    fn remove_tags(&self, file: &Utf8Path, tags: &[String]) -> anyhow::Result<bool>;
+    fn remove_artwork(&self, file: &Utf8Path) -> anyhow::Result<bool>;
+    /// Every embedded picture. Formats we can't read pictures from report none.
+    fn pictures(&self, _file: &Utf8Path) -> anyhow::Result<Vec<EmbeddedPicture>> {
+        Ok(Vec::new())
+    }
+    /// Raw tag names which every file of this type must have, and may be the only ones it has.
+    fn expected_tags(&self) -> HashSet<String>;
+    /// Raw tag names which we tolerate, but don't want.
+    fn irrelevant_tags(&self) -> HashSet<String>;
+    /// Raw tag names which a file doesn't need, but which are worth keeping.
+    fn optional_tags(&self) -> HashSet<String>;
+    /// Writes ReplayGain tags, removing any album tags if `gain` has no album.
+    fn set_loudness(&self, _file: &Utf8Path, _gain: &ReplayGain) -> anyhow::Result<bool> {
+        Err(anyhow!(
+            "cannot write loudness tags to {} files",
+            self.name()
+        ))
+    }
+}
+
+pub fn by_name(name: &str) -> Option<&'static dyn MediaFormat> {
+    FORMATS.iter().find(|f| f.name() == name).copied()
+}
+
+pub fn for_path(file: &Utf8Path) -> Option<&'static dyn MediaFormat> {
+    file.extension().and_then(by_name)
+}
+
+// Disc numbers are often written as 'n/total'.
+fn disc_number(value: &str) -> u32 {
+    value
+        .split('/')
+        .next()
+        .and_then(|n| n.trim().parse::<u32>().ok())
+        .unwrap_or(0)
+}
+
+fn string_set(tags: &[&str]) -> HashSet<String> {
+    tags.iter().map(|t| t.to_string()).collect()
+}
+
+// Where a format keeps ReplayGain tags under their own names.
+fn with_replaygain_tags(tags: &[&str]) -> HashSet<String> {
+    let mut ret = string_set(tags);
+    ret.extend(string_set(&REPLAYGAIN_TAGS));
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_for_path() {
+        assert_eq!("flac", for_path(Utf8Path::new("/a/b.flac")).unwrap().name());
+        assert_eq!("opus", for_path(Utf8Path::new("/a/b.opus")).unwrap().name());
+        assert_eq!("m4a", for_path(Utf8Path::new("b.m4a")).unwrap().name());
+        assert!(for_path(Utf8Path::new("/a/cover.jpg")).is_none());
+        assert!(for_path(Utf8Path::new("/a/flac")).is_none());
+    }
+
+    #[test]
+    fn test_by_name() {
+        assert!(by_name("mp3").unwrap().expected_tags().contains("tit2"));
+        assert!(
+            by_name("ogg")
+                .unwrap()
+                .irrelevant_tags()
+                .contains("encoder")
+        );
+        assert!(
+            by_name("flac")
+                .unwrap()
+                .optional_tags()
+                .contains("replaygain_track_gain")
+        );
+        assert!(by_name("wav").is_none());
+    }
+
+    #[test]
+    fn test_pictures() {
+        let dir = fixture!("commands/strip");
+        let flac = by_name("flac")
+            .unwrap()
+            .pictures(&dir.join("01.tester.not_stripped.flac"))
+            .unwrap();
+
+        assert_eq!(1, flac.len());
+        assert_eq!(PictureKind::FrontCover, flac[0].kind);
+        assert!(flac[0].data.starts_with(b"\x89PNG"));
+
+        let mp3 = by_name("mp3")
+            .unwrap()
+            .pictures(&dir.join("02.tester.not_stripped.mp3"))
+            .unwrap();
+
+        assert_eq!(1, mp3.len());
+        assert_eq!(PictureKind::Unspecified, mp3[0].kind);
+        assert!(
+            by_name("opus")
+                .unwrap()
+                .pictures(&fixture!("info/test.opus"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_disc_number() {
+        assert_eq!(2, disc_number("2"));
+        assert_eq!(1, disc_number("1/3"));
+        assert_eq!(0, disc_number(""));
+        assert_eq!(0, disc_number("one"));
+    }
+}
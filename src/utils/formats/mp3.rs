@@ -0,0 +1,202 @@
+use super::{
+    EmbeddedPicture, FormatData, MediaFormat, PictureKind, string_set, with_replaygain_tags,
+};
+use crate::utils::loudness::{REPLAYGAIN_TAGS, ReplayGain};
+use crate::utils::metadata::{AurQuality, AurTags, AurTime, RawTags, UNDEFINED};
+use crate::utils::mp3_stream::Mp3Stream;
+use anyhow::{Context, anyhow};
+use camino::Utf8Path;
+use id3::Tag as Id3Tag;
+use id3::TagLike;
+use id3::frame::{ExtendedText, PictureType};
+use std::collections::HashSet;
+
+#[derive(Debug)]
+pub struct Mp3;
+
+impl MediaFormat for Mp3 {
+    fn name(&self) -> &'static str {
+        "mp3"
+    }
+
+    fn read(&self, file: &Utf8Path) -> anyhow::Result<FormatData> {
+        let id3tags = Id3Tag::read_from_path(file).context(format!("problem reading {}", file))?;
+
+        // An audio stream we can't make sense of mustn't stop anyone fixing the tags.
+        let (quality, time, encoding) = match Mp3Stream::read_from_path(file) {
+            Ok(stream) => (
+                AurQuality::lossy(stream.bitrate()),
+                AurTime::from_seconds(stream.duration()),
+                Some(stream.encoding()),
+            ),
+            Err(_) => (AurQuality::unknown(), AurTime::unknown(), None),
+        };
+
+        Ok(FormatData {
+            tags: tags(&id3tags),
+            quality,
+            time,
+            rawtags: rawtags(&id3tags),
+            has_picture: id3tags.pictures().next().is_some(),
+            encoding,
+        })
+    }
+
+    fn set_tag(&self, file: &Utf8Path, tag_name: &str, value: &str) -> anyhow::Result<bool> {
+        let mut tag = Id3Tag::read_from_path(file)?;
+
+        match tag_name {
+            "artist" => tag.set_artist(value),
+            "album" => tag.set_album(value),
+            "title" => tag.set_title(value),
+            "t_num" => tag.set_track(value.to_owned().parse::<u32>()?),
+            "disc" => tag.set_disc(value.to_owned().parse::<u32>()?),
+            "year" => tag.set_year(value.to_owned().parse::<i32>()?),
+            "genre" => tag.set_genre(value),
+            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
+        }
+
+        tag.write_to_path(file, id3::Version::Id3v24)?;
+        Ok(true)
+    }
+
+    fn remove_tags(&self, file: &Utf8Path, tags: &[String]) -> anyhow::Result<bool> {
+        let mut tag = Id3Tag::read_from_path(file)?;
+        let mut ret = false;
+
+        for tag_name in tags {
+            if is_replaygain(tag_name) {
+                if remove_extended_texts(&mut tag, |d| d.eq_ignore_ascii_case(tag_name)) {
+                    ret = true;
+                }
+                continue;
+            }
+
+            // Only the TXXX frames which aren't ReplayGain, because rawtags() reports those
+            // separately.
+            if tag_name.eq_ignore_ascii_case("txxx") {
+                if remove_extended_texts(&mut tag, |d| !is_replaygain(d)) {
+                    ret = true;
+                }
+                continue;
+            }
+
+            if tag.get(tag_name).is_some() {
+                tag.remove(tag_name);
+                ret = true;
+            }
+
+            let tag_name_uc = tag_name.to_uppercase();
+
+            if tag.get(&tag_name_uc).is_some() {
+                tag.remove(&tag_name_uc);
+                ret = true;
+            }
+        }
+
+        if ret {
+            tag.write_to_path(file, id3::Version::Id3v24)?;
+        }
+
+        Ok(ret)
+    }
+
+    fn remove_artwork(&self, file: &Utf8Path) -> anyhow::Result<bool> {
+        let mut tag = Id3Tag::read_from_path(file)?;
+        tag.remove_all_pictures();
+        tag.write_to_path(file, id3::Version::Id3v24)?;
+        Ok(true)
+    }
+
+    fn pictures(&self, file: &Utf8Path) -> anyhow::Result<Vec<EmbeddedPicture>> {
+        let tag = Id3Tag::read_from_path(file)?;
+
+        Ok(tag
+            .pictures()
+            .map(|p| EmbeddedPicture {
+                kind: match p.picture_type {
+                    PictureType::CoverFront => PictureKind::FrontCover,
+                    PictureType::Other => PictureKind::Unspecified,
+                    _ => PictureKind::Other,
+                },
+                data: p.data.clone(),
+            })
+            .collect())
+    }
+
+    fn expected_tags(&self) -> HashSet<String> {
+        string_set(&["tpe1", "talb", "tit2", "trck", "tyer", "tcon"])
+    }
+
+    fn irrelevant_tags(&self) -> HashSet<String> {
+        string_set(&["tlen", "tsse"])
+    }
+
+    fn optional_tags(&self) -> HashSet<String> {
+        with_replaygain_tags(&["tpos"])
+    }
+
+    fn set_loudness(&self, file: &Utf8Path, gain: &ReplayGain) -> anyhow::Result<bool> {
+        let mut tag = Id3Tag::read_from_path(file)?;
+
+        remove_extended_texts(&mut tag, is_replaygain);
+
+        for (name, value) in gain.tags() {
+            tag.add_frame(ExtendedText {
+                description: name.to_uppercase(),
+                value,
+            });
+        }
+
+        tag.write_to_path(file, id3::Version::Id3v24)?;
+        Ok(true)
+    }
+}
+
+fn tags(id3tag: &Id3Tag) -> AurTags {
+    AurTags {
+        artist: id3tag.artist().unwrap_or(UNDEFINED).to_owned(),
+        album: id3tag.album().unwrap_or(UNDEFINED).to_owned(),
+        title: id3tag.title().unwrap_or(UNDEFINED).to_owned(),
+        t_num: id3tag.track().unwrap_or(0),
+        disc: id3tag.disc().unwrap_or(0),
+        year: id3tag.year().unwrap_or(0),
+        genre: id3tag.genre().unwrap_or(UNDEFINED).to_owned(),
+    }
+}
+
+// ReplayGain values are in TXXX frames, which we name by their description, to match the Vorbis
+// comments.
+fn rawtags(id3tag: &Id3Tag) -> RawTags {
+    id3tag
+        .frames()
+        .map(|frame| match frame.content().extended_text() {
+            Some(text) if is_replaygain(&text.description) => {
+                (text.description.to_lowercase(), text.value.clone())
+            }
+            _ => (
+                frame.id().to_string().to_lowercase(),
+                frame.content().to_string(),
+            ),
+        })
+        .collect()
+}
+
+// True if anything was removed.
+fn remove_extended_texts(tag: &mut Id3Tag, matches: impl Fn(&str) -> bool) -> bool {
+    let descriptions: Vec<String> = tag
+        .extended_texts()
+        .filter(|t| matches(&t.description))
+        .map(|t| t.description.clone())
+        .collect();
+
+    for description in &descriptions {
+        tag.remove_extended_text(Some(description), None);
+    }
+
+    !descriptions.is_empty()
+}
+
+fn is_replaygain(name: &str) -> bool {
+    REPLAYGAIN_TAGS.iter().any(|t| t.eq_ignore_ascii_case(name))
+}
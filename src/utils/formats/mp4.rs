@@ -0,0 +1,128 @@
+use super::{FormatData, MediaFormat, string_set};
+use crate::utils::metadata::{AurQuality, AurTags, AurTime, UNDEFINED};
+use crate::utils::mp4_file::Mp4File;
+use anyhow::{Context, anyhow};
+use camino::Utf8Path;
+use std::collections::HashSet;
+
+/// AAC or ALAC audio in an MPEG-4 container, tagged with iTunes-style atoms.
+#[derive(Debug)]
+pub struct Mp4;
+
+impl MediaFormat for Mp4 {
+    fn name(&self) -> &'static str {
+        "m4a"
+    }
+
+    fn read(&self, file: &Utf8Path) -> anyhow::Result<FormatData> {
+        let mp4 = Mp4File::read_from_path(file).context(format!("problem reading {}", file))?;
+
+        Ok(FormatData {
+            tags: tags(&mp4),
+            quality: quality(&mp4),
+            time: AurTime::from_seconds(mp4.duration),
+            rawtags: mp4
+                .raw_tags()
+                .into_iter()
+                .map(|(tag, val)| (tag.to_lowercase(), val))
+                .collect(),
+            has_picture: mp4.has_picture(),
+            encoding: None,
+        })
+    }
+
+    fn set_tag(&self, file: &Utf8Path, tag_name: &str, value: &str) -> anyhow::Result<bool> {
+        let mut mp4 = Mp4File::read_from_path(file)?;
+
+        match tag_name {
+            "artist" => mp4.set_text(b"\xa9ART", value),
+            "album" => mp4.set_text(b"\xa9alb", value),
+            "title" => mp4.set_text(b"\xa9nam", value),
+            "t_num" => mp4.set_track_number(value.parse::<u16>()?),
+            "disc" => mp4.set_disc_number(value.parse::<u16>()?),
+            "year" => mp4.set_text(b"\xa9day", value),
+            "genre" => mp4.set_text(b"\xa9gen", value),
+            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
+        }
+
+        mp4.save()?;
+        Ok(true)
+    }
+
+    fn remove_tags(&self, file: &Utf8Path, tags: &[String]) -> anyhow::Result<bool> {
+        let mut mp4 = Mp4File::read_from_path(file)?;
+        let mut ret = false;
+
+        for tag_name in tags {
+            if mp4.remove(tag_name) {
+                ret = true;
+            }
+        }
+
+        if ret {
+            mp4.save()?;
+        }
+
+        Ok(ret)
+    }
+
+    fn remove_artwork(&self, file: &Utf8Path) -> anyhow::Result<bool> {
+        let mut mp4 = Mp4File::read_from_path(file)?;
+
+        if mp4.remove_pictures() {
+            mp4.save()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expected_tags(&self) -> HashSet<String> {
+        string_set(&[
+            "\u{a9}art",
+            "\u{a9}alb",
+            "\u{a9}nam",
+            "trkn",
+            "\u{a9}day",
+            "\u{a9}gen",
+        ])
+    }
+
+    fn irrelevant_tags(&self) -> HashSet<String> {
+        string_set(&["\u{a9}too"])
+    }
+
+    fn optional_tags(&self) -> HashSet<String> {
+        string_set(&["disk"])
+    }
+}
+
+// ©day is often a full timestamp, but the year always comes first.
+fn tags(mp4: &Mp4File) -> AurTags {
+    let get = |key| mp4.get_text(key).unwrap_or(UNDEFINED.to_owned());
+
+    AurTags {
+        artist: get(b"\xa9ART"),
+        album: get(b"\xa9alb"),
+        title: get(b"\xa9nam"),
+        t_num: mp4.track_number().unwrap_or(0),
+        disc: mp4.disc_number().unwrap_or(0),
+        year: mp4
+            .get_text(b"\xa9day")
+            .and_then(|d| d.get(..4).and_then(|y| y.parse::<i32>().ok()))
+            .unwrap_or(0),
+        genre: get(b"\xa9gen"),
+    }
+}
+
+// ALAC is described like FLAC, AAC like MP3. Only the audio data counts towards the bitrate, not
+// the tags or the artwork.
+fn quality(mp4: &Mp4File) -> AurQuality {
+    if mp4.is_lossless() {
+        return AurQuality::lossless(mp4.bit_depth, mp4.sample_rate);
+    }
+
+    let bitrate = (mp4.audio_bytes * 8).checked_div(mp4.duration).unwrap_or(0) / 1000;
+
+    AurQuality::lossy(bitrate as u32)
+}
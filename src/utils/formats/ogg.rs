@@ -0,0 +1,135 @@
+use super::{FormatData, MediaFormat, disc_number, string_set, with_replaygain_tags};
+use crate::utils::metadata::{AurQuality, AurTags, AurTime, UNDEFINED};
+use crate::utils::ogg_stream::OggStream;
+use anyhow::{Context, anyhow};
+use camino::Utf8Path;
+use std::collections::HashSet;
+use std::fs;
+
+/// Ogg Vorbis and Ogg Opus files. They only differ in codec, so one type covers both, named by
+/// suffix.
+#[derive(Debug)]
+pub struct Ogg(pub &'static str);
+
+impl MediaFormat for Ogg {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn read(&self, file: &Utf8Path) -> anyhow::Result<FormatData> {
+        let stream =
+            OggStream::read_from_path(file).context(format!("problem reading {}", file))?;
+
+        Ok(FormatData {
+            tags: tags(&stream),
+            quality: quality(file, &stream)?,
+            time: time(&stream),
+            rawtags: stream
+                .comments
+                .iter()
+                .map(|(tag, val)| (tag.to_lowercase(), val.to_owned()))
+                .collect(),
+            has_picture: stream.has_picture(),
+            encoding: None,
+        })
+    }
+
+    fn set_tag(&self, file: &Utf8Path, tag_name: &str, value: &str) -> anyhow::Result<bool> {
+        let mut stream = OggStream::read_from_path(file)?;
+
+        match tag_name {
+            "artist" => stream.set("artist", value),
+            "album" => stream.set("album", value),
+            "title" => stream.set("title", value),
+            "t_num" => stream.set("tracknumber", value),
+            "disc" => stream.set("discnumber", value),
+            "year" => stream.set("date", value),
+            "genre" => stream.set("genre", value),
+            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
+        }
+
+        stream.save()?;
+        Ok(true)
+    }
+
+    fn remove_tags(&self, file: &Utf8Path, tags: &[String]) -> anyhow::Result<bool> {
+        let mut stream = OggStream::read_from_path(file)?;
+        let mut ret = false;
+
+        for tag_name in tags {
+            if stream.remove(tag_name) {
+                ret = true;
+            }
+        }
+
+        if ret {
+            stream.save()?;
+        }
+
+        Ok(ret)
+    }
+
+    fn remove_artwork(&self, file: &Utf8Path) -> anyhow::Result<bool> {
+        let mut stream = OggStream::read_from_path(file)?;
+
+        if stream.remove_pictures() {
+            stream.save()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expected_tags(&self) -> HashSet<String> {
+        string_set(&["artist", "album", "title", "tracknumber", "genre", "date"])
+    }
+
+    fn irrelevant_tags(&self) -> HashSet<String> {
+        string_set(&["encoder"])
+    }
+
+    fn optional_tags(&self) -> HashSet<String> {
+        with_replaygain_tags(&["discnumber"])
+    }
+}
+
+fn tags(stream: &OggStream) -> AurTags {
+    let get = |key| stream.get(key).unwrap_or(UNDEFINED).to_owned();
+
+    AurTags {
+        artist: get("artist"),
+        album: get("album"),
+        title: get("title"),
+        t_num: stream
+            .get("tracknumber")
+            .and_then(|t| t.parse::<u32>().ok())
+            .unwrap_or(0),
+        disc: stream.get("discnumber").map_or(0, disc_number),
+        year: stream
+            .get("date")
+            .and_then(|d| d.parse::<i32>().ok())
+            .unwrap_or(0),
+        genre: get("genre"),
+    }
+}
+
+fn quality(path: &Utf8Path, stream: &OggStream) -> anyhow::Result<AurQuality> {
+    let file_size = fs::metadata(path)?.len();
+    let duration = stream.samples() / stream.decode_rate().max(1) as u64;
+
+    let bitrate = match (file_size * 8).checked_div(duration) {
+        Some(bps) => bps / 1000,
+        None => stream.nominal_bitrate as u64 / 1000,
+    };
+
+    Ok(AurQuality::lossy(bitrate as u32))
+}
+
+fn time(stream: &OggStream) -> AurTime {
+    let duration = match stream.decode_rate() {
+        0 => 0,
+        rate => stream.samples() / rate as u64,
+    };
+
+    AurTime::from_seconds(duration)
+}
@@ -1,6 +1,8 @@
+use crate::utils::types::OutputFormat;
 use anyhow::ensure;
 use camino::Utf8Path;
 use indicatif::ProgressBar;
+use serde_json::Value;
 
 pub fn check_hierarchy(root: &Utf8Path) -> anyhow::Result<()> {
     let mp3_root = root.join("mp3");
@@ -40,3 +42,33 @@ impl MaybeProgress {
         }
     }
 }
+
+// Machine-readable output for the lint commands. JSON Lines are printed as soon as we have them,
+// so a long run can be streamed, but JSON has to wait until the end to close the array.
+//
+pub struct JsonReport {
+    format: OutputFormat,
+    entries: Vec<Value>,
+}
+
+impl JsonReport {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, entry: Value) {
+        match self.format {
+            OutputFormat::Jsonl => println!("{}", entry),
+            _ => self.entries.push(entry),
+        }
+    }
+
+    pub fn finish(self) {
+        if self.format == OutputFormat::Json {
+            println!("{}", Value::Array(self.entries));
+        }
+    }
+}
@@ -0,0 +1,766 @@
+use crate::utils::external::find_binary;
+use anyhow::{Context, anyhow, ensure};
+use camino::Utf8Path;
+use jpeg_decoder::{Decoder, PixelFormat};
+use jpeg_encoder::{ColorType, Encoder};
+use resize::Type::Lanczos3;
+use rgb::alt::Gray;
+use rgb::{ComponentBytes, FromSlice, RGB};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Cursor};
+use std::process::Command;
+
+const ARTWORK_TYPES: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// How far any channel of a pixel can be from the average and still be part of a plain border.
+// Scans and JPEG artefacts are never perfectly flat.
+const BORDER_VARIANCE: u8 = 24;
+
+// How far apart, on any channel, the average colours of two pictures can be for them to look alike.
+const COLOUR_TOLERANCE: u8 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageType {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+// Pictures aren't always what their names say, so we go by what's in them.
+fn image_type(header: &[u8]) -> Option<ImageType> {
+    if header.starts_with(&[0xff, 0xd8]) {
+        Some(ImageType::Jpeg)
+    } else if header.starts_with(&PNG_SIGNATURE) {
+        Some(ImageType::Png)
+    } else if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+        Some(ImageType::Webp)
+    } else {
+        None
+    }
+}
+
+/// A decoded picture, in 8-bit sRGB or greyscale, ready to be written out as a JPEG.
+#[derive(Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub color: ColorType,
+    pub pixels: Vec<u8>,
+}
+
+pub fn is_artwork(file: &Utf8Path) -> bool {
+    file.extension()
+        .is_some_and(|e| ARTWORK_TYPES.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+pub fn is_jpeg(file: &Utf8Path) -> bool {
+    file.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("jpg") || e.eq_ignore_ascii_case("jpeg"))
+}
+
+/// True for a JPEG which isn't plain RGB or greyscale, like a CMYK scan, and for anything which
+/// isn't a JPEG at all, whatever it's called. Only the header is read.
+pub fn jpeg_needs_conversion(file: &Utf8Path) -> anyhow::Result<bool> {
+    let mut reader = BufReader::new(File::open(file)?);
+
+    if image_type(reader.fill_buf()?) != Some(ImageType::Jpeg) {
+        return Ok(true);
+    }
+
+    let mut decoder = Decoder::new(reader);
+    decoder.read_info()?;
+    let info = decoder.info().context("failed to get JPEG info")?;
+    Ok(!matches!(
+        info.pixel_format,
+        PixelFormat::RGB24 | PixelFormat::L8
+    ))
+}
+
+/// What a picture looks like, boiled down so copies of it at different sizes and qualities can be
+/// recognised. The hash only knows where a picture gets lighter and darker, so without the average
+/// colour, every plain square would look like every other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub hash: u64,
+    pub colour: [u8; 3],
+}
+
+impl Fingerprint {
+    /// `distance` is how many bits the hashes may differ by.
+    pub fn looks_like(&self, other: &Fingerprint, distance: u32) -> bool {
+        hash_distance(self.hash, other.hash) <= distance
+            && self
+                .colour
+                .iter()
+                .zip(other.colour)
+                .all(|(a, b)| a.abs_diff(b) <= COLOUR_TOLERANCE)
+    }
+}
+
+fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// True if the longer side is no more than `tolerance` percent longer than the shorter.
+pub fn is_nearly_square(width: usize, height: usize, tolerance: f64) -> bool {
+    let (long, short) = (width.max(height), width.min(height));
+    short > 0 && (long - short) as f64 * 100.0 <= short as f64 * tolerance
+}
+
+impl Image {
+    pub fn load(file: &Utf8Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(file)?;
+
+        match image_type(&bytes) {
+            Some(ImageType::Webp) => Self::from_webp(file),
+            Some(_) => Self::from_bytes(&bytes),
+            None => Err(anyhow!("{} is not a supported image type", file)),
+        }
+    }
+
+    // For pictures which come without a name, like those embedded in media files.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        match image_type(bytes) {
+            Some(ImageType::Jpeg) => Self::from_jpeg(bytes),
+            Some(ImageType::Png) => Self::from_png(bytes),
+            _ => Err(anyhow!("unsupported image type")),
+        }
+    }
+
+    pub fn from_jpeg(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decoder = Decoder::new(bytes);
+        let pixels = decoder.decode()?;
+        let info = decoder.info().context("failed to get JPEG info")?;
+
+        let (color, pixels) = match info.pixel_format {
+            PixelFormat::RGB24 => (ColorType::Rgb, pixels),
+            PixelFormat::L8 => (ColorType::Luma, pixels),
+            PixelFormat::L16 => (
+                ColorType::Luma,
+                pixels
+                    .chunks_exact(2)
+                    .map(|p| (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8)
+                    .collect(),
+            ),
+            // jpeg_decoder has already undone Adobe's inversion, so no ink is 0.
+            PixelFormat::CMYK32 => (
+                ColorType::Rgb,
+                pixels
+                    .chunks_exact(4)
+                    .flat_map(|p| {
+                        let white = 255 - p[3] as u32;
+                        [0, 1, 2].map(|i| ((255 - p[i] as u32) * white / 255) as u8)
+                    })
+                    .collect(),
+            ),
+        };
+
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            color,
+            pixels,
+        })
+    }
+
+    // Anything transparent is put on a white background.
+    pub fn from_png(bytes: &[u8]) -> anyhow::Result<Self> {
+        ensure!(bytes.starts_with(&PNG_SIGNATURE), "not a PNG");
+
+        // Palettes, transparency and odd bit depths all become 8-bit grey or RGB, with alpha.
+        let mut decoder = png::Decoder::new(Cursor::new(bytes));
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size().context("PNG is too big")?];
+        let info = reader.next_frame(&mut data)?;
+        data.truncate(info.buffer_size());
+
+        let (grey, alpha) = match info.color_type {
+            png::ColorType::Grayscale => (true, false),
+            png::ColorType::GrayscaleAlpha => (true, true),
+            png::ColorType::Rgb => (false, false),
+            png::ColorType::Rgba => (false, true),
+            other => return Err(anyhow!("unexpected PNG colour type {:?}", other)),
+        };
+
+        let channels = if grey { 1 } else { 3 };
+
+        let pixels = if alpha {
+            data.chunks_exact(channels + 1)
+                .flat_map(|p| {
+                    let a = p[channels] as u32;
+                    p[..channels]
+                        .iter()
+                        .map(move |c| ((*c as u32 * a + 255 * (255 - a)) / 255) as u8)
+                })
+                .collect()
+        } else {
+            data
+        };
+
+        Ok(Self {
+            width: info.width as usize,
+            height: info.height as usize,
+            color: if grey {
+                ColorType::Luma
+            } else {
+                ColorType::Rgb
+            },
+            pixels,
+        })
+    }
+
+    // There's no WebP decoder in pure Rust which we trust, so ffmpeg does the work.
+    pub fn from_webp(file: &Utf8Path) -> anyhow::Result<Self> {
+        let size = imagesize::size(file)?;
+        let output = Command::new(find_binary("ffmpeg")?)
+            .args(["-hide_banner", "-loglevel", "error", "-i"])
+            .arg(file)
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+            .output()?;
+
+        ensure!(
+            output.status.success(),
+            "ffmpeg could not decode {}: {}",
+            file,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+
+        ensure!(
+            output.stdout.len() == size.width * size.height * 3,
+            "unexpected amount of image data from ffmpeg"
+        );
+
+        Ok(Self {
+            width: size.width,
+            height: size.height,
+            color: ColorType::Rgb,
+            pixels: output.stdout,
+        })
+    }
+
+    /// A perceptual "difference hash". The picture is shrunk to a 9x8 greyscale thumbnail, and
+    /// each bit says whether a pixel is brighter than the one to its right. Copies of a picture at
+    /// different sizes or qualities hash the same, or nearly so.
+    pub fn difference_hash(&self) -> anyhow::Result<u64> {
+        let thumbnail = self.to_grey().resize(9, 8)?;
+
+        Ok(thumbnail
+            .pixels
+            .chunks_exact(9)
+            .flat_map(|row| row.windows(2))
+            .fold(0, |hash, pair| hash << 1 | (pair[0] > pair[1]) as u64))
+    }
+
+    pub fn fingerprint(&self) -> anyhow::Result<Fingerprint> {
+        let c = self.channels();
+        let pixel_count = (self.pixels.len() / c).max(1);
+        let mean = |i: usize| {
+            (self
+                .pixels
+                .iter()
+                .skip(i)
+                .step_by(c)
+                .map(|v| *v as usize)
+                .sum::<usize>()
+                / pixel_count) as u8
+        };
+
+        let colour = match self.color {
+            ColorType::Luma => [mean(0); 3],
+            _ => [mean(0), mean(1), mean(2)],
+        };
+
+        Ok(Fingerprint {
+            hash: self.difference_hash()?,
+            colour,
+        })
+    }
+
+    fn to_grey(&self) -> Self {
+        let pixels = match self.color {
+            ColorType::Luma => self.pixels.clone(),
+            _ => self
+                .pixels
+                .chunks_exact(3)
+                .map(|p| ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8)
+                .collect(),
+        };
+
+        Self {
+            width: self.width,
+            height: self.height,
+            color: ColorType::Luma,
+            pixels,
+        }
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.width == self.height
+    }
+
+    /// Makes a nearly-square picture square. Plain borders at the ends of the long side, like
+    /// those a scanner leaves, are trimmed first. Whatever is left over is made up by padding
+    /// with the edge colour if the edges are plain, or by cropping from the middle if they
+    /// aren't.
+    pub fn square(self) -> Self {
+        if self.is_square() {
+            return self;
+        }
+
+        // A tall picture is squared by removing rows, a wide one by removing columns.
+        let rows = self.height > self.width;
+        let long = self.width.max(self.height);
+        let short = self.width.min(self.height);
+        let first = self.plain_colour(rows, 0);
+        let last = self.plain_colour(rows, long - 1);
+        let mut excess = long - short;
+        let (mut start, mut end) = (0, long);
+
+        let trimmable = |index: usize, edge: &Option<Vec<u8>>| match edge {
+            Some(edge) => self
+                .plain_colour(rows, index)
+                .is_some_and(|colour| is_close(&colour, edge)),
+            None => false,
+        };
+
+        // Alternate ends, so a picture with a border all round loses a bit of each.
+        loop {
+            let before = excess;
+
+            if excess > 0 && trimmable(start, &first) {
+                start += 1;
+                excess -= 1;
+            }
+
+            if excess > 0 && trimmable(end - 1, &last) {
+                end -= 1;
+                excess -= 1;
+            }
+
+            if excess == before {
+                break;
+            }
+        }
+
+        let img = self.crop_lines(rows, start, end);
+
+        if excess == 0 {
+            return img;
+        }
+
+        match (
+            img.plain_colour(!rows, 0),
+            img.plain_colour(!rows, short - 1),
+        ) {
+            (Some(a), Some(b)) if is_close(&a, &b) => img.pad(short + excess, &a),
+            _ => img.crop_lines(rows, excess / 2, excess / 2 + short),
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self.color {
+            ColorType::Luma => 1,
+            _ => 3,
+        }
+    }
+
+    // A row if `rows` is set, otherwise a column.
+    fn line(&self, rows: bool, index: usize) -> Vec<&[u8]> {
+        let c = self.channels();
+
+        if rows {
+            self.pixels[index * self.width * c..(index + 1) * self.width * c]
+                .chunks_exact(c)
+                .collect()
+        } else {
+            (0..self.height)
+                .map(|y| {
+                    let i = (y * self.width + index) * c;
+                    &self.pixels[i..i + c]
+                })
+                .collect()
+        }
+    }
+
+    // The average colour of a line, if it is plain enough to be a border.
+    fn plain_colour(&self, rows: bool, index: usize) -> Option<Vec<u8>> {
+        let line = self.line(rows, index);
+        let colour: Vec<u8> = (0..self.channels())
+            .map(|c| (line.iter().map(|p| p[c] as usize).sum::<usize>() / line.len()) as u8)
+            .collect();
+
+        line.iter().all(|p| is_close(p, &colour)).then_some(colour)
+    }
+
+    // Keeps the rows, or columns, from start up to end.
+    fn crop_lines(&self, rows: bool, start: usize, end: usize) -> Self {
+        if rows {
+            self.crop(0, start, self.width, end - start)
+        } else {
+            self.crop(start, 0, end - start, self.height)
+        }
+    }
+
+    fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        let c = self.channels();
+        let pixels = (y..y + height)
+            .flat_map(|row| {
+                let i = (row * self.width + x) * c;
+                self.pixels[i..i + width * c].iter().copied()
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            color: self.color,
+            pixels,
+        }
+    }
+
+    // Centres the picture on a square of the given colour.
+    fn pad(&self, side: usize, colour: &[u8]) -> Self {
+        let c = self.channels();
+        let row_bytes = self.width * c;
+        let (x0, y0) = ((side - self.width) / 2, (side - self.height) / 2);
+        let mut pixels = colour.repeat(side * side);
+
+        for (y, row) in self.pixels.chunks_exact(row_bytes).enumerate() {
+            let i = ((y0 + y) * side + x0) * c;
+            pixels[i..i + row_bytes].copy_from_slice(row);
+        }
+
+        Self {
+            width: side,
+            height: side,
+            color: self.color,
+            pixels,
+        }
+    }
+
+    pub fn resize(self, width: usize, height: usize) -> anyhow::Result<Self> {
+        let pixels = match self.color {
+            ColorType::Luma => {
+                let src: &[Gray<u8>] = bytemuck::cast_slice(&self.pixels);
+                let mut dst = vec![Gray(0u8); width * height];
+                resize::new(
+                    self.width,
+                    self.height,
+                    width,
+                    height,
+                    resize::Pixel::Gray8,
+                    Lanczos3,
+                )?
+                .resize(src, &mut dst)?;
+                bytemuck::cast_slice(&dst).to_vec()
+            }
+            _ => {
+                let src: &[RGB<u8>] = self.pixels.as_rgb();
+                let mut dst = vec![RGB::new(0u8, 0, 0); width * height];
+                resize::new(
+                    self.width,
+                    self.height,
+                    width,
+                    height,
+                    resize::Pixel::RGB8,
+                    Lanczos3,
+                )?
+                .resize(src, &mut dst)?;
+                dst.as_bytes().to_vec()
+            }
+        };
+
+        Ok(Self {
+            width,
+            height,
+            color: self.color,
+            pixels,
+        })
+    }
+
+    pub fn to_jpeg(&self, quality: u8) -> anyhow::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        Encoder::new(&mut output, quality).encode(
+            &self.pixels,
+            self.width.try_into()?,
+            self.height.try_into()?,
+            self.color,
+        )?;
+        Ok(output)
+    }
+}
+
+fn is_close(a: &[u8], b: &[u8]) -> bool {
+    a.iter()
+        .zip(b)
+        .all(|(a, b)| a.abs_diff(*b) <= BORDER_VARIANCE)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_is_artwork() {
+        assert!(is_artwork(Utf8Path::new("/a/cover.jpg")));
+        assert!(is_artwork(Utf8Path::new("/a/Folder.PNG")));
+        assert!(is_artwork(Utf8Path::new("/a/front.webp")));
+        assert!(!is_artwork(Utf8Path::new("/a/01.artist.song.flac")));
+        assert!(is_jpeg(Utf8Path::new("/a/some_file.JPEG")));
+        assert!(!is_jpeg(Utf8Path::new("/a/front.png")));
+    }
+
+    #[test]
+    fn test_load_jpeg() {
+        let image = Image::load(&fixture!("commands/artfix/tester.too_big/cover.jpg")).unwrap();
+        assert_eq!((900, 900), (image.width, image.height));
+        assert_eq!(900 * 900 * 3, image.pixels.len());
+        assert!(image.is_square());
+    }
+
+    #[test]
+    fn test_cmyk_jpeg() {
+        let mut cmyk = Vec::new();
+        Encoder::new(&mut cmyk, 90)
+            .encode(&[0, 255, 255, 0].repeat(64), 8, 8, ColorType::Cmyk)
+            .unwrap();
+
+        let image = Image::from_jpeg(&cmyk).unwrap();
+        assert_eq!(ColorType::Rgb, image.color);
+        assert_eq!(8 * 8 * 3, image.pixels.len());
+        assert!(image.pixels[0] > 240 && image.pixels[1] < 15 && image.pixels[2] < 15);
+    }
+
+    #[test]
+    fn test_load_png() {
+        let image = Image::load(&fixture!("commands/artfix/tester.png_art/folder.png")).unwrap();
+        assert_eq!((800, 800), (image.width, image.height));
+        assert_eq!(ColorType::Rgb, image.color);
+        assert_eq!(&[255, 0, 0], &image.pixels[0..3]);
+        assert_eq!(&[0, 0, 255], &image.pixels[image.pixels.len() - 3..]);
+    }
+
+    #[test]
+    fn test_png_variants() {
+        let dir = fixture!("image");
+
+        for name in [
+            "grey_alpha.png",
+            "interlaced.png",
+            "palette.png",
+            "rgb16.png",
+            "rgb_trns.png",
+        ] {
+            let image = Image::load(&dir.join(name)).unwrap();
+            assert_eq!((5, 3), (image.width, image.height), "{}", name);
+            let channels = if image.color == ColorType::Luma { 1 } else { 3 };
+            assert_eq!(15 * channels, image.pixels.len(), "{}", name);
+            assert_eq!(&image.pixels[..channels], &[255; 3][..channels], "{}", name);
+            assert_eq!(
+                &image.pixels[14 * channels..],
+                &[0; 3][..channels],
+                "{}",
+                name
+            );
+        }
+
+        assert!(Image::from_png(b"not a png").is_err());
+        assert!(Image::from_bytes(b"GIF89a").is_err());
+        assert!(Image::from_bytes(&fs::read(dir.join("palette.png")).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_resize_and_encode() {
+        let image = Image::load(&fixture!("commands/artfix/tester.png_art/folder.png"))
+            .unwrap()
+            .resize(100, 100)
+            .unwrap();
+
+        assert_eq!(100 * 100 * 3, image.pixels.len());
+
+        let jpeg = Image::from_jpeg(&image.to_jpeg(75).unwrap()).unwrap();
+        assert_eq!((100, 100), (jpeg.width, jpeg.height));
+    }
+
+    // A grey square with a red band on the left, and a shade of blue outside it.
+    fn framed(width: usize, height: usize, side: usize, busy: bool) -> Image {
+        let (x0, y0) = ((width - side) / 2, (height - side) / 2);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                if x < x0 || x >= x0 + side || y < y0 || y >= y0 + side {
+                    if busy {
+                        [(x * 37 % 256) as u8, (y * 91 % 256) as u8, 0]
+                    } else {
+                        [0, 0, 200]
+                    }
+                } else if x < x0 + 2 {
+                    [255, 0, 0]
+                } else {
+                    [128, 128, 128]
+                }
+            })
+            .collect();
+
+        Image {
+            width,
+            height,
+            color: ColorType::Rgb,
+            pixels,
+        }
+    }
+
+    fn pixel(image: &Image, x: usize, y: usize) -> &[u8] {
+        let i = (y * image.width + x) * 3;
+        &image.pixels[i..i + 3]
+    }
+
+    // Smooth waves of colour, which look the same at any size.
+    fn waves(side: usize, frequency: f64) -> Image {
+        let level = |v: usize| {
+            let v = v as f64 * frequency / side as f64;
+            (128.0 + 100.0 * v.sin()) as u8
+        };
+
+        let pixels = (0..side)
+            .flat_map(|y| (0..side).map(move |x| (x, y)))
+            .flat_map(|(x, y)| [level(x), level(y), level(x + y)])
+            .collect();
+
+        Image {
+            width: side,
+            height: side,
+            color: ColorType::Rgb,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn test_difference_hash() {
+        let hash = waves(400, 7.0).difference_hash().unwrap();
+        let smaller = Image::from_jpeg(&waves(150, 7.0).to_jpeg(50).unwrap())
+            .unwrap()
+            .difference_hash()
+            .unwrap();
+
+        assert!(hash_distance(hash, smaller) <= 6);
+
+        let other = waves(400, 13.0).difference_hash().unwrap();
+        assert!(hash_distance(hash, other) > 10);
+        assert_eq!(0, hash_distance(hash, hash));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let black = Image::load(&fixture!("commands/artfix/tester.good_art/cover.jpg"))
+            .unwrap()
+            .fingerprint()
+            .unwrap();
+        let blue = Image::load(&fixture!("commands/artfix/tester.too_big/cover.jpg"))
+            .unwrap()
+            .fingerprint()
+            .unwrap();
+        let small_blue = Image::load(&fixture!("commands/artfix/tester.too_big/cover.jpg"))
+            .unwrap()
+            .resize(100, 100)
+            .unwrap()
+            .fingerprint()
+            .unwrap();
+
+        assert_eq!(black.hash, blue.hash);
+        assert!(!black.looks_like(&blue, 6));
+        assert!(blue.looks_like(&small_blue, 0));
+        assert!(
+            waves(300, 7.0)
+                .fingerprint()
+                .unwrap()
+                .looks_like(&waves(200, 7.0).fingerprint().unwrap(), 6)
+        );
+    }
+
+    #[test]
+    fn test_is_nearly_square() {
+        assert!(is_nearly_square(1000, 1000, 0.0));
+        assert!(is_nearly_square(1000, 1012, 3.0));
+        assert!(is_nearly_square(1030, 1000, 3.0));
+        assert!(!is_nearly_square(1031, 1000, 3.0));
+        assert!(!is_nearly_square(1600, 800, 3.0));
+        assert!(!is_nearly_square(0, 0, 3.0));
+    }
+
+    #[test]
+    fn test_square_trims_border() {
+        let image = framed(20, 24, 20, false).square();
+        assert_eq!((20, 20), (image.width, image.height));
+        assert_eq!(&[255, 0, 0], pixel(&image, 0, 0));
+        assert_eq!(&[128, 128, 128], pixel(&image, 19, 19));
+    }
+
+    #[test]
+    fn test_square_pads_plain_edges() {
+        let pixels = (0..20)
+            .flat_map(|y| (0..24).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                if (2..18).contains(&y) {
+                    [x as u8 * 10, 0, 0]
+                } else {
+                    [0, 0, 200]
+                }
+            })
+            .collect();
+
+        let image = Image {
+            width: 24,
+            height: 20,
+            color: ColorType::Rgb,
+            pixels,
+        }
+        .square();
+
+        assert_eq!((24, 24), (image.width, image.height));
+        assert_eq!(&[0, 0, 200], pixel(&image, 0, 0));
+        assert_eq!(&[0, 0, 200], pixel(&image, 0, 3));
+        assert_eq!(&[50, 0, 0], pixel(&image, 5, 12));
+    }
+
+    #[test]
+    fn test_square_crops_busy_edges() {
+        let image = framed(20, 26, 18, true).square();
+        assert_eq!((20, 20), (image.width, image.height));
+        assert_eq!(&[255, 0, 0], pixel(&image, 1, 10));
+        assert_eq!(&[128, 128, 128], pixel(&image, 10, 16));
+    }
+
+    #[test]
+    fn test_jpeg_needs_conversion() {
+        assert!(
+            !jpeg_needs_conversion(&fixture!("commands/artfix/tester.good_art/cover.jpg")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_png_called_jpg() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file = tmp.path().join("cover.jpg");
+        fs::copy(fixture!("image/rgb_trns.png"), &file).unwrap();
+
+        assert!(jpeg_needs_conversion(&file).unwrap());
+        assert_eq!((5, 3), {
+            let image = Image::load(&file).unwrap();
+            (image.width, image.height)
+        });
+    }
+
+    #[test]
+    fn test_image_type() {
+        assert_eq!(Some(ImageType::Jpeg), image_type(&[0xff, 0xd8, 0xff, 0xe0]));
+        assert_eq!(Some(ImageType::Png), image_type(&PNG_SIGNATURE));
+        assert_eq!(Some(ImageType::Webp), image_type(b"RIFF\0\0\0\0WEBPVP8 "));
+        assert_eq!(None, image_type(b"RIFF\0\0\0\0WAVE"));
+        assert_eq!(None, image_type(&[]));
+    }
+}
@@ -0,0 +1,249 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::time::UNIX_EPOCH;
+
+// The size and modification time of every file a result depends on. If any of them changes, the
+// result has to be worked out again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint(Vec<(String, u64, u64, u32)>);
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CacheEntry<T> {
+    fingerprint: Fingerprint,
+    results: T,
+}
+
+// Check results, stored between runs. Results found with different options, like lint --audio,
+// are kept in separate variants, so a run with one set of options doesn't lose what a run with
+// another found. The whole cache is thrown away if the checks_hash it was written with is not the
+// one it is loaded with.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LintCache<T> {
+    checks_hash: u64,
+    variants: HashMap<String, HashMap<String, CacheEntry<T>>>,
+    #[serde(skip)]
+    variant: String,
+    #[serde(skip)]
+    file: Option<Utf8PathBuf>,
+}
+
+// FNV-1a. std's hashers can change from one Rust release to the next, and this goes on disk.
+pub struct StableHash(u64);
+
+impl Default for StableHash {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl StableHash {
+    // Every value is terminated, so ["ab", "c"] and ["a", "bc"] hash differently.
+    pub fn add(&mut self, value: impl AsRef<str>) -> &mut Self {
+        for byte in value.as_ref().bytes().chain([0xff]) {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+
+        self
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Fingerprint {
+    pub fn new<P: AsRef<Utf8Path>>(files: &[P]) -> anyhow::Result<Self> {
+        let mut ret = Vec::new();
+
+        for file in files {
+            let file = file.as_ref();
+            let metadata = fs::metadata(file)?;
+            let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+            ret.push((
+                file.to_string(),
+                metadata.len(),
+                mtime.as_secs(),
+                mtime.subsec_nanos(),
+            ));
+        }
+
+        ret.sort();
+        Ok(Self(ret))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> LintCache<T> {
+    // With no file, nothing is read or written, and every lookup misses. A cache which can't be
+    // read is treated as empty: the worst that can happen is we do the work again.
+    pub fn load(file: Option<&Utf8Path>, checks_hash: u64, variant: &str) -> Self {
+        let empty = Self {
+            checks_hash,
+            variants: HashMap::new(),
+            variant: variant.to_owned(),
+            file: file.map(|f| f.to_path_buf()),
+        };
+
+        let file = match file {
+            Some(file) if file.exists() => file,
+            _ => return empty,
+        };
+
+        let cache: Option<Self> = fs::read_to_string(file)
+            .ok()
+            .and_then(|raw| serde_json::from_str(&raw).ok());
+
+        match cache {
+            Some(cache) if cache.checks_hash == checks_hash => Self {
+                variant: variant.to_owned(),
+                file: Some(file.to_path_buf()),
+                ..cache
+            },
+            _ => empty,
+        }
+    }
+
+    pub fn get(&self, path: &Utf8Path, fingerprint: &Fingerprint) -> Option<&T> {
+        self.file.as_ref()?;
+
+        self.variants
+            .get(&self.variant)?
+            .get(path.as_str())
+            .filter(|entry| &entry.fingerprint == fingerprint)
+            .map(|entry| &entry.results)
+    }
+
+    pub fn insert(&mut self, path: &Utf8Path, fingerprint: Fingerprint, results: T) {
+        if self.file.is_some() {
+            self.variants
+                .entry(self.variant.clone())
+                .or_default()
+                .insert(
+                    path.to_string(),
+                    CacheEntry {
+                        fingerprint,
+                        results,
+                    },
+                );
+        }
+    }
+
+    // Entries for things which have gone are dropped, so the cache doesn't grow forever. Other
+    // processes may be reading the cache, so it is replaced whole, never rewritten in place.
+    pub fn save(mut self) -> anyhow::Result<()> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        for entries in self.variants.values_mut() {
+            entries.retain(|path, _| Utf8Path::new(path).exists());
+        }
+
+        let dir = file.parent().unwrap_or(Utf8Path::new("."));
+        fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        tmp.write_all(serde_json::to_string(&self)?.as_bytes())?;
+        tmp.persist(&file)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_fingerprint() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let file = tmp.path().join("test.flac");
+
+        let before = Fingerprint::new(&[&file]).unwrap();
+        assert_eq!(before, Fingerprint::new(&[&file]).unwrap());
+
+        fs::write(&file, "changed").unwrap();
+        assert_ne!(before, Fingerprint::new(&[&file]).unwrap());
+        assert!(Fingerprint::new(&[tmp.path().join("missing.flac")]).is_err());
+    }
+
+    #[test]
+    fn test_lint_cache() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let file = tmp.path().join("test.flac");
+        let cache_file = tmp.path().join("cache").join("lint.json");
+        let fingerprint = Fingerprint::new(&[&file]).unwrap();
+
+        let mut cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 1, "a");
+        assert_eq!(None, cache.get(&file, &fingerprint));
+        cache.insert(&file, fingerprint.clone(), vec!["problem".to_owned()]);
+        cache.insert(&tmp.path().join("gone.flac"), fingerprint.clone(), vec![]);
+        cache.save().unwrap();
+
+        let cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 1, "a");
+        assert_eq!(
+            Some(&vec!["problem".to_owned()]),
+            cache.get(&file, &fingerprint)
+        );
+        assert_eq!(1, cache.variants["a"].len());
+
+        let cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 2, "a");
+        assert_eq!(None, cache.get(&file, &fingerprint));
+    }
+
+    #[test]
+    fn test_lint_cache_variants() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let file = tmp.path().join("test.flac");
+        let cache_file = tmp.path().join("lint.json");
+        let fingerprint = Fingerprint::new(&[&file]).unwrap();
+
+        let mut cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 1, "a");
+        cache.insert(&file, fingerprint.clone(), vec!["from a".to_owned()]);
+        cache.save().unwrap();
+
+        let mut cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 1, "b");
+        assert_eq!(None, cache.get(&file, &fingerprint));
+        cache.insert(&file, fingerprint.clone(), vec!["from b".to_owned()]);
+        cache.save().unwrap();
+
+        let cache: LintCache<Vec<String>> = LintCache::load(Some(&cache_file), 1, "a");
+        assert_eq!(
+            Some(&vec!["from a".to_owned()]),
+            cache.get(&file, &fingerprint)
+        );
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // Whatever Rust release built us.
+        assert_eq!(
+            0xfc18_2483_ee08_06dc,
+            StableHash::default().add("abc").finish()
+        );
+        assert_ne!(
+            StableHash::default().add("ab").add("c").finish(),
+            StableHash::default().add("a").add("bc").finish()
+        );
+    }
+
+    #[test]
+    fn test_lint_cache_disabled() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let file = tmp.path().join("test.flac");
+        let fingerprint = Fingerprint::new(&[&file]).unwrap();
+
+        let mut cache: LintCache<Vec<String>> = LintCache::load(None, 1, "a");
+        cache.insert(&file, fingerprint.clone(), vec!["problem".to_owned()]);
+        assert_eq!(None, cache.get(&file, &fingerprint));
+        cache.save().unwrap();
+    }
+}
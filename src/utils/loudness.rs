@@ -0,0 +1,341 @@
+use crate::utils::decoder;
+use crate::utils::metadata::RawTags;
+use anyhow::anyhow;
+use camino::Utf8Path;
+use std::f64::consts::PI;
+
+// Loudness measurement as described by ITU-R BS.1770 and EBU R128, and the ReplayGain 2.0 tags
+// which carry the results. ReplayGain 2.0 levels everything to -18 LUFS.
+
+pub const REFERENCE_LOUDNESS: f64 = -18.0;
+
+pub const TRACK_GAIN: &str = "replaygain_track_gain";
+pub const TRACK_PEAK: &str = "replaygain_track_peak";
+pub const ALBUM_GAIN: &str = "replaygain_album_gain";
+pub const ALBUM_PEAK: &str = "replaygain_album_peak";
+pub const REPLAYGAIN_TAGS: [&str; 4] = [TRACK_GAIN, TRACK_PEAK, ALBUM_GAIN, ALBUM_PEAK];
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// The mean square power of every 400ms gating block in a piece of audio, and its sample peak.
+#[derive(Debug, Clone, Default)]
+pub struct Loudness {
+    blocks: Vec<f64>,
+    pub peak: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayGain {
+    pub track_gain: f64,
+    pub track_peak: f64,
+    pub album_gain: Option<f64>,
+    pub album_peak: Option<f64>,
+}
+
+impl Loudness {
+    /// Decodes the whole of the given file.
+    pub fn from_path(file: &Utf8Path) -> anyhow::Result<Self> {
+        let mut meter: Option<Meter> = None;
+
+        decoder::decode::<f32, _>(file, |spec, samples| {
+            meter
+                .get_or_insert_with(|| Meter::new(spec.rate, spec.channels.count()))
+                .add_interleaved(samples);
+        })?;
+
+        match meter {
+            Some(meter) => Ok(meter.finish()),
+            None => Err(anyhow!("no audio in {}", file)),
+        }
+    }
+
+    /// An album is measured as though its tracks were one long piece of audio.
+    pub fn combine(tracks: &[Loudness]) -> Self {
+        Self {
+            blocks: tracks.iter().flat_map(|t| t.blocks.clone()).collect(),
+            peak: tracks.iter().map(|t| t.peak).fold(0.0, f64::max),
+        }
+    }
+
+    /// Gated loudness in LUFS. None if the audio is too short or too quiet to measure.
+    pub fn integrated(&self) -> Option<f64> {
+        let above_absolute: Vec<f64> = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|&b| to_lufs(b) > ABSOLUTE_GATE)
+            .collect();
+
+        let relative_gate = to_lufs(mean(&above_absolute)?) + RELATIVE_GATE;
+
+        let above_relative: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|&b| to_lufs(b) > relative_gate)
+            .collect();
+
+        mean(&above_relative).map(to_lufs)
+    }
+}
+
+impl ReplayGain {
+    /// Leave out the album for loose tracks.
+    pub fn new(track: &Loudness, album: Option<&Loudness>) -> Option<Self> {
+        let album_gain = match album {
+            Some(album) => Some(REFERENCE_LOUDNESS - album.integrated()?),
+            None => None,
+        };
+
+        Some(Self {
+            track_gain: REFERENCE_LOUDNESS - track.integrated()?,
+            track_peak: track.peak,
+            album_gain,
+            album_peak: album.map(|a| a.peak),
+        })
+    }
+
+    pub fn from_rawtags(rawtags: &RawTags) -> Option<Self> {
+        let get = |name: &str| {
+            rawtags
+                .iter()
+                .find(|(k, _)| k == name)
+                .and_then(|(_, v)| v.trim_end_matches("dB").trim().parse::<f64>().ok())
+        };
+
+        Some(Self {
+            track_gain: get(TRACK_GAIN)?,
+            track_peak: get(TRACK_PEAK)?,
+            album_gain: get(ALBUM_GAIN),
+            album_peak: get(ALBUM_PEAK),
+        })
+    }
+
+    /// Tag names and values, as written into files. Tags for a missing album are not included.
+    pub fn tags(&self) -> Vec<(&'static str, String)> {
+        let mut ret = vec![
+            (TRACK_GAIN, format_gain(self.track_gain)),
+            (TRACK_PEAK, format_peak(self.track_peak)),
+        ];
+
+        if let (Some(gain), Some(peak)) = (self.album_gain, self.album_peak) {
+            ret.push((ALBUM_GAIN, format_gain(gain)));
+            ret.push((ALBUM_PEAK, format_peak(peak)));
+        }
+
+        ret
+    }
+}
+
+fn format_gain(gain: f64) -> String {
+    format!("{:.2} dB", gain)
+}
+
+fn format_peak(peak: f64) -> String {
+    format!("{:.6}", peak)
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+// A second-order IIR filter, in transposed direct form II.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+// BS.1770 gives K-weighting coefficients for 48kHz only. These are derived for any sample rate,
+// from the filter parameters which produce the published ones.
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+// Gating blocks are 400ms long and overlap by 75%, so we collect the power of each 100ms segment,
+// and make blocks out of four at a time.
+struct Meter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    segment_length: usize,
+    segment_position: usize,
+    segment_sum: f64,
+    segments: Vec<f64>,
+    peak: f64,
+}
+
+impl Meter {
+    fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            channels,
+            filters: vec![k_weighting(rate); channels],
+            segment_length: (rate as usize / 10).max(1),
+            segment_position: 0,
+            segment_sum: 0.0,
+            segments: Vec::new(),
+            peak: 0.0,
+        }
+    }
+
+    fn add_interleaved(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (sample, filters) in frame.iter().zip(self.filters.iter_mut()) {
+                let sample = *sample as f64;
+                self.peak = self.peak.max(sample.abs());
+                let shelved = filters[0].process(sample);
+                let weighted = filters[1].process(shelved);
+                self.segment_sum += weighted * weighted;
+            }
+
+            self.segment_position += 1;
+
+            if self.segment_position == self.segment_length {
+                self.segments
+                    .push(self.segment_sum / self.segment_length as f64);
+                self.segment_position = 0;
+                self.segment_sum = 0.0;
+            }
+        }
+    }
+
+    fn finish(self) -> Loudness {
+        Loudness {
+            blocks: self
+                .segments
+                .windows(4)
+                .map(|w| w.iter().sum::<f64>() / 4.0)
+                .collect(),
+            peak: self.peak,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    fn measure(samples: &[f32]) -> Loudness {
+        let mut meter = Meter::new(48000, 2);
+        meter.add_interleaved(samples);
+        meter.finish()
+    }
+
+    // 1kHz, in stereo, with both channels peaking at the given level.
+    fn sine(seconds: usize, dbfs: f64) -> Vec<f32> {
+        let amplitude = 10f64.powf(dbfs / 20.0);
+
+        (0..seconds * 48000)
+            .flat_map(|i| {
+                let s = (amplitude * (2.0 * PI * 1000.0 * i as f64 / 48000.0).sin()) as f32;
+                [s, s]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_integrated() {
+        let loudness = measure(&sine(5, -23.0));
+        assert!((loudness.integrated().unwrap() + 23.0).abs() < 0.1);
+        assert!((loudness.peak - 0.0708).abs() < 0.001);
+
+        let quiet = measure(&sine(5, -80.0));
+        assert_eq!(None, quiet.integrated());
+
+        let short = measure(&sine(5, -23.0)[..9600]);
+        assert_eq!(None, short.integrated());
+    }
+
+    #[test]
+    fn test_replaygain() {
+        let loud = measure(&sine(5, -12.0));
+        let quiet = measure(&sine(5, -30.0));
+        let album = Loudness::combine(&[loud.clone(), quiet.clone()]);
+
+        let gain = ReplayGain::new(&quiet, Some(&album)).unwrap();
+
+        assert!((gain.track_gain - 12.0).abs() < 0.1);
+        // The quiet track falls below the relative gate, so the loud one sets the album level.
+        assert!((gain.album_gain.unwrap() + 6.0).abs() < 0.1);
+        assert_eq!(Some(loud.peak), gain.album_peak);
+        assert_eq!(4, gain.tags().len());
+
+        let gain = ReplayGain::new(&quiet, None).unwrap();
+        assert_eq!(2, gain.tags().len());
+        assert_eq!(TRACK_GAIN, gain.tags()[0].0);
+        assert!(gain.tags()[0].1.ends_with(" dB"));
+    }
+
+    #[test]
+    fn test_from_rawtags() {
+        let rawtags: RawTags = vec![
+            ("title".to_owned(), "Song".to_owned()),
+            (TRACK_GAIN.to_owned(), "-6.52 dB".to_owned()),
+            (TRACK_PEAK.to_owned(), "0.988525".to_owned()),
+        ];
+
+        assert_eq!(
+            Some(ReplayGain {
+                track_gain: -6.52,
+                track_peak: 0.988525,
+                album_gain: None,
+                album_peak: None,
+            }),
+            ReplayGain::from_rawtags(&rawtags)
+        );
+
+        assert_eq!(None, ReplayGain::from_rawtags(&Vec::new()));
+    }
+
+    #[test]
+    fn test_from_path() {
+        let loudness = Loudness::from_path(&fixture!("info/test.flac")).unwrap();
+        assert_eq!(2, loudness.blocks.len());
+        assert!(Loudness::from_path(&fixture!("info/bad_file.flac")).is_err());
+    }
+}
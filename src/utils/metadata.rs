@@ -1,14 +1,10 @@
-use anyhow::{Context, anyhow};
+use crate::utils::formats::{self, MediaFormat};
+use anyhow::anyhow;
 use camino::{Utf8Path, Utf8PathBuf};
-use id3::Tag as Id3Tag;
-use id3::TagLike;
-use metaflac::Tag as FlacTag;
-use mp3_metadata::{self, MP3Metadata};
 use std::collections::HashSet;
 use std::ffi::OsStr;
-use std::fs;
 
-const UNDEFINED: &str = "unknown";
+pub const UNDEFINED: &str = "unknown";
 
 pub type RawTags = Vec<(String, String)>;
 
@@ -17,15 +13,18 @@ pub struct AurMetadata {
     pub filename: String,
     pub path: Utf8PathBuf,
     pub filetype: String,
+    pub format: &'static dyn MediaFormat,
     pub tags: AurTags,
     pub time: AurTime,
     pub quality: AurQuality,
     pub rawtags: RawTags,
     pub has_picture: bool,
     pub in_tracks: bool,
+    pub encoding: Option<String>,
 }
 
 type AurTNum = u32;
+type AurDiscNum = u32;
 type AurYear = i32;
 
 #[derive(Debug, PartialEq)]
@@ -34,6 +33,8 @@ pub struct AurTags {
     pub album: String,
     pub title: String,
     pub t_num: AurTNum,
+    /// Zero if the file has no disc number.
+    pub disc: AurDiscNum,
     pub year: AurYear,
     pub genre: String,
 }
@@ -54,44 +55,14 @@ pub struct AurTime {
 impl AurMetadata {
     pub fn new(file: &Utf8Path) -> anyhow::Result<Self> {
         let file = file.canonicalize_utf8()?;
-        let tags: AurTags;
-        let filetype: String;
-        let quality: AurQuality;
-        let time: AurTime;
-        let rawtags: RawTags;
-        let has_picture: bool;
         let in_tracks = in_tracks(&file);
 
-        match file.extension() {
-            Some("flac") => {
-                let raw_info = FlacTag::read_from_path(&file)?;
-                filetype = "flac".to_owned();
-                tags = AurTags::from_flac(&raw_info)?;
-                quality = AurQuality::from_flac(&raw_info)?;
-                time = AurTime::from_flac(&raw_info)?;
-                rawtags = Self::rawtags_from_flac(&raw_info)?;
-                has_picture = Self::has_picture_flac(&raw_info)?;
-            }
-            Some("mp3") => {
-                let id3tags =
-                    Id3Tag::read_from_path(&file).context(format!("problem reading {}", file))?;
-                filetype = "mp3".to_owned();
-                tags = AurTags::from_mp3(&id3tags)
-                    .context(format!("problem with metadata for {}", file))?;
-                quality = AurQuality {
-                    bit_depth: 0,
-                    sample_rate: 0,
-                    formatted: String::new(),
-                };
-                time = AurTime {
-                    raw: 0,
-                    formatted: String::new(),
-                };
-                rawtags = Self::rawtags_from_mp3(&id3tags)?;
-                has_picture = Self::has_picture_mp3(&id3tags)?;
-            }
-            _ => return Err(anyhow!("Unsupported filetype: {}", file)),
-        }
+        let format = match formats::for_path(&file) {
+            Some(format) => format,
+            None => return Err(anyhow!("Unsupported filetype: {}", file)),
+        };
+
+        let data = format.read(&file)?;
 
         let filename = match file.file_name() {
             Some(name) => name.to_owned(),
@@ -101,36 +72,24 @@ impl AurMetadata {
         Ok(Self {
             filename,
             path: file.to_path_buf(),
-            tags,
-            filetype,
-            time,
-            quality,
-            rawtags,
-            has_picture,
+            filetype: format.name().to_owned(),
+            format,
+            tags: data.tags,
+            time: data.time,
+            quality: data.quality,
+            rawtags: data.rawtags,
+            has_picture: data.has_picture,
             in_tracks,
+            encoding: data.encoding,
         })
     }
 
     pub fn time(&self) -> AurTime {
-        match self.filetype.as_str() {
-            "flac" => self.time.clone(),
-            "mp3" => match mp3_metadata::read_from_file(&self.path) {
-                Ok(metadata) => AurTime::from_mp3(&metadata),
-                Err(e) => panic!("Failed to read MP3 metadata in {}: {}", self.path, e),
-            },
-            _ => unreachable!("Cannot get time of unknown filetype"),
-        }
+        self.time.clone()
     }
 
     pub fn quality(&self) -> AurQuality {
-        match self.filetype.as_str() {
-            "flac" => self.quality.clone(),
-            "mp3" => match mp3_metadata::read_from_file(&self.path) {
-                Ok(metadata) => AurQuality::from_mp3(&self.path, &metadata),
-                Err(e) => panic!("Failed to read MP3 metadata in {}: {}", self.path, e),
-            },
-            _ => unreachable!("Cannot get time of unknown filetype"),
-        }
+        self.quality.clone()
     }
 
     pub fn get_tag(&self, tag: &str) -> anyhow::Result<String> {
@@ -140,161 +99,62 @@ impl AurMetadata {
             "title" => self.tags.title.to_owned(),
             "genre" => self.tags.genre.to_owned(),
             "t_num" => self.tags.t_num.to_string(),
+            "disc" => self.tags.disc.to_string(),
             "year" => self.tags.year.to_string(),
             _ => return Err(anyhow!("Unknown tag: {}", tag)),
         };
 
         Ok(ret)
     }
-
-    fn rawtags_from_flac(raw_info: &FlacTag) -> anyhow::Result<RawTags> {
-        let ret: RawTags;
-
-        if let Some(vorbis_comments) = raw_info.vorbis_comments() {
-            ret = vorbis_comments
-                .comments
-                .clone()
-                .into_iter()
-                .map(|(tag, val)| (tag.to_lowercase(), val.join(",")))
-                .collect()
-        } else {
-            ret = Vec::new();
-        }
-
-        Ok(ret)
-    }
-
-    fn rawtags_from_mp3(id3tag: &Id3Tag) -> anyhow::Result<RawTags> {
-        let ret: RawTags = id3tag
-            .frames()
-            .map(|frame| {
-                (
-                    frame.id().to_string().to_lowercase(),
-                    frame.content().to_string(),
-                )
-            })
-            .collect();
-
-        Ok(ret)
-    }
-
-    fn has_picture_flac(raw_info: &FlacTag) -> anyhow::Result<bool> {
-        let pictures: Vec<_> = raw_info.pictures().collect();
-        Ok(!pictures.is_empty())
-    }
-
-    fn has_picture_mp3(id3tag: &Id3Tag) -> anyhow::Result<bool> {
-        let pictures: Vec<_> = id3tag.pictures().collect();
-        Ok(!pictures.is_empty())
-    }
-}
-
-impl AurTags {
-    fn from_flac(raw_info: &FlacTag) -> anyhow::Result<Self> {
-        let comments = raw_info.vorbis_comments();
-
-        Ok(Self {
-            artist: Self::first_or_default(comments.and_then(|c| c.artist())),
-            album: Self::first_or_default(comments.and_then(|c| c.album())),
-            title: Self::first_or_default(comments.and_then(|c| c.title())),
-            t_num: comments.and_then(|c| c.track()).unwrap_or(0),
-            year: Self::first_or_default(comments.and_then(|c| c.get("DATE")))
-                .parse::<i32>()
-                .unwrap_or(0),
-            genre: Self::first_or_default(comments.and_then(|c| c.genre())),
-        })
-    }
-
-    fn from_mp3(id3tag: &Id3Tag) -> anyhow::Result<Self> {
-        Ok(Self {
-            artist: id3tag.artist().unwrap_or(UNDEFINED).to_owned(),
-            album: id3tag.album().unwrap_or(UNDEFINED).to_owned(),
-            title: id3tag.title().unwrap_or(UNDEFINED).to_owned(),
-            t_num: id3tag.track().unwrap_or(0),
-            year: id3tag.year().unwrap_or(0),
-            genre: id3tag.genre().unwrap_or(UNDEFINED).to_owned(),
-        })
-    }
-
-    fn first_or_default(option: Option<&Vec<String>>) -> String {
-        option
-            .and_then(|vec| vec.first())
-            .unwrap_or(&UNDEFINED.to_owned())
-            .clone()
-    }
 }
 
 impl AurQuality {
-    fn from_flac(raw_info: &FlacTag) -> anyhow::Result<Self> {
-        let ret = match raw_info.get_streaminfo() {
-            Some(info) => Self {
-                bit_depth: info.bits_per_sample,
-                sample_rate: info.sample_rate,
-                formatted: format!("{}-bit/{}Hz", info.bits_per_sample, info.sample_rate),
-            },
-            None => Self {
-                bit_depth: 0,
-                sample_rate: 0,
-                formatted: "unknown".to_owned(),
-            },
-        };
-
-        Ok(ret)
+    pub fn lossless(bit_depth: u8, sample_rate: u32) -> Self {
+        Self {
+            bit_depth,
+            sample_rate,
+            formatted: format!("{}-bit/{}Hz", bit_depth, sample_rate),
+        }
     }
 
-    fn from_mp3(path: &Utf8Path, metadata: &MP3Metadata) -> Self {
-        let file_size = fs::metadata(path).unwrap().len();
-        let duration = metadata.duration.as_secs();
-
-        let bitrate = if duration > 0 {
-            file_size * 8 / duration / 1000
-        } else {
-            match metadata.frames.first().map(|f| f.bitrate) {
-                Some(bitrate) => bitrate as u64,
-                None => 0,
-            }
-        };
-
+    // Lossy formats put their bitrate, in kbps, where the sample rate would go.
+    pub fn lossy(bitrate: u32) -> Self {
         Self {
             bit_depth: 16,
-            sample_rate: bitrate as u32,
+            sample_rate: bitrate,
             formatted: format!("{}kbps", bitrate),
         }
     }
+
+    pub fn unknown() -> Self {
+        Self {
+            bit_depth: 0,
+            sample_rate: 0,
+            formatted: UNDEFINED.to_owned(),
+        }
+    }
 }
 
 impl AurTime {
-    fn from_flac(raw_info: &FlacTag) -> anyhow::Result<Self> {
-        let ret = match raw_info.get_streaminfo() {
-            Some(info) => {
-                let duration: u64 = if info.sample_rate > 0 {
-                    info.total_samples / info.sample_rate as u64
-                } else {
-                    0
-                };
-
-                Self {
-                    raw: duration,
-                    formatted: Self::format_duration(&duration),
-                }
-            }
-            None => Self {
-                raw: 0,
-                formatted: "unknown".to_owned(),
-            },
-        };
-
-        Ok(ret)
+    pub fn from_seconds(seconds: u64) -> Self {
+        Self {
+            raw: seconds,
+            formatted: Self::format_duration(&seconds),
+        }
     }
 
-    fn from_mp3(metadata: &MP3Metadata) -> Self {
-        let duration = metadata.duration.as_secs();
+    pub fn unknown() -> Self {
         Self {
-            raw: duration,
-            formatted: Self::format_duration(&duration),
+            raw: 0,
+            formatted: UNDEFINED.to_owned(),
         }
     }
 
+    // A raw value of zero may be a real, if very short, track.
+    pub fn is_unknown(&self) -> bool {
+        self.formatted == UNDEFINED
+    }
+
     fn format_duration(seconds: &u64) -> String {
         let hours = seconds / 3600;
         let minutes = (seconds % 3600) / 60;
@@ -310,6 +170,7 @@ impl Default for AurTags {
             album: UNDEFINED.to_owned(),
             title: UNDEFINED.to_owned(),
             t_num: 0,
+            disc: 0,
             year: 0,
             genre: UNDEFINED.to_owned(),
         }
@@ -317,32 +178,23 @@ impl Default for AurTags {
 }
 
 pub fn expected_tags(filetype: &str) -> anyhow::Result<HashSet<String>> {
-    match filetype {
-        "flac" => Ok(HashSet::from([
-            "artist".to_owned(),
-            "album".to_owned(),
-            "title".to_owned(),
-            "tracknumber".to_owned(),
-            "genre".to_owned(),
-            "date".to_owned(),
-        ])),
-        "mp3" => Ok(HashSet::from([
-            "tpe1".to_owned(),
-            "talb".to_owned(),
-            "tit2".to_owned(),
-            "trck".to_owned(),
-            "tyer".to_owned(),
-            "tcon".to_owned(),
-        ])),
-        _ => Err(anyhow!("unknown filetype")),
+    match formats::by_name(filetype) {
+        Some(format) => Ok(format.expected_tags()),
+        None => Err(anyhow!("unknown filetype")),
     }
 }
 
 pub fn irrelevant_tags(filetype: &str) -> anyhow::Result<HashSet<String>> {
-    match filetype {
-        "flac" => Ok(HashSet::from(["encoder".to_owned(), "blank".to_owned()])),
-        "mp3" => Ok(HashSet::from(["tlen".to_owned(), "tsse".to_owned()])),
-        _ => Err(anyhow!("unknown filetype")),
+    match formats::by_name(filetype) {
+        Some(format) => Ok(format.irrelevant_tags()),
+        None => Err(anyhow!("unknown filetype")),
+    }
+}
+
+pub fn optional_tags(filetype: &str) -> anyhow::Result<HashSet<String>> {
+    match formats::by_name(filetype) {
+        Some(format) => Ok(format.optional_tags()),
+        None => Err(anyhow!("unknown filetype")),
     }
 }
 
@@ -358,6 +210,8 @@ pub fn in_tracks(file: &Utf8Path) -> bool {
 #[cfg(test)]
 mod test {
     use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use id3::TagLike;
     use snltest::fixture;
 
     #[test]
@@ -368,6 +222,7 @@ mod test {
             title: "Test Title".to_owned(),
             genre: "Test Genre".to_owned(),
             t_num: 6,
+            disc: 0,
             year: 2021,
         };
 
@@ -389,6 +244,84 @@ mod test {
         assert_eq!("00:00:00", mp3_result.time().formatted);
     }
 
+    #[test]
+    fn test_metadata_valid_opus() {
+        let expected_tags = AurTags {
+            artist: "Test Artist".to_owned(),
+            album: "Test Album".to_owned(),
+            title: "Test Title".to_owned(),
+            genre: "Test Genre".to_owned(),
+            t_num: 6,
+            disc: 0,
+            year: 2021,
+        };
+
+        let opus_result = AurMetadata::new(&fixture!("info/test.opus")).unwrap();
+
+        assert_eq!("opus", opus_result.filetype);
+        assert_eq!(expected_tags, opus_result.tags);
+        assert_eq!("00:00:01", opus_result.time().formatted);
+        assert_eq!("3kbps", opus_result.quality().formatted);
+        assert!(!opus_result.has_picture);
+        assert_eq!(6, opus_result.rawtags.len());
+        assert!(
+            opus_result
+                .rawtags
+                .contains(&("tracknumber".to_owned(), "6".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_metadata_valid_m4a() {
+        let expected_tags = AurTags {
+            artist: "Test Artist".to_owned(),
+            album: "Test Album".to_owned(),
+            title: "Test Title".to_owned(),
+            genre: "Test Genre".to_owned(),
+            t_num: 6,
+            disc: 0,
+            year: 2021,
+        };
+
+        let m4a_result = AurMetadata::new(&fixture!("info/test.m4a")).unwrap();
+
+        assert_eq!("m4a", m4a_result.filetype);
+        assert_eq!(expected_tags, m4a_result.tags);
+        assert_eq!("00:00:01", m4a_result.time().formatted);
+        assert_eq!("0kbps", m4a_result.quality().formatted);
+        assert!(!m4a_result.has_picture);
+        assert_eq!(
+            super::expected_tags("m4a").unwrap(),
+            m4a_result
+                .rawtags
+                .iter()
+                .map(|(k, _)| k.to_owned())
+                .collect::<HashSet<_>>()
+        );
+        assert!(
+            m4a_result
+                .rawtags
+                .contains(&("trkn".to_owned(), "6/0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_metadata_mp3_bad_stream() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file = tmp.path().join("01.tester.song.mp3");
+        std::fs::write(&file, [0u8; 1024]).unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("Song");
+        tag.write_to_path(&file, id3::Version::Id3v24).unwrap();
+
+        let result = AurMetadata::new(&file).unwrap();
+
+        assert_eq!("Song", result.tags.title);
+        assert_eq!(UNDEFINED, result.time().formatted);
+        assert_eq!(UNDEFINED, result.quality().formatted);
+        assert_eq!(None, result.encoding);
+    }
+
     #[test]
     fn test_metadata_missing_file() {
         assert!(matches!(
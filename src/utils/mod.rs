@@ -1,17 +1,29 @@
 #[macro_use]
 pub mod macros;
 
+pub mod audio_stats;
+pub mod authenticity;
 pub mod config;
+pub mod decoder;
 pub mod dir;
 pub mod external;
+pub mod formats;
 pub mod helpers;
+pub mod image;
 pub mod layout;
+pub mod lint_cache;
+pub mod loudness;
 pub mod metadata;
 pub mod mp3_encoder;
+pub mod mp3_stream;
+pub mod mp4_file;
+pub mod ogg_stream;
+pub mod profile;
 pub mod rename;
 pub mod renumber_file;
 pub mod retitler;
 pub mod string;
+pub mod sync_state;
 pub mod tag_maker;
 pub mod tag_validator;
 pub mod tagger;
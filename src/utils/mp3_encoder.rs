@@ -1,50 +1,103 @@
+use crate::utils::dir;
 use crate::utils::external;
 use crate::utils::metadata::AurMetadata;
+use crate::utils::profile::{Encoder, Profile};
+use crate::utils::rename;
+use crate::utils::sync_state::SyncState;
 use crate::utils::tagger::Tagger;
 use crate::utils::types::{GlobalOpts, Mp3dirOpts};
-use anyhow::{anyhow, ensure};
+use anyhow::{Context, anyhow, ensure};
 use camino::{Utf8Path, Utf8PathBuf};
 use colored::Colorize;
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
-use std::process::{Command, Stdio};
+use std::process::Command;
 
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub struct TranscodeAction {
     pub flac_src: Utf8PathBuf,
-    pub mp3_target: Utf8PathBuf,
+    pub target: Utf8PathBuf,
+    /// The target exists, but was made from a FLAC which has since changed.
+    pub stale: bool,
 }
 
 pub type CleanupAction = Utf8PathBuf;
 
+// A target whose FLAC has gone, which can become the target of a FLAC which needs one, because
+// it's the same audio. Normally that's because the FLAC was renamed or moved.
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+pub struct MoveAction {
+    pub orphan: Utf8PathBuf,
+    pub flac_src: Utf8PathBuf,
+    pub target: Utf8PathBuf,
+    pub fingerprint: String,
+}
+
+// A lossy hierarchy kept in step with flac/, like mp3/. `tree` is its directory under the root.
+pub struct SyncTarget {
+    pub tree: String,
+    pub cmds: TranscodeCmds,
+}
+
 pub struct TranscodeCmds {
-    flac: Utf8PathBuf,
-    lame: Utf8PathBuf,
+    pub profile: Profile,
+    encoder: Utf8PathBuf,
+    flac: Option<Utf8PathBuf>,
 }
 
-pub fn transcode_cmds() -> anyhow::Result<TranscodeCmds> {
+// LAME can't read FLACs, so it needs flac to decode for it. ffmpeg can.
+pub fn transcode_cmds(profile: Profile) -> anyhow::Result<TranscodeCmds> {
+    let flac = match profile.encoder {
+        Encoder::Lame => Some(external::find_binary("flac")?),
+        Encoder::Ffmpeg => None,
+    };
+
     Ok(TranscodeCmds {
-        lame: external::find_binary("lame")?,
-        flac: external::find_binary("flac")?,
+        encoder: external::find_binary(profile.encoder.binary())?,
+        flac,
+        profile,
     })
 }
 
+pub fn mp3_transcode_cmds(profile: Profile) -> anyhow::Result<TranscodeCmds> {
+    profile.ensure_makes("mp3")?;
+    transcode_cmds(profile)
+}
+
+// Targets we have no record of are assumed to be up to date, and recorded as such.
 pub fn make_transcode_list(
     flac_dir: &Utf8Path,
-    mp3_dir: &Utf8Path,
+    target_dir: &Utf8Path,
+    extension: &str,
     overwrite: bool,
+    state: &mut SyncState,
 ) -> anyhow::Result<Vec<TranscodeAction>> {
     let mut ret = Vec::new();
 
     for stem in file_stems(flac_dir, "flac")? {
-        let mp3_target = mp3_dir.join(format!("{}.mp3", stem));
+        let flac_src = flac_dir.join(format!("{}.flac", stem));
+        let target = target_dir.join(format!("{}.{}", stem, extension));
 
-        if overwrite || !mp3_target.exists() {
+        if overwrite || !target.exists() {
             ret.push(TranscodeAction {
-                flac_src: flac_dir.join(format!("{}.flac", stem)),
-                mp3_target,
+                flac_src,
+                target,
+                stale: false,
             });
+            continue;
+        }
+
+        let fingerprint = state.fingerprint(&flac_src)?;
+
+        if state.is_stale(&target, &fingerprint) {
+            ret.push(TranscodeAction {
+                flac_src,
+                target,
+                stale: true,
+            });
+        } else {
+            state.record(&target, fingerprint);
         }
     }
 
@@ -57,12 +110,19 @@ pub fn transcode_file(
     cmd_opts: &Mp3dirOpts,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
-    if action.mp3_target.exists() && !cmd_opts.force {
-        println!("  target exists ({})", action.mp3_target);
+    if action.target.exists() && !cmd_opts.force && !action.stale {
+        println!("  target exists ({})", action.target);
         return Ok(false);
     }
 
-    println!("  {}", action.flac_src.file_name().unwrap());
+    if action.stale {
+        println!(
+            "  {} (source changed)",
+            action.flac_src.file_name().unwrap()
+        );
+    } else {
+        println!("  {}", action.flac_src.file_name().unwrap());
+    }
 
     if opts.noop {
         return Ok(false);
@@ -70,38 +130,32 @@ pub fn transcode_file(
 
     let flac_info = AurMetadata::new(&action.flac_src)?;
 
-    let mut flac_decode = Command::new(&cmds.flac)
-        .arg("-dsc")
-        .arg(&action.flac_src)
-        .stdout(Stdio::piped())
-        .spawn()?;
-
-    let flac_stdout = flac_decode
-        .stdout
-        .take()
-        .ok_or_else(|| anyhow!("Failed to decode flac stdout"))?;
-
-    let mut lame_encode = Command::new(&cmds.lame)
-        .arg("-q1")
-        .arg("--vbr-new")
-        .arg("-V0")
-        .arg("--preset")
-        .arg(&cmd_opts.preset)
-        .arg("--add-id3v2")
-        .arg("--id3v2-only")
-        .arg("--silent")
-        .stdin(Stdio::from(flac_stdout))
-        .arg("-")
-        .arg(&action.mp3_target)
-        .spawn()?;
-
-    lame_encode.wait()?;
-    flac_decode.wait()?;
+    external::run_pipeline(&action.target, |tmp| match &cmds.flac {
+        Some(flac) => {
+            let mut flac_decode = Command::new(flac);
+            flac_decode.arg("-dsc").arg(&action.flac_src);
+
+            vec![
+                flac_decode,
+                cmds.profile.command(
+                    &cmds.encoder,
+                    &["--add-id3v2", "--id3v2-only"],
+                    Utf8Path::new("-"),
+                    tmp,
+                ),
+            ]
+        }
+        None => vec![
+            cmds.profile
+                .command(&cmds.encoder, &[], &action.flac_src, tmp),
+        ],
+    })
+    .context(format!("failed to encode {}", action.target))?;
 
     // Turns out slashes are separators in ID3 tags, so LAME will drop them if we pass tag
     // values which contain them. So now we tag as a separate stage.
 
-    let mp3_info = AurMetadata::new(&action.mp3_target)?;
+    let mp3_info = AurMetadata::new(&action.target)?;
     Tagger::new(&mp3_info)?.batch_tag(&flac_info.tags, !opts.verbose)
 }
 
@@ -122,15 +176,16 @@ fn file_stems(dir: &Utf8Path, suffix: &str) -> anyhow::Result<HashSet<String>> {
 
 pub fn make_clean_up_list(
     flac_dir: &Utf8Path,
-    mp3_dir: &Utf8Path,
+    target_dir: &Utf8Path,
+    extension: &str,
 ) -> anyhow::Result<Vec<CleanupAction>> {
     let mut ret = Vec::new();
 
-    for stem in file_stems(mp3_dir, "mp3")? {
-        let mp3_file = mp3_dir.join(format!("{}.mp3", stem));
+    for stem in file_stems(target_dir, extension)? {
+        let target_file = target_dir.join(format!("{}.{}", stem, extension));
         let flac_source = flac_dir.join(format!("{}.flac", stem));
         if !flac_source.exists() {
-            ret.push(mp3_file);
+            ret.push(target_file);
         }
     }
 
@@ -139,35 +194,47 @@ pub fn make_clean_up_list(
 
 pub fn sync_dir(
     flac_dir: &Utf8Path,
-    mp3_dir: &Utf8Path,
+    target_dir: &Utf8Path,
     cmds: &TranscodeCmds,
     cmd_opts: &Mp3dirOpts,
+    state: &mut SyncState,
     opts: &GlobalOpts,
 ) -> anyhow::Result<bool> {
     ensure!(
-        flac_dir != mp3_dir,
-        format!("FLAC and MP3 directories have the same path: {}", flac_dir)
+        flac_dir != target_dir,
+        format!(
+            "FLAC and target directories have the same path: {}",
+            flac_dir
+        )
     );
 
-    let list = make_transcode_list(flac_dir, mp3_dir, cmd_opts.force)?;
+    let extension = &cmds.profile.extension;
+    let list = make_transcode_list(flac_dir, target_dir, extension, cmd_opts.force, state)?;
 
     if !list.is_empty() {
-        println!("{} -> {}", flac_dir.to_string().bold(), mp3_dir);
-        if !mp3_dir.exists() && !opts.noop {
+        println!("{} -> {}", flac_dir.to_string().bold(), target_dir);
+        if !target_dir.exists() && !opts.noop {
             verbose!(opts, "  Creating target");
-            fs::create_dir_all(mp3_dir)?;
+            fs::create_dir_all(target_dir)?;
         }
 
         list.par_iter()
             .try_for_each(|t| transcode_file(t, cmds, cmd_opts, opts).map(|_| ()))?;
+
+        if !opts.noop {
+            for t in &list {
+                let fingerprint = state.fingerprint(&t.flac_src)?;
+                state.record(&t.target, fingerprint);
+            }
+        }
     }
 
-    if mp3_dir.exists() && mp3_dir.file_name().unwrap() != "tracks" {
+    if target_dir.exists() && target_dir.file_name().unwrap() != "tracks" {
         // it might not be there if we just no-oped, and we allow tracks/ to be different
-        let clean_up_list = make_clean_up_list(flac_dir, mp3_dir)?;
+        let clean_up_list = make_clean_up_list(flac_dir, target_dir, extension)?;
 
         if !clean_up_list.is_empty() {
-            println!("{}", mp3_dir.to_string().bold());
+            println!("{}", target_dir.to_string().bold());
         }
 
         for f in clean_up_list {
@@ -178,25 +245,170 @@ pub fn sync_dir(
     Ok(true)
 }
 
-pub fn clean_up_file(superfluous_mp3: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<bool> {
-    verbose!(opts, "  Removing {}", superfluous_mp3);
+pub fn clean_up_file(superfluous_file: &Utf8Path, opts: &GlobalOpts) -> anyhow::Result<bool> {
+    verbose!(opts, "  Removing {}", superfluous_file);
 
     if opts.noop {
         Ok(false)
     } else {
-        fs::remove_file(superfluous_mp3)?;
+        fs::remove_file(superfluous_file)?;
         Ok(true)
     }
 }
 
-pub fn mp3_dir_from(flac_dir: &Utf8Path, cmd_opts: &Mp3dirOpts) -> Utf8PathBuf {
-    let mut target_path_as_string = flac_dir.to_string().replace("/flac", "/mp3");
+// Every file in the tree which should have come from a FLAC which isn't there. Files we didn't
+// make ourselves, like MP3s with no FLAC, are included, but never match anything.
+pub fn find_orphans(
+    root: &Utf8Path,
+    tree: &str,
+    extension: &str,
+) -> anyhow::Result<Vec<Utf8PathBuf>> {
+    let tree_root = root.join(tree);
+
+    if !tree_root.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(
+        dir::expand_file_list(std::slice::from_ref(&tree_root), true)?
+            .into_iter()
+            .filter(|f| f.extension() == Some(extension))
+            .filter(|f| {
+                f.strip_prefix(&tree_root)
+                    .is_ok_and(|rel| !root.join("flac").join(rel).with_extension("flac").exists())
+            })
+            .collect(),
+    )
+}
+
+// Pairs FLACs which have no target with orphans made from the same audio. If more than one orphan
+// was, we go for the one with the same tags.
+pub fn make_move_list(
+    root: &Utf8Path,
+    tree: &str,
+    extension: &str,
+    flac_dirs: &BTreeSet<Utf8PathBuf>,
+    state: &mut SyncState,
+) -> anyhow::Result<Vec<MoveAction>> {
+    let mut orphans = find_orphans(root, tree, extension)?;
+    orphans.retain(|o| state.source_of(o).is_some());
+    let mut ret = Vec::new();
+
+    for flac_dir in flac_dirs {
+        if orphans.is_empty() {
+            break;
+        }
+
+        let target_dir = target_dir_from(flac_dir, root, tree, None)?;
+
+        for action in make_transcode_list(flac_dir, &target_dir, extension, false, state)? {
+            if action.stale {
+                continue;
+            }
+
+            let fingerprint = state.fingerprint(&action.flac_src)?;
 
-    if cmd_opts.suffix {
-        target_path_as_string.push_str(&format!("-{}", cmd_opts.preset));
+            if let Some(i) = matching_orphan(&orphans, &action.flac_src, &fingerprint, state)? {
+                ret.push(MoveAction {
+                    orphan: orphans.remove(i),
+                    flac_src: action.flac_src,
+                    target: action.target,
+                    fingerprint,
+                });
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+fn matching_orphan(
+    orphans: &[Utf8PathBuf],
+    flac_src: &Utf8Path,
+    fingerprint: &str,
+    state: &SyncState,
+) -> anyhow::Result<Option<usize>> {
+    let candidates: Vec<usize> = orphans
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| state.source_of(o) == Some(fingerprint))
+        .map(|(i, _)| i)
+        .collect();
+
+    if candidates.len() < 2 {
+        return Ok(candidates.first().copied());
+    }
+
+    let flac_tags = AurMetadata::new(flac_src)?.tags;
+
+    for i in &candidates {
+        if AurMetadata::new(&orphans[*i]).is_ok_and(|info| info.tags == flac_tags) {
+            return Ok(Some(*i));
+        }
+    }
+
+    Ok(candidates.first().copied())
+}
+
+// The tags are copied over as they are when we transcode, because they may have changed along
+// with the name. Any directories the move empties are removed.
+pub fn move_file(
+    action: &MoveAction,
+    tree_root: &Utf8Path,
+    state: &mut SyncState,
+    opts: &GlobalOpts,
+) -> anyhow::Result<bool> {
+    println!(
+        "{} -> {}",
+        action.orphan.parent().unwrap().to_string().bold(),
+        action.target.parent().unwrap()
+    );
+
+    rename::rename((action.orphan.clone(), action.target.clone()), opts.noop)?;
+
+    if opts.noop {
+        return Ok(false);
+    }
+
+    let flac_info = AurMetadata::new(&action.flac_src)?;
+    let target_info = AurMetadata::new(&action.target)?;
+    Tagger::new(&target_info)?.batch_tag(&flac_info.tags, !opts.verbose)?;
+    state.record(&action.target, action.fingerprint.clone());
+
+    let mut dir = action.orphan.parent();
+
+    while let Some(d) = dir {
+        if d == tree_root || !d.starts_with(tree_root) || d.read_dir_utf8()?.next().is_some() {
+            break;
+        }
+
+        verbose!(opts, "  Removing {}", d);
+        fs::remove_dir(d)?;
+        dir = d.parent();
+    }
+
+    Ok(true)
+}
+
+// The equivalent of flac_dir in the given tree. flac_dir must be under root/flac.
+pub fn target_dir_from(
+    flac_dir: &Utf8Path,
+    root: &Utf8Path,
+    tree: &str,
+    suffix: Option<&str>,
+) -> anyhow::Result<Utf8PathBuf> {
+    let flac_root = root.join("flac");
+    let relative_dir = flac_dir
+        .strip_prefix(&flac_root)
+        .map_err(|_| anyhow!("{} is not under {}", flac_dir, flac_root))?;
+
+    let mut ret = root.join(tree).join(relative_dir).into_string();
+
+    if let Some(suffix) = suffix {
+        ret.push_str(&format!("-{}", suffix));
     }
 
-    Utf8PathBuf::from(target_path_as_string)
+    Ok(Utf8PathBuf::from(ret))
 }
 
 #[cfg(test)]
@@ -208,10 +420,7 @@ mod test {
 
     #[test]
     fn test_transcode_file() {
-        let cmds = TranscodeCmds {
-            lame: external::find_binary("lame").unwrap(),
-            flac: external::find_binary("flac").unwrap(),
-        };
+        let cmds = mp3_transcode_cmds(Profile::builtin("extreme").unwrap()).unwrap();
 
         let file_name = "02.band.song_2.flac";
         let tmp = Utf8TempDir::new().unwrap();
@@ -225,11 +434,12 @@ mod test {
 
         let action = TranscodeAction {
             flac_src: file_under_test.clone(),
-            mp3_target: mp3_file.clone(),
+            target: mp3_file.clone(),
+            stale: false,
         };
 
         let cmd_opts = Mp3dirOpts {
-            preset: "extreme".to_owned(),
+            profile: "extreme".to_owned(),
             force: false,
             recurse: false,
             root: Utf8PathBuf::from("/storage"),
@@ -256,6 +466,7 @@ mod test {
             make_clean_up_list(
                 &fixture!("commands/syncflac/flac/eps/band.flac_and_mp3_unequal"),
                 &fixture!("commands/syncflac/mp3/eps/band.flac_and_mp3_unequal"),
+                "mp3",
             )
             .unwrap()
         );
@@ -265,6 +476,7 @@ mod test {
             make_clean_up_list(
                 &fixture!("commands/syncflac/flac/albums/abc/already.synced"),
                 &fixture!("commands/syncflac/mp3/albums/abc/already.synced"),
+                "mp3",
             )
             .unwrap()
         );
@@ -277,7 +489,9 @@ mod test {
             make_transcode_list(
                 &fixture!("commands/syncflac/flac/albums/abc/already.synced"),
                 &fixture!("commands/syncflac/mp3/albums/abc/already.synced"),
+                "mp3",
                 false,
+                &mut SyncState::default(),
             )
             .unwrap()
         );
@@ -288,55 +502,228 @@ mod test {
                     flac_src: fixture!(
                         "commands/syncflac/flac/albums/tuv/tester.flac_album/01.tester.song_1.flac"
                     ),
-                    mp3_target: fixture!(
+                    target: fixture!(
                         "commands/syncflac/mp3/albums/tuv/tester.flac_album/01.tester.song_1.mp3"
                     ),
+                    stale: false,
                 },
                 TranscodeAction {
                     flac_src: fixture!(
                         "commands/syncflac/flac/albums/tuv/tester.flac_album/02.tester.song_2.flac"
                     ),
-                    mp3_target: fixture!(
+                    target: fixture!(
                         "commands/syncflac/mp3/albums/tuv/tester.flac_album/02.tester.song_2.mp3"
                     ),
+                    stale: false,
                 }
             ],
             make_transcode_list(
                 &fixture!("commands/syncflac/flac/albums/tuv/tester.flac_album"),
                 &fixture!("commands/syncflac/mp3/albums/tuv/tester.flac_album"),
+                "mp3",
+                false,
+                &mut SyncState::default(),
+            )
+            .unwrap()
+        );
+
+        assert_eq!(
+            vec![TranscodeAction {
+                flac_src: fixture!(
+                    "commands/syncflac/flac/albums/abc/already.synced/01.already.synced.flac"
+                ),
+                target: fixture!(
+                    "commands/syncflac/mp3/albums/abc/already.synced/01.already.synced.opus"
+                ),
+                stale: false,
+            }],
+            make_transcode_list(
+                &fixture!("commands/syncflac/flac/albums/abc/already.synced"),
+                &fixture!("commands/syncflac/mp3/albums/abc/already.synced"),
+                "opus",
                 false,
+                &mut SyncState::default(),
             )
             .unwrap()
         );
     }
+
+    #[test]
+    fn test_transcode_list_stale() {
+        let flac_dir = fixture!("commands/syncflac/flac/albums/abc/already.synced");
+        let mp3_dir = fixture!("commands/syncflac/mp3/albums/abc/already.synced");
+        let target = mp3_dir.join("01.already.synced.mp3");
+        let mut state = SyncState::default();
+
+        assert!(
+            make_transcode_list(&flac_dir, &mp3_dir, "mp3", false, &mut state)
+                .unwrap()
+                .is_empty()
+        );
+
+        state.record(&target, "made from something else".to_owned());
+
+        assert_eq!(
+            vec![TranscodeAction {
+                flac_src: flac_dir.join("01.already.synced.flac"),
+                target,
+                stale: true,
+            }],
+            make_transcode_list(&flac_dir, &mp3_dir, "mp3", false, &mut state).unwrap()
+        );
+    }
+
+    // A FLAC in flac/eps/band.new whose MP3 is still in mp3/eps/band.old.
+    fn moved_album() -> Utf8TempDir {
+        let tmp = Utf8TempDir::new().unwrap();
+        let album = "eps/band.flac_and_mp3_unequal";
+        tmp.copy_from(
+            fixture!("commands/syncflac"),
+            &[
+                &format!("flac/{album}/01.band.song_1.flac"),
+                &format!("mp3/{album}/01.band.song_1.mp3"),
+                &format!("mp3/{album}/03.band.song_3.mp3"),
+            ],
+        )
+        .unwrap();
+        fs::rename(
+            tmp.path().join("flac").join(album),
+            tmp.path().join("flac/eps/band.new"),
+        )
+        .unwrap();
+        fs::rename(
+            tmp.path().join("mp3").join(album),
+            tmp.path().join("mp3/eps/band.old"),
+        )
+        .unwrap();
+        tmp
+    }
+
     #[test]
-    fn test_mp3_dir_from() {
+    fn test_find_orphans() {
+        let tmp = moved_album();
+        let root = tmp.path();
+
+        assert_eq!(
+            vec![
+                root.join("mp3/eps/band.old/01.band.song_1.mp3"),
+                root.join("mp3/eps/band.old/03.band.song_3.mp3"),
+            ],
+            find_orphans(root, "mp3", "mp3").unwrap()
+        );
+
+        assert!(find_orphans(root, "opus", "opus").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_move_file() {
+        let tmp = moved_album();
+        let root = tmp.path();
+        let flac_src = root.join("flac/eps/band.new/01.band.song_1.flac");
+        let orphan = root.join("mp3/eps/band.old/01.band.song_1.mp3");
+        let target = root.join("mp3/eps/band.new/01.band.song_1.mp3");
+        let flac_dirs = BTreeSet::from([root.join("flac/eps/band.new")]);
+        let mut state = SyncState::default();
+        let fingerprint = state.fingerprint(&flac_src).unwrap();
+
+        assert!(
+            make_move_list(root, "mp3", "mp3", &flac_dirs, &mut state)
+                .unwrap()
+                .is_empty()
+        );
+
+        state.record(&orphan, fingerprint.clone());
+        let moves = make_move_list(root, "mp3", "mp3", &flac_dirs, &mut state).unwrap();
+
+        assert_eq!(
+            vec![MoveAction {
+                orphan: orphan.clone(),
+                flac_src: flac_src.clone(),
+                target: target.clone(),
+                fingerprint: fingerprint.clone(),
+            }],
+            moves
+        );
+
+        assert!(
+            move_file(
+                &moves[0],
+                &root.join("mp3"),
+                &mut state,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+        assert!(!orphan.exists());
+        assert!(target.exists());
+        assert!(root.join("mp3/eps/band.old/03.band.song_3.mp3").exists());
+
+        let orphan = root.join("mp3/eps/band.old/03.band.song_3.mp3");
+        state.record(&orphan, fingerprint.clone());
+        fs::remove_file(&target).unwrap();
+        let moves = make_move_list(root, "mp3", "mp3", &flac_dirs, &mut state).unwrap();
+        assert_eq!(orphan, moves[0].orphan);
+        assert!(
+            move_file(
+                &moves[0],
+                &root.join("mp3"),
+                &mut state,
+                &GlobalOpts::default()
+            )
+            .unwrap()
+        );
+        assert!(!root.join("mp3/eps/band.old").exists());
+        assert!(root.join("mp3/eps").exists());
+        assert_eq!(Some(fingerprint.as_str()), state.source_of(&target));
+        assert_eq!(
+            AurMetadata::new(&flac_src).unwrap().tags,
+            AurMetadata::new(&target).unwrap().tags
+        );
+    }
+
+    #[test]
+    fn test_transcode_cmds() {
+        assert_eq!(
+            "profile 'cdq' makes flac files, not mp3",
+            mp3_transcode_cmds(Profile::builtin("cdq").unwrap())
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_target_dir_from() {
+        let root = Utf8Path::new("/storage");
+
         assert_eq!(
             Utf8PathBuf::from("/storage/mp3/tracks"),
-            mp3_dir_from(
-                "/storage/flac/tracks".into(),
-                &Mp3dirOpts {
-                    preset: "standard".to_owned(),
-                    force: false,
-                    recurse: false,
-                    root: "/storage".into(),
-                    suffix: false
-                }
-            ),
+            target_dir_from("/storage/flac/tracks".into(), root, "mp3", None).unwrap(),
         );
 
         assert_eq!(
             Utf8PathBuf::from("/storage/mp3/eps/band.ep-standard"),
-            mp3_dir_from(
+            target_dir_from(
                 "/storage/flac/eps/band.ep".into(),
-                &Mp3dirOpts {
-                    preset: "standard".to_owned(),
-                    force: false,
-                    recurse: false,
-                    root: "/storage".into(),
-                    suffix: true
-                }
-            ),
+                root,
+                "mp3",
+                Some("standard")
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(
+            Utf8PathBuf::from("/storage/opus/albums/abc/band.flac_album"),
+            target_dir_from(
+                "/storage/flac/albums/abc/band.flac_album".into(),
+                root,
+                "opus",
+                None
+            )
+            .unwrap(),
         );
+
+        assert!(target_dir_from("/storage/mp3/eps/band.ep".into(), root, "mp3", None).is_err());
+        assert!(target_dir_from("/backup/flac/eps/band.ep".into(), root, "mp3", None).is_err());
     }
 }
@@ -0,0 +1,407 @@
+use anyhow::{Context, ensure};
+use camino::Utf8Path;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+// Enough of the start of the audio to find the first frame, even behind junk, and to hold the
+// Xing or VBRI frame which follows it.
+const PROBE_SIZE: u64 = 64 * 1024;
+const ID3V1_SIZE: u64 = 128;
+
+const BITRATES_V1_L1: [u32; 15] = [
+    0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+];
+const BITRATES_V1_L2: [u32; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+];
+const BITRATES_V1_L3: [u32; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const BITRATES_V2_L1: [u32; 15] = [
+    0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+];
+const BITRATES_V2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateMode {
+    Cbr,
+    Abr,
+    Vbr,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FrameHeader {
+    mpeg1: bool,
+    layer: u8,
+    bitrate: u32,
+    sample_rate: u32,
+    mono: bool,
+    length: u64,
+}
+
+/// What we can learn about an MP3 without decoding it: the first frame header, and whatever a
+/// Xing, Info, or VBRI frame, and any LAME extension to it, tell us about the rest.
+#[derive(Debug)]
+pub struct Mp3Stream {
+    pub sample_rate: u32,
+    pub frames: u64,
+    pub audio_bytes: u64,
+    pub mode: BitrateMode,
+    pub encoder: Option<String>,
+    pub preset: Option<String>,
+    pub delay: u32,
+    pub padding: u32,
+    header_bitrate: u32,
+    samples_per_frame: u32,
+}
+
+impl Mp3Stream {
+    pub fn read_from_path(path: &Utf8Path) -> anyhow::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let audio_start = id3v2_size(&mut file)?;
+        let audio_end = file_len - id3v1_size(&mut file, file_len)?;
+
+        let mut probe = Vec::new();
+        file.seek(SeekFrom::Start(audio_start))?;
+        (&mut file).take(PROBE_SIZE).read_to_end(&mut probe)?;
+
+        let (offset, header) =
+            first_frame(&probe).context(format!("no MPEG audio frames in {}", path))?;
+
+        let audio_start = audio_start + offset as u64;
+        ensure!(audio_end > audio_start, "no audio data in {}", path);
+
+        let samples_per_frame = match (header.layer, header.mpeg1) {
+            (1, _) => 384,
+            (2, _) | (3, true) => 1152,
+            (3, false) => 576,
+            _ => unreachable!(),
+        };
+
+        let mut ret = Self {
+            sample_rate: header.sample_rate,
+            frames: 0,
+            audio_bytes: audio_end - audio_start,
+            mode: BitrateMode::Cbr,
+            encoder: None,
+            preset: None,
+            delay: 0,
+            padding: 0,
+            header_bitrate: header.bitrate,
+            samples_per_frame,
+        };
+
+        let frame = &probe[offset..];
+
+        if !ret.read_xing(frame, &header) {
+            ret.read_vbri(frame);
+        }
+
+        if ret.frames == 0 {
+            // No frame count, so this is most likely plain CBR, and every frame is the same
+            // length, give or take a padding byte.
+            let frame_bytes = samples_per_frame as f64 / 8.0 * header.bitrate as f64 * 1000.0
+                / header.sample_rate as f64;
+            ret.frames = (ret.audio_bytes as f64 / frame_bytes).round() as u64;
+        }
+
+        Ok(ret)
+    }
+
+    /// Playable samples, per channel. Encoder delay and padding are silence added by the encoder,
+    /// so they don't count.
+    pub fn samples(&self) -> u64 {
+        (self.frames * self.samples_per_frame as u64)
+            .saturating_sub(self.delay as u64 + self.padding as u64)
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.samples() / self.sample_rate as u64
+    }
+
+    /// In kbps. For CBR this is what every frame says. Otherwise it's the true average, from the
+    /// size of the audio alone, not tags or artwork.
+    pub fn bitrate(&self) -> u32 {
+        if self.mode == BitrateMode::Cbr {
+            return self.header_bitrate;
+        }
+
+        match self.samples() {
+            0 => self.header_bitrate,
+            samples => {
+                (self.audio_bytes as f64 * 8.0 * self.sample_rate as f64 / samples as f64 / 1000.0)
+                    .round() as u32
+            }
+        }
+    }
+
+    /// A summary, like "VBR V2, LAME3.100".
+    pub fn encoding(&self) -> String {
+        let mode = match self.mode {
+            BitrateMode::Cbr => "CBR",
+            BitrateMode::Abr => "ABR",
+            BitrateMode::Vbr => "VBR",
+        };
+
+        let mut ret = match &self.preset {
+            Some(preset) => format!("{} {}", mode, preset),
+            None => mode.to_owned(),
+        };
+
+        if let Some(encoder) = &self.encoder {
+            ret.push_str(&format!(", {}", encoder));
+        }
+
+        ret
+    }
+
+    // The Xing header sits where the first frame's audio would go, after the side information.
+    // "Info" is the same thing, written by LAME for CBR files.
+    fn read_xing(&mut self, frame: &[u8], header: &FrameHeader) -> bool {
+        let side_info = match (header.mpeg1, header.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        };
+
+        let mut pos = 4 + side_info;
+
+        let tag = match frame.get(pos..pos + 4) {
+            Some(tag) if tag == b"Xing" || tag == b"Info" => tag.to_owned(),
+            _ => return false,
+        };
+
+        let flags = match be_u32(frame, pos + 4) {
+            Some(flags) => flags,
+            None => return false,
+        };
+
+        pos += 8;
+        self.mode = if tag == b"Xing" {
+            BitrateMode::Vbr
+        } else {
+            BitrateMode::Cbr
+        };
+
+        if flags & 0x1 != 0 {
+            self.frames = be_u32(frame, pos).unwrap_or(0) as u64;
+            pos += 4;
+        }
+
+        if flags & 0x2 != 0 {
+            if let Some(bytes) = be_u32(frame, pos) {
+                self.audio_bytes = bytes as u64;
+            }
+            pos += 4;
+        }
+
+        if flags & 0x4 != 0 {
+            pos += 100;
+        }
+
+        let quality = if flags & 0x8 != 0 {
+            pos += 4;
+            be_u32(frame, pos - 4)
+        } else {
+            None
+        };
+
+        self.read_lame(frame.get(pos..).unwrap_or_default(), quality);
+        true
+    }
+
+    // LAME, and FFmpeg, which borrowed its format, extend the Xing header with the encoder
+    // version, how it was driven, and how much silence it added at each end.
+    fn read_lame(&mut self, ext: &[u8], xing_quality: Option<u32>) {
+        let encoder = match ext.get(..9) {
+            Some(bytes) if bytes.starts_with(b"L") => String::from_utf8_lossy(bytes)
+                .trim_end_matches(['\0', ' '])
+                .to_owned(),
+            _ => return,
+        };
+
+        if encoder.is_empty() || ext.len() < 36 {
+            return;
+        }
+
+        self.mode = match ext[9] & 0x0f {
+            1 | 8 => BitrateMode::Cbr,
+            2 | 9 => BitrateMode::Abr,
+            3..=6 => BitrateMode::Vbr,
+            _ => self.mode,
+        };
+
+        self.delay = ((ext[21] as u32) << 4) | ((ext[22] as u32) >> 4);
+        self.padding = (((ext[22] & 0x0f) as u32) << 8) | ext[23] as u32;
+
+        let preset = u16::from_be_bytes([ext[26], ext[27]]) & 0x07ff;
+
+        self.preset = match preset {
+            8..=320 if self.mode == BitrateMode::Abr => Some(format!("{}kbps", preset)),
+            410..=500 if preset.is_multiple_of(10) => Some(format!("V{}", (500 - preset) / 10)),
+            1000 => Some("r3mix".to_owned()),
+            1001 | 1004 => Some("standard".to_owned()),
+            1002 | 1005 => Some("extreme".to_owned()),
+            1003 => Some("insane".to_owned()),
+            1006 | 1007 => Some("medium".to_owned()),
+            // Plain -V doesn't set a preset, but LAME stores 100 - 10 * V - q as the quality.
+            _ if self.mode == BitrateMode::Vbr && encoder.starts_with("LAME") => xing_quality
+                .filter(|q| *q <= 100)
+                .map(|q| format!("V{}", (100 - q) / 10)),
+            _ => None,
+        };
+
+        self.encoder = Some(encoder);
+    }
+
+    // Fraunhofer's encoder puts its VBRI header at a fixed offset, whatever the frame type.
+    fn read_vbri(&mut self, frame: &[u8]) {
+        if frame.get(36..40) != Some(b"VBRI".as_slice()) {
+            return;
+        }
+
+        if let (Some(bytes), Some(frames)) = (be_u32(frame, 46), be_u32(frame, 50)) {
+            self.mode = BitrateMode::Vbr;
+            self.audio_bytes = bytes as u64;
+            self.frames = frames as u64;
+            self.delay = u16::from_be_bytes([frame[42], frame[43]]) as u32;
+            self.encoder = Some("Fraunhofer".to_owned());
+        }
+    }
+}
+
+fn id3v2_size(file: &mut File) -> anyhow::Result<u64> {
+    let mut header = [0u8; 10];
+    file.seek(SeekFrom::Start(0))?;
+
+    if file.read_exact(&mut header).is_err() || &header[0..3] != b"ID3" {
+        return Ok(0);
+    }
+
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 7) | (*b & 0x7f) as u64);
+
+    // A footer is a copy of the header, at the end of the tag.
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    Ok(10 + size + footer)
+}
+
+fn id3v1_size(file: &mut File, file_len: u64) -> anyhow::Result<u64> {
+    if file_len < ID3V1_SIZE {
+        return Ok(0);
+    }
+
+    let mut tag = [0u8; 3];
+    file.seek(SeekFrom::Start(file_len - ID3V1_SIZE))?;
+    file.read_exact(&mut tag)?;
+
+    Ok(if &tag == b"TAG" { ID3V1_SIZE } else { 0 })
+}
+
+// A frame sync can turn up by chance in junk, so a header only counts if another follows it, or
+// if it's the last thing we have.
+fn first_frame(data: &[u8]) -> Option<(usize, FrameHeader)> {
+    (0..data.len().saturating_sub(4)).find_map(|i| {
+        let header = parse_header(&data[i..])?;
+        let next = i + header.length as usize;
+
+        if next + 4 > data.len() || parse_header(&data[next..]).is_some() {
+            Some((i, header))
+        } else {
+            None
+        }
+    })
+}
+
+fn parse_header(data: &[u8]) -> Option<FrameHeader> {
+    let h = data.get(..4)?;
+
+    if h[0] != 0xff || h[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+
+    let version = (h[1] >> 3) & 0x03;
+    let layer = match (h[1] >> 1) & 0x03 {
+        3 => 1,
+        2 => 2,
+        1 => 3,
+        _ => return None,
+    };
+
+    let bitrate_index = (h[2] >> 4) as usize;
+    let rate_index = ((h[2] >> 2) & 0x03) as usize;
+
+    if version == 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+
+    let mpeg1 = version == 3;
+    let bitrate = match (mpeg1, layer) {
+        (true, 1) => BITRATES_V1_L1,
+        (true, 2) => BITRATES_V1_L2,
+        (true, _) => BITRATES_V1_L3,
+        (false, 1) => BITRATES_V2_L1,
+        (false, _) => BITRATES_V2_L23,
+    }[bitrate_index];
+
+    let sample_rate = match version {
+        3 => SAMPLE_RATES[rate_index],
+        2 => SAMPLE_RATES[rate_index] / 2,
+        _ => SAMPLE_RATES[rate_index] / 4,
+    };
+
+    let padding = ((h[2] >> 1) & 0x01) as u64;
+    let length = match (layer, mpeg1) {
+        (1, _) => (12 * bitrate as u64 * 1000 / sample_rate as u64 + padding) * 4,
+        (3, false) => 72 * bitrate as u64 * 1000 / sample_rate as u64 + padding,
+        _ => 144 * bitrate as u64 * 1000 / sample_rate as u64 + padding,
+    };
+
+    Some(FrameHeader {
+        mpeg1,
+        layer,
+        bitrate,
+        sample_rate,
+        mono: h[3] >> 6 == 3,
+        length,
+    })
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_read_lame_vbr() {
+        let stream =
+            Mp3Stream::read_from_path(&fixture!("commands/tags/01.test_artist.test_track.mp3"))
+                .unwrap();
+
+        assert_eq!(BitrateMode::Vbr, stream.mode);
+        assert_eq!(Some("LAME3.100".to_owned()), stream.encoder);
+        assert_eq!(576, stream.delay);
+    }
+
+    #[test]
+    fn test_read_info_cbr() {
+        let stream = Mp3Stream::read_from_path(&fixture!("info/test.mp3")).unwrap();
+
+        assert_eq!(BitrateMode::Cbr, stream.mode);
+        assert_eq!(64, stream.bitrate());
+        assert_eq!(0, stream.duration());
+    }
+
+    #[test]
+    fn test_read_not_an_mp3() {
+        assert!(Mp3Stream::read_from_path(&fixture!("info/bad_file.mp3")).is_err());
+    }
+}
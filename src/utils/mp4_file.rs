@@ -0,0 +1,767 @@
+use anyhow::{Context, anyhow, ensure};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+// Atoms which contain only other atoms, and which we need to walk through to find the tags, the
+// audio description, or the chunk offsets.
+const CONTAINERS: [&[u8; 4]; 8] = [
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"meta", b"ilst",
+];
+
+const DATA_TYPE_BINARY: u32 = 0;
+const DATA_TYPE_UTF8: u32 = 1;
+const FREEFORM: &[u8; 4] = b"----";
+const PICTURE_KEY: &[u8; 4] = b"covr";
+const TRACK_KEY: &[u8; 4] = b"trkn";
+const DISC_KEY: &[u8; 4] = b"disk";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mp4Codec {
+    Aac,
+    Alac,
+    Other(String),
+}
+
+#[derive(Debug, Clone)]
+struct Atom {
+    kind: [u8; 4],
+    prefix: Vec<u8>,
+    payload: Vec<u8>,
+    children: Option<Vec<Atom>>,
+}
+
+/// The `moov` atom of an MPEG-4 audio file. That's where the iTunes-style tags live, along with
+/// the duration and a description of the audio stream. We only hold `moov` in memory, and splice
+/// it back into the file on save. `audio_bytes` is the size of the `mdat` payloads, which is what
+/// a bitrate should be worked out from: the rest of the file is tags and artwork.
+#[derive(Debug)]
+pub struct Mp4File {
+    path: Utf8PathBuf,
+    moov: Atom,
+    moov_offset: u64,
+    moov_size: u64,
+    pub codec: Mp4Codec,
+    pub channels: u16,
+    pub bit_depth: u8,
+    pub sample_rate: u32,
+    pub duration: u64,
+    pub audio_bytes: u64,
+}
+
+impl Mp4File {
+    pub fn read_from_path(path: &Utf8Path) -> anyhow::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut offset = 0;
+        let mut moov = None;
+        let mut audio_bytes = 0;
+
+        while offset + 8 <= file_len {
+            file.seek(SeekFrom::Start(offset))?;
+            let mut header = [0u8; 8];
+            file.read_exact(&mut header)?;
+            let kind: [u8; 4] = header[4..8].try_into()?;
+
+            let (header_len, size) = match u32::from_be_bytes(header[0..4].try_into()?) {
+                0 => (8, file_len - offset),
+                1 => {
+                    let mut large = [0u8; 8];
+                    file.read_exact(&mut large)?;
+                    (16, u64::from_be_bytes(large))
+                }
+                size => (8, size as u64),
+            };
+
+            ensure!(size >= header_len, "invalid atom size in {}", path);
+
+            if &kind == b"mdat" {
+                audio_bytes += size.min(file_len - offset) - header_len;
+            } else if &kind == b"moov" && moov.is_none() {
+                // Don't trust a size we'd have to allocate before finding out it's wrong.
+                ensure!(
+                    size <= file_len - offset,
+                    "moov atom runs past the end of {}",
+                    path
+                );
+                let mut data = vec![0u8; size as usize];
+                file.seek(SeekFrom::Start(offset))?;
+                file.read_exact(&mut data)?;
+                moov = Some((offset, size, data));
+            }
+
+            offset = offset.saturating_add(size);
+        }
+
+        let (moov_offset, moov_size, data) = moov.context(format!("no moov atom in {}", path))?;
+        let moov = parse_atoms(&data, false)?
+            .pop()
+            .context(format!("unreadable moov atom in {}", path))?;
+
+        let mut ret = Self {
+            path: path.to_path_buf(),
+            moov,
+            moov_offset,
+            moov_size,
+            codec: Mp4Codec::Other("unknown".to_owned()),
+            channels: 0,
+            bit_depth: 0,
+            sample_rate: 0,
+            duration: 0,
+            audio_bytes,
+        };
+
+        ret.duration = ret.read_duration()?;
+        ret.read_audio_description()?;
+        Ok(ret)
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        self.codec == Mp4Codec::Alac
+    }
+
+    pub fn get_text(&self, key: &[u8; 4]) -> Option<String> {
+        let item = self.items()?.iter().find(|a| &a.kind == key)?;
+        let (data_type, value) = item_data(item)?;
+
+        if data_type == DATA_TYPE_UTF8 {
+            Some(String::from_utf8_lossy(value).into_owned())
+        } else {
+            None
+        }
+    }
+
+    pub fn set_text(&mut self, key: &[u8; 4], value: &str) {
+        self.set_item(key, DATA_TYPE_UTF8, value.as_bytes().to_vec());
+    }
+
+    pub fn track_number(&self) -> Option<u32> {
+        self.numbered_item(TRACK_KEY)
+    }
+
+    pub fn set_track_number(&mut self, number: u16) {
+        self.set_numbered_item(TRACK_KEY, number, &[0, 0]);
+    }
+
+    pub fn disc_number(&self) -> Option<u32> {
+        self.numbered_item(DISC_KEY)
+    }
+
+    // Unlike trkn, disk has no trailing padding.
+    pub fn set_disc_number(&mut self, number: u16) {
+        self.set_numbered_item(DISC_KEY, number, &[]);
+    }
+
+    fn numbered_item(&self, key: &[u8; 4]) -> Option<u32> {
+        let item = self.items()?.iter().find(|a| &a.kind == key)?;
+        let (_, value) = item_data(item)?;
+        value
+            .get(2..4)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as u32)
+    }
+
+    // trkn and disk are 'n of total', and we keep whatever total was there before.
+    fn set_numbered_item(&mut self, key: &[u8; 4], number: u16, padding: &[u8]) {
+        let total = self
+            .items()
+            .and_then(|items| items.iter().find(|a| &a.kind == key))
+            .and_then(item_data)
+            .and_then(|(_, v)| v.get(4..6).map(|b| [b[0], b[1]]))
+            .unwrap_or([0, 0]);
+
+        let mut value = vec![0, 0];
+        value.extend_from_slice(&number.to_be_bytes());
+        value.extend_from_slice(&total);
+        value.extend_from_slice(padding);
+        self.set_item(key, DATA_TYPE_BINARY, value);
+    }
+
+    pub fn raw_tags(&self) -> Vec<(String, String)> {
+        match self.items() {
+            Some(items) => items
+                .iter()
+                .map(|item| (item_name(item), item_value(item)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Removes the item with the given name, as returned by `raw_tags()`. Case is not significant.
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.items_mut() {
+            Some(items) => {
+                let before = items.len();
+                items.retain(|item| !item_name(item).eq_ignore_ascii_case(name));
+                items.len() != before
+            }
+            None => false,
+        }
+    }
+
+    pub fn has_picture(&self) -> bool {
+        self.items()
+            .is_some_and(|items| items.iter().any(|a| &a.kind == PICTURE_KEY))
+    }
+
+    pub fn remove_pictures(&mut self) -> bool {
+        match self.items_mut() {
+            Some(items) => {
+                let before = items.len();
+                items.retain(|a| &a.kind != PICTURE_KEY);
+                items.len() != before
+            }
+            None => false,
+        }
+    }
+
+    // Changing the size of moov moves everything after it. If the audio data comes after moov,
+    // every chunk offset in every track has to move by the same amount.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let dir = self
+            .path
+            .parent()
+            .context(format!("cannot get directory of {}", self.path))?;
+
+        let mut original = File::open(&self.path)?;
+        let mut moov = self.moov.clone();
+        let delta = moov.size() as i64 - self.moov_size as i64;
+
+        if delta != 0 {
+            shift_chunk_offsets(&mut moov, self.moov_offset, delta)?;
+        }
+
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        io::copy(&mut (&original).take(self.moov_offset), &mut tmp)?;
+        tmp.write_all(&moov.to_bytes())?;
+        original.seek(SeekFrom::Start(self.moov_offset + self.moov_size))?;
+        io::copy(&mut original, &mut tmp)?;
+        tmp.flush()?;
+        fs::set_permissions(tmp.path(), fs::metadata(&self.path)?.permissions())?;
+        tmp.persist(&self.path)?;
+        Ok(())
+    }
+
+    fn items(&self) -> Option<&Vec<Atom>> {
+        self.moov
+            .child(b"udta")?
+            .child(b"meta")?
+            .child(b"ilst")?
+            .children
+            .as_ref()
+    }
+
+    fn items_mut(&mut self) -> Option<&mut Vec<Atom>> {
+        self.moov
+            .child_mut(b"udta")?
+            .child_mut(b"meta")?
+            .child_mut(b"ilst")?
+            .children
+            .as_mut()
+    }
+
+    // Files straight out of an encoder may not have any of udta, meta, or ilst, so we make them
+    // as we need them.
+    fn ilst_mut(&mut self) -> &mut Vec<Atom> {
+        let udta = self.moov.child_or_insert(b"udta", Vec::new());
+        let meta = udta.child_or_insert(b"meta", vec![0, 0, 0, 0]);
+
+        if meta.child(b"hdlr").is_none() {
+            let mut hdlr = vec![0u8; 8];
+            hdlr.extend_from_slice(b"mdirappl");
+            hdlr.extend_from_slice(&[0u8; 9]);
+            meta.children
+                .get_or_insert_with(Vec::new)
+                .insert(0, Atom::leaf(b"hdlr", hdlr));
+        }
+
+        meta.child_or_insert(b"ilst", Vec::new())
+            .children
+            .get_or_insert_with(Vec::new)
+    }
+
+    fn set_item(&mut self, key: &[u8; 4], data_type: u32, value: Vec<u8>) {
+        let mut payload = data_type.to_be_bytes().to_vec();
+        payload.extend_from_slice(&[0, 0, 0, 0]);
+        payload.extend(value);
+
+        let item = Atom {
+            kind: *key,
+            prefix: Vec::new(),
+            payload: Vec::new(),
+            children: Some(vec![Atom::leaf(b"data", payload)]),
+        };
+
+        let items = self.ilst_mut();
+
+        match items.iter_mut().find(|a| &a.kind == key) {
+            Some(existing) => *existing = item,
+            None => items.push(item),
+        }
+    }
+
+    fn read_duration(&self) -> anyhow::Result<u64> {
+        let mvhd = &self
+            .moov
+            .child(b"mvhd")
+            .context(format!("no mvhd atom in {}", self.path))?
+            .payload;
+
+        let (timescale, duration) = if mvhd.first() == Some(&1) {
+            (be_u32(mvhd, 20)? as u64, be_u64(mvhd, 24)?)
+        } else {
+            (be_u32(mvhd, 12)? as u64, be_u32(mvhd, 16)? as u64)
+        };
+
+        Ok(duration.checked_div(timescale).unwrap_or(0))
+    }
+
+    fn read_audio_description(&mut self) -> anyhow::Result<()> {
+        let stsd = match self.sound_track().and_then(|t| {
+            t.child(b"mdia")?
+                .child(b"minf")?
+                .child(b"stbl")?
+                .child(b"stsd")
+        }) {
+            Some(stsd) => stsd.payload.clone(),
+            None => return Ok(()),
+        };
+
+        // stsd is a full box with an entry count, then the sample entries themselves. We only
+        // look at the first.
+        let entry = parse_atoms(stsd.get(8..).unwrap_or_default(), false)?
+            .into_iter()
+            .next()
+            .context(format!("no sample description in {}", self.path))?;
+
+        self.describe_sound(&entry)
+    }
+
+    // QuickTime sound descriptions come in three versions, each longer than the last, with any
+    // child atoms, like the ALAC magic cookie, after them. MP4 files only use the first. Version
+    // 2 puts placeholders in the version 0 fields, and the real values in its extension.
+    fn describe_sound(&mut self, entry: &Atom) -> anyhow::Result<()> {
+        self.channels = be_u16(&entry.payload, 16)?;
+        self.bit_depth = be_u16(&entry.payload, 18)? as u8;
+        self.sample_rate = be_u32(&entry.payload, 24)? >> 16;
+
+        let children_start = match be_u16(&entry.payload, 8)? {
+            0 => Some(28),
+            1 => Some(44),
+            2 => {
+                self.sample_rate = f64::from_bits(be_u64(&entry.payload, 32)?) as u32;
+                self.channels = be_u32(&entry.payload, 40)? as u16;
+                self.bit_depth = be_u32(&entry.payload, 48)? as u8;
+                Some(64)
+            }
+            _ => None,
+        };
+
+        let children = children_start
+            .and_then(|start| entry.payload.get(start..))
+            .and_then(|data| parse_atoms(data, false).ok())
+            .unwrap_or_default();
+
+        self.codec = match &entry.kind {
+            b"mp4a" => Mp4Codec::Aac,
+            b"alac" => {
+                // The sample entry's rate is 16.16 fixed point, so it can't describe anything
+                // over 65535Hz. The ALAC magic cookie has the real values.
+                if let Some(cookie) = children.iter().find(|a| &a.kind == b"alac") {
+                    self.bit_depth = *cookie.payload.get(9).unwrap_or(&self.bit_depth);
+                    self.sample_rate = be_u32(&cookie.payload, 24)?;
+                }
+
+                Mp4Codec::Alac
+            }
+            other => Mp4Codec::Other(String::from_utf8_lossy(other).into_owned()),
+        };
+
+        Ok(())
+    }
+
+    fn sound_track(&self) -> Option<&Atom> {
+        self.moov.children.as_ref()?.iter().find(|t| {
+            &t.kind == b"trak"
+                && t.child(b"mdia")
+                    .and_then(|m| m.child(b"hdlr"))
+                    .and_then(|h| h.payload.get(8..12))
+                    == Some(b"soun".as_slice())
+        })
+    }
+
+    #[cfg(test)]
+    fn chunk_offsets(&self) -> Vec<u64> {
+        let mut ret = Vec::new();
+        collect_chunk_offsets(&self.moov, &mut ret);
+        ret
+    }
+}
+
+impl Atom {
+    fn leaf(kind: &[u8; 4], payload: Vec<u8>) -> Self {
+        Self {
+            kind: *kind,
+            prefix: Vec::new(),
+            payload,
+            children: None,
+        }
+    }
+
+    fn child(&self, kind: &[u8; 4]) -> Option<&Atom> {
+        self.children.as_ref()?.iter().find(|a| &a.kind == kind)
+    }
+
+    fn child_mut(&mut self, kind: &[u8; 4]) -> Option<&mut Atom> {
+        self.children.as_mut()?.iter_mut().find(|a| &a.kind == kind)
+    }
+
+    fn child_or_insert(&mut self, kind: &[u8; 4], prefix: Vec<u8>) -> &mut Atom {
+        let children = self.children.get_or_insert_with(Vec::new);
+
+        let index = match children.iter().position(|a| &a.kind == kind) {
+            Some(index) => index,
+            None => {
+                children.push(Atom {
+                    kind: *kind,
+                    prefix,
+                    payload: Vec::new(),
+                    children: Some(Vec::new()),
+                });
+                children.len() - 1
+            }
+        };
+
+        &mut children[index]
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut ret = self.prefix.clone();
+
+        match &self.children {
+            Some(children) => children.iter().for_each(|c| ret.extend(c.to_bytes())),
+            None => ret.extend_from_slice(&self.payload),
+        }
+
+        ret
+    }
+
+    fn size(&self) -> u64 {
+        8 + self.body().len() as u64
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let body = self.body();
+        let mut ret = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        ret.extend_from_slice(&self.kind);
+        ret.extend(body);
+        ret
+    }
+}
+
+fn parse_atoms(data: &[u8], in_ilst: bool) -> anyhow::Result<Vec<Atom>> {
+    let mut ret = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= data.len() {
+        let (header_len, size) = match be_u32(data, offset)? {
+            0 => (8, data.len() - offset),
+            1 => (16, be_u64(data, offset + 8)? as usize),
+            size => (8, size as usize),
+        };
+
+        ensure!(
+            size >= header_len && offset + size <= data.len(),
+            "invalid atom size"
+        );
+
+        let kind: [u8; 4] = data[offset + 4..offset + 8].try_into()?;
+        let body = &data[offset + header_len..offset + size];
+
+        let atom = if in_ilst {
+            Atom {
+                kind,
+                prefix: Vec::new(),
+                payload: Vec::new(),
+                children: Some(parse_atoms(body, false)?),
+            }
+        } else if &kind == b"meta" {
+            // In MP4 files meta is a full box, with four bytes of version and flags before its
+            // children. In older QuickTime files it isn't.
+            let prefix_len = if body.get(4..8) == Some(b"hdlr".as_slice()) {
+                0
+            } else {
+                4
+            };
+
+            Atom {
+                kind,
+                prefix: body.get(..prefix_len).unwrap_or_default().to_vec(),
+                payload: Vec::new(),
+                children: Some(parse_atoms(
+                    body.get(prefix_len..).unwrap_or_default(),
+                    false,
+                )?),
+            }
+        } else if CONTAINERS.contains(&&kind) {
+            Atom {
+                kind,
+                prefix: Vec::new(),
+                payload: Vec::new(),
+                children: Some(parse_atoms(body, &kind == b"ilst")?),
+            }
+        } else {
+            Atom::leaf(&kind, body.to_vec())
+        };
+
+        ret.push(atom);
+        offset += size;
+    }
+
+    Ok(ret)
+}
+
+fn shift_chunk_offsets(atom: &mut Atom, moov_offset: u64, delta: i64) -> anyhow::Result<()> {
+    match (&atom.kind, atom.children.as_mut()) {
+        (_, Some(children)) => {
+            for child in children {
+                shift_chunk_offsets(child, moov_offset, delta)?;
+            }
+        }
+        (b"stco", None) => {
+            let count = be_u32(&atom.payload, 4)? as usize;
+
+            for i in 0..count {
+                let pos = 8 + i * 4;
+                let offset = be_u32(&atom.payload, pos)? as u64;
+
+                if offset > moov_offset {
+                    let new_offset = u32::try_from(offset as i64 + delta)
+                        .map_err(|_| anyhow!("chunk offset out of range"))?;
+                    atom.payload[pos..pos + 4].copy_from_slice(&new_offset.to_be_bytes());
+                }
+            }
+        }
+        (b"co64", None) => {
+            let count = be_u32(&atom.payload, 4)? as usize;
+
+            for i in 0..count {
+                let pos = 8 + i * 8;
+                let offset = be_u64(&atom.payload, pos)?;
+
+                if offset > moov_offset {
+                    let new_offset = (offset as i64 + delta) as u64;
+                    atom.payload[pos..pos + 8].copy_from_slice(&new_offset.to_be_bytes());
+                }
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn collect_chunk_offsets(atom: &Atom, aggr: &mut Vec<u64>) {
+    match (&atom.kind, atom.children.as_ref()) {
+        (_, Some(children)) => children.iter().for_each(|c| collect_chunk_offsets(c, aggr)),
+        (b"stco", None) => {
+            let count = be_u32(&atom.payload, 4).unwrap() as usize;
+            (0..count).for_each(|i| aggr.push(be_u32(&atom.payload, 8 + i * 4).unwrap() as u64));
+        }
+        _ => (),
+    }
+}
+
+// An ilst item holds a data atom, which is a four-byte type code, a four-byte locale, then the
+// value. Freeform items also have mean and name atoms.
+fn item_data(item: &Atom) -> Option<(u32, &[u8])> {
+    let data = item.child(b"data")?;
+    let data_type = be_u32(&data.payload, 0).ok()? & 0x00ff_ffff;
+    Some((data_type, data.payload.get(8..)?))
+}
+
+fn item_name(item: &Atom) -> String {
+    let name: String = item.kind.iter().map(|&b| b as char).collect();
+
+    if &item.kind == FREEFORM {
+        let suffix = item
+            .child(b"name")
+            .and_then(|n| n.payload.get(4..))
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .unwrap_or_default();
+        format!("{}:{}", name, suffix)
+    } else {
+        name
+    }
+}
+
+fn item_value(item: &Atom) -> String {
+    match item_data(item) {
+        Some((DATA_TYPE_UTF8, value)) => String::from_utf8_lossy(value).into_owned(),
+        Some((_, value)) if &item.kind == TRACK_KEY || &item.kind == DISC_KEY => {
+            let number = value
+                .get(2..4)
+                .map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+            let total = value
+                .get(4..6)
+                .map_or(0, |b| u16::from_be_bytes([b[0], b[1]]));
+            format!("{}/{}", number, total)
+        }
+        Some((_, value)) => format!("[{} bytes]", value.len()),
+        None => String::new(),
+    }
+}
+
+fn be_u16(data: &[u8], pos: usize) -> anyhow::Result<u16> {
+    let bytes = data.get(pos..pos + 2).context("truncated atom")?;
+    Ok(u16::from_be_bytes(bytes.try_into()?))
+}
+
+fn be_u32(data: &[u8], pos: usize) -> anyhow::Result<u32> {
+    let bytes = data.get(pos..pos + 4).context("truncated atom")?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+fn be_u64(data: &[u8], pos: usize) -> anyhow::Result<u64> {
+    let bytes = data.get(pos..pos + 8).context("truncated atom")?;
+    Ok(u64::from_be_bytes(bytes.try_into()?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_read_m4a() {
+        let file = Mp4File::read_from_path(&fixture!("info/test.m4a")).unwrap();
+
+        assert_eq!(Mp4Codec::Aac, file.codec);
+        assert_eq!(2, file.channels);
+        assert_eq!(44100, file.sample_rate);
+        assert_eq!(1, file.duration);
+        assert_eq!(32, file.audio_bytes);
+        assert_eq!(Some("Test Artist".to_owned()), file.get_text(b"\xa9ART"));
+        assert_eq!(Some(6), file.track_number());
+        assert_eq!(None, file.disc_number());
+        assert!(!file.has_picture());
+    }
+
+    #[test]
+    fn test_quicktime_sound_descriptions() {
+        let mut file = Mp4File::read_from_path(&fixture!("info/test.m4a")).unwrap();
+
+        let sound_description = |version: u16, extension: &[u8], children: &[u8]| {
+            let mut payload = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            payload.extend(version.to_be_bytes());
+            payload.extend([0; 6]);
+            payload.extend(2u16.to_be_bytes());
+            payload.extend(16u16.to_be_bytes());
+            payload.extend([0; 4]);
+            payload.extend((44100u32 << 16).to_be_bytes());
+            payload.extend(extension);
+            payload.extend(children);
+            payload
+        };
+
+        let mut cookie = 36u32.to_be_bytes().to_vec();
+        cookie.extend(b"alac");
+        cookie.extend([0; 28]);
+        cookie[8 + 9] = 24;
+        cookie[8 + 24..8 + 28].copy_from_slice(&96000u32.to_be_bytes());
+
+        let v1 = Atom::leaf(b"alac", sound_description(1, &[0; 16], &cookie));
+        file.describe_sound(&v1).unwrap();
+        assert_eq!(
+            (Mp4Codec::Alac, 2, 24, 96000),
+            (
+                file.codec.clone(),
+                file.channels,
+                file.bit_depth,
+                file.sample_rate
+            )
+        );
+
+        let mut extension = 72u32.to_be_bytes().to_vec();
+        extension.extend(48000f64.to_bits().to_be_bytes());
+        extension.extend(6u32.to_be_bytes());
+        extension.extend(0x7f000000u32.to_be_bytes());
+        extension.extend(24u32.to_be_bytes());
+        extension.extend([0; 12]);
+
+        let v2 = Atom::leaf(b"mp4a", sound_description(2, &extension, &[]));
+        file.describe_sound(&v2).unwrap();
+        assert_eq!(
+            (Mp4Codec::Aac, 6, 24, 48000),
+            (
+                file.codec.clone(),
+                file.channels,
+                file.bit_depth,
+                file.sample_rate
+            )
+        );
+
+        // A version we don't know, or children we can't parse, don't make the file unreadable.
+        let v9 = Atom::leaf(b"alac", sound_description(9, &[0xff; 16], &[]));
+        file.describe_sound(&v9).unwrap();
+        assert_eq!(Mp4Codec::Alac, file.codec);
+        assert_eq!(44100, file.sample_rate);
+    }
+
+    #[test]
+    fn test_moov_bigger_than_file() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let m4a = tmp.path().join("test.m4a");
+        let mut bytes = 16u32.to_be_bytes().to_vec();
+        bytes.extend(b"ftypM4A \0\0\0\0");
+        bytes.extend(u32::MAX.to_be_bytes());
+        bytes.extend(b"moov");
+        fs::write(&m4a, bytes).unwrap();
+
+        assert!(Mp4File::read_from_path(&m4a).is_err());
+    }
+
+    #[test]
+    fn test_rewrite_m4a() {
+        let name = "test.m4a";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[name]).unwrap();
+        let m4a = tmp.path().join(name);
+        let mut file = Mp4File::read_from_path(&m4a).unwrap();
+        let original_bytes = fs::read(&m4a).unwrap();
+        let original_offset = file.chunk_offsets()[0] as usize;
+
+        file.set_text(b"\xa9nam", "A Much Longer Title Than There Was Before");
+        file.set_track_number(11);
+        file.set_disc_number(2);
+        assert!(file.remove("\u{a9}gen"));
+        assert!(!file.remove("\u{a9}gen"));
+        file.save().unwrap();
+
+        let new_file = Mp4File::read_from_path(&m4a).unwrap();
+        let new_bytes = fs::read(&m4a).unwrap();
+        let new_offset = new_file.chunk_offsets()[0] as usize;
+
+        assert_eq!(
+            Some("A Much Longer Title Than There Was Before".to_owned()),
+            new_file.get_text(b"\xa9nam")
+        );
+        assert_eq!(Some(11), new_file.track_number());
+        assert_eq!(Some(2), new_file.disc_number());
+        assert!(
+            new_file
+                .raw_tags()
+                .contains(&("disk".to_owned(), "2/0".to_owned()))
+        );
+        assert_eq!(None, new_file.get_text(b"\xa9gen"));
+        assert_eq!(
+            Some("Test Artist".to_owned()),
+            new_file.get_text(b"\xa9ART")
+        );
+        assert_eq!(
+            original_bytes[original_offset..original_offset + 16],
+            new_bytes[new_offset..new_offset + 16]
+        );
+    }
+}
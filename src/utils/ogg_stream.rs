@@ -0,0 +1,369 @@
+use anyhow::{Context, anyhow, ensure};
+use camino::{Utf8Path, Utf8PathBuf};
+use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom};
+
+const VORBIS_ID_MAGIC: &[u8] = b"\x01vorbis";
+const VORBIS_COMMENT_MAGIC: &[u8] = b"\x03vorbis";
+const OPUS_ID_MAGIC: &[u8] = b"OpusHead";
+const OPUS_COMMENT_MAGIC: &[u8] = b"OpusTags";
+const OPUS_SAMPLE_RATE: u32 = 48000;
+const PICTURE_KEYS: [&str; 2] = ["metadata_block_picture", "coverart"];
+
+// The largest possible Ogg page is 65307 bytes, so the last granule position is always in the
+// final chunk of this size.
+const MAX_PAGE_SIZE: u64 = 65307;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OggCodec {
+    Vorbis,
+    Opus,
+}
+
+/// The parts of an Ogg Vorbis or Ogg Opus file we care about: enough of the identification header
+/// to describe the audio, and the Vorbis comments, which we can rewrite in place.
+#[derive(Debug)]
+pub struct OggStream {
+    path: Utf8PathBuf,
+    pub codec: OggCodec,
+    pub sample_rate: u32,
+    pub nominal_bitrate: u32,
+    pub pre_skip: u64,
+    pub last_granule: u64,
+    pub vendor: String,
+    pub comments: Vec<(String, String)>,
+    trailer: Vec<u8>,
+}
+
+impl OggStream {
+    pub fn read_from_path(path: &Utf8Path) -> anyhow::Result<Self> {
+        let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+
+        let id_packet = reader
+            .read_packet()?
+            .context(format!("no identification header in {}", path))?;
+
+        let (codec, sample_rate, nominal_bitrate, pre_skip) = parse_id_header(&id_packet.data)?;
+
+        let comment_packet = reader
+            .read_packet()?
+            .context(format!("no comment header in {}", path))?;
+
+        let (vendor, comments, trailer) = parse_comment_header(codec, &comment_packet.data)?;
+        let last_granule = last_granule(reader.into_inner().get_mut())?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            codec,
+            sample_rate,
+            nominal_bitrate,
+            pre_skip,
+            last_granule,
+            vendor,
+            comments,
+            trailer,
+        })
+    }
+
+    /// Length of the audio, in samples at the decoding rate.
+    pub fn samples(&self) -> u64 {
+        self.last_granule.saturating_sub(self.pre_skip)
+    }
+
+    /// Rate at which granule positions are counted. Opus always decodes at 48kHz, whatever the
+    /// input rate was.
+    pub fn decode_rate(&self) -> u32 {
+        match self.codec {
+            OggCodec::Vorbis => self.sample_rate,
+            OggCodec::Opus => OPUS_SAMPLE_RATE,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.comments
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Vorbis comments may legitimately repeat, but we only ever want one of anything.
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.remove(key);
+        self.comments.push((key.to_uppercase(), value.to_owned()));
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.comments.len();
+        self.comments.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+        self.comments.len() != before
+    }
+
+    pub fn has_picture(&self) -> bool {
+        self.comments
+            .iter()
+            .any(|(k, _)| PICTURE_KEYS.iter().any(|p| k.eq_ignore_ascii_case(p)))
+    }
+
+    pub fn remove_pictures(&mut self) -> bool {
+        let mut changed = false;
+
+        for key in PICTURE_KEYS {
+            changed |= self.remove(key);
+        }
+
+        changed
+    }
+
+    // Ogg pages are checksummed and sequenced, so we can't patch the comment header in place.
+    // Instead we repacketize the whole stream into a temporary file next to the original, with
+    // the new comment header swapped in, then move it over the original.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let dir = self
+            .path
+            .parent()
+            .context(format!("cannot get directory of {}", self.path))?;
+
+        let mut reader = PacketReader::new(BufReader::new(File::open(&self.path)?));
+        let tmp = tempfile::NamedTempFile::new_in(dir)?;
+        let mut writer = PacketWriter::new(BufWriter::new(tmp.as_file()));
+        let mut index = 0;
+
+        while let Some(packet) = reader.read_packet()? {
+            let end_info = if packet.last_in_stream() {
+                PacketWriteEndInfo::EndStream
+            } else if packet.last_in_page() {
+                PacketWriteEndInfo::EndPage
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+
+            let data = if index == 1 {
+                self.comment_header()
+            } else {
+                packet.data.clone()
+            };
+
+            writer.write_packet(
+                data.into_boxed_slice(),
+                packet.stream_serial(),
+                end_info,
+                packet.absgp_page(),
+            )?;
+            index += 1;
+        }
+
+        writer
+            .into_inner()
+            .into_inner()
+            .map_err(|e| anyhow!("failed to write {}: {}", self.path, e))?;
+        fs::set_permissions(tmp.path(), fs::metadata(&self.path)?.permissions())?;
+        tmp.persist(&self.path)?;
+        Ok(())
+    }
+
+    fn comment_header(&self) -> Vec<u8> {
+        let mut ret = match self.codec {
+            OggCodec::Vorbis => VORBIS_COMMENT_MAGIC.to_vec(),
+            OggCodec::Opus => OPUS_COMMENT_MAGIC.to_vec(),
+        };
+
+        push_string(&mut ret, &self.vendor);
+        ret.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+
+        for (k, v) in &self.comments {
+            push_string(&mut ret, &format!("{}={}", k, v));
+        }
+
+        ret.extend_from_slice(&self.trailer);
+        ret
+    }
+}
+
+type IdHeader = (OggCodec, u32, u32, u64);
+
+fn parse_id_header(data: &[u8]) -> anyhow::Result<IdHeader> {
+    if data.starts_with(VORBIS_ID_MAGIC) {
+        ensure!(data.len() >= 30, "truncated Vorbis identification header");
+        Ok((
+            OggCodec::Vorbis,
+            u32::from_le_bytes(data[12..16].try_into()?),
+            i32::from_le_bytes(data[20..24].try_into()?).max(0) as u32,
+            0,
+        ))
+    } else if data.starts_with(OPUS_ID_MAGIC) {
+        ensure!(data.len() >= 19, "truncated Opus identification header");
+        Ok((
+            OggCodec::Opus,
+            u32::from_le_bytes(data[12..16].try_into()?),
+            0,
+            u16::from_le_bytes(data[10..12].try_into()?) as u64,
+        ))
+    } else {
+        Err(anyhow!("not an Ogg Vorbis or Ogg Opus stream"))
+    }
+}
+
+type CommentHeader = (String, Vec<(String, String)>, Vec<u8>);
+
+fn parse_comment_header(codec: OggCodec, data: &[u8]) -> anyhow::Result<CommentHeader> {
+    let magic = match codec {
+        OggCodec::Vorbis => VORBIS_COMMENT_MAGIC,
+        OggCodec::Opus => OPUS_COMMENT_MAGIC,
+    };
+
+    ensure!(data.starts_with(magic), "invalid comment header");
+
+    let mut cursor = magic.len();
+    let vendor = read_string(data, &mut cursor)?;
+    let count = read_u32(data, &mut cursor)?;
+    let mut comments = Vec::new();
+
+    for _ in 0..count {
+        let comment = read_string(data, &mut cursor)?;
+
+        if let Some((k, v)) = comment.split_once('=') {
+            comments.push((k.to_owned(), v.to_owned()));
+        }
+    }
+
+    Ok((vendor, comments, data[cursor..].to_vec()))
+}
+
+fn read_u32(data: &[u8], cursor: &mut usize) -> anyhow::Result<u32> {
+    let bytes = data
+        .get(*cursor..*cursor + 4)
+        .context("truncated comment header")?;
+    *cursor += 4;
+    Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_string(data: &[u8], cursor: &mut usize) -> anyhow::Result<String> {
+    let len = read_u32(data, cursor)? as usize;
+    let bytes = data
+        .get(*cursor..*cursor + len)
+        .context("truncated comment header")?;
+    *cursor += len;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn push_string(buf: &mut Vec<u8>, string: &str) {
+    buf.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    buf.extend_from_slice(string.as_bytes());
+}
+
+// The granule position of the final page tells us how many samples are in the stream. Pages on
+// which no packet finishes have a granule position of -1, so we skip back past those. Audio can
+// contain "OggS" too, so only a whole page with a good checksum counts.
+fn last_granule<R: Read + Seek>(reader: &mut R) -> anyhow::Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    let start = len.saturating_sub(MAX_PAGE_SIZE);
+    let mut buf = Vec::new();
+    reader.seek(SeekFrom::Start(start))?;
+    reader.take(MAX_PAGE_SIZE).read_to_end(&mut buf)?;
+
+    let granule = (0..buf.len())
+        .rev()
+        .filter_map(|i| valid_page(&buf[i..]))
+        .map(|page| u64::from_le_bytes(page[6..14].try_into().unwrap()))
+        .find(|g| *g != u64::MAX);
+
+    Ok(granule.unwrap_or(0))
+}
+
+// The page at the start of buf, if all of it is there and it is what it says it is.
+fn valid_page(buf: &[u8]) -> Option<&[u8]> {
+    let header = buf.get(..27)?;
+
+    if &header[..4] != b"OggS" || header[4] != 0 {
+        return None;
+    }
+
+    let segments = header[26] as usize;
+    let body: usize = buf
+        .get(27..27 + segments)?
+        .iter()
+        .map(|s| *s as usize)
+        .sum();
+    let page = buf.get(..27 + segments + body)?;
+    let crc = u32::from_le_bytes(page[22..26].try_into().ok()?);
+
+    (page_crc(page) == crc).then_some(page)
+}
+
+// Ogg's CRC-32 covers the whole page, with the checksum field itself taken as zero.
+fn page_crc(page: &[u8]) -> u32 {
+    let mut crc = 0u32;
+
+    for (i, byte) in page.iter().enumerate() {
+        let byte = if (22..26).contains(&i) { 0 } else { *byte };
+        crc ^= u32::from(byte) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_opus() {
+        let stream = OggStream::read_from_path(&fixture!("info/test.opus")).unwrap();
+
+        assert_eq!(OggCodec::Opus, stream.codec);
+        assert_eq!(44100, stream.sample_rate);
+        assert_eq!(48000, stream.decode_rate());
+        assert_eq!(Some("Test Artist"), stream.get("artist"));
+        assert_eq!(Some("Test Artist"), stream.get("ARTIST"));
+        assert_eq!(None, stream.get("composer"));
+        assert!(!stream.has_picture());
+    }
+
+    #[test]
+    fn test_last_granule() {
+        let mut bytes = fs::read(fixture!("info/test.opus")).unwrap();
+        let granule = last_granule(&mut Cursor::new(&bytes)).unwrap();
+
+        assert!(valid_page(&bytes).is_some());
+        assert!(granule > 0);
+
+        // Something which looks like a page header, but isn't.
+        bytes.extend(b"OggS\0\0");
+        bytes.extend(12345u64.to_le_bytes());
+        bytes.extend([0u8; 32]);
+        assert_eq!(granule, last_granule(&mut Cursor::new(&bytes)).unwrap());
+    }
+
+    #[test]
+    fn test_rewrite_comments() {
+        let file = "test.opus";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file]).unwrap();
+        let opus = tmp.path().join(file);
+        let mut stream = OggStream::read_from_path(&opus).unwrap();
+        let samples = stream.samples();
+
+        stream.set("title", "New Title");
+        assert!(stream.remove("genre"));
+        assert!(!stream.remove("genre"));
+        stream.save().unwrap();
+
+        let new_stream = OggStream::read_from_path(&opus).unwrap();
+        assert_eq!(Some("New Title"), new_stream.get("title"));
+        assert_eq!(None, new_stream.get("genre"));
+        assert_eq!(Some("Test Artist"), new_stream.get("artist"));
+        assert_eq!(samples, new_stream.samples());
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::{anyhow, ensure};
+use camino::Utf8Path;
+use serde::Deserialize;
+use std::process::Command;
+
+pub const DEFAULT_MP3_PROFILE: &str = "extreme";
+pub const CDQ_PROFILE: &str = "cdq";
+pub const REENCODE_FLAC_PROFILE: &str = "reencode_flac";
+pub const REENCODE_MP3_PROFILE: &str = "reencode_mp3";
+
+const LAME_PRESETS: [&str; 4] = ["medium", "standard", "extreme", "insane"];
+
+/// How to make one kind of file. Profiles are defined in `[profiles.<name>]` sections of the
+/// config file, and a few are built in. A profile in the config replaces a built-in one of the
+/// same name.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(skip)]
+    pub name: String,
+    pub encoder: Encoder,
+    /// Passed to the encoder, after the input file for ffmpeg, and before it for LAME.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Of the files the profile makes.
+    pub extension: String,
+    /// Used where a command has to tell its output from the original, like the "-cdq" on the end
+    /// of a file `cdq` makes. Defaults to the name of the profile.
+    suffix: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoder {
+    Ffmpeg,
+    Lame,
+}
+
+impl Encoder {
+    pub fn binary(&self) -> &'static str {
+        match self {
+            Encoder::Ffmpeg => "ffmpeg",
+            Encoder::Lame => "lame",
+        }
+    }
+}
+
+impl Profile {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (encoder, args, extension, suffix) = match name {
+            n if LAME_PRESETS.contains(&n) => (
+                Encoder::Lame,
+                vec!["-q1", "--vbr-new", "-V0", "--preset", n],
+                "mp3",
+                None,
+            ),
+            CDQ_PROFILE => (
+                Encoder::Ffmpeg,
+                vec!["-af", "aresample=out_sample_fmt=s16:out_sample_rate=44100"],
+                "flac",
+                None,
+            ),
+            REENCODE_FLAC_PROFILE => (
+                Encoder::Ffmpeg,
+                vec!["-compression_level", "9"],
+                "flac",
+                Some("reencoded"),
+            ),
+            REENCODE_MP3_PROFILE => (
+                Encoder::Lame,
+                vec!["-q2", "--vbr-new", "--preset", "128"],
+                "mp3",
+                Some("reencoded"),
+            ),
+            _ => return None,
+        };
+
+        Some(Self {
+            name: name.to_owned(),
+            encoder,
+            args: args.into_iter().map(String::from).collect(),
+            extension: extension.to_owned(),
+            suffix: suffix.map(String::from),
+        })
+    }
+
+    // What transcode has always done: let ffmpeg pick everything from the extension.
+    pub fn ffmpeg(extension: &str) -> Self {
+        Self {
+            name: extension.to_owned(),
+            encoder: Encoder::Ffmpeg,
+            args: Vec::new(),
+            extension: extension.to_owned(),
+            suffix: None,
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.extension.is_empty(),
+            "profile '{}' has no extension",
+            self.name
+        );
+        ensure!(
+            self.encoder != Encoder::Lame || self.extension == "mp3",
+            "profile '{}' uses LAME, which can only make mp3 files",
+            self.name
+        );
+        Ok(())
+    }
+
+    pub fn suffix(&self) -> &str {
+        self.suffix.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Commands can only put what they make in the right place if it's the right type.
+    pub fn ensure_makes(&self, extension: &str) -> anyhow::Result<()> {
+        ensure!(
+            self.extension == extension,
+            "profile '{}' makes {} files, not {}",
+            self.name,
+            self.extension,
+            extension
+        );
+        Ok(())
+    }
+
+    /// The encoder command which turns `input` into `output`. `extra_args` go after the
+    /// profile's own. LAME reads standard input if `input` is "-".
+    pub fn command(
+        &self,
+        binary: &Utf8Path,
+        extra_args: &[&str],
+        input: &Utf8Path,
+        output: &Utf8Path,
+    ) -> Command {
+        let mut cmd = Command::new(binary);
+
+        match self.encoder {
+            Encoder::Ffmpeg => {
+                cmd.args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+                    .arg(input)
+                    .args(&self.args)
+                    .args(extra_args)
+                    .arg(output);
+            }
+            Encoder::Lame => {
+                cmd.args(&self.args)
+                    .args(extra_args)
+                    .arg("--silent")
+                    .arg(input)
+                    .arg(output);
+            }
+        }
+
+        cmd
+    }
+}
+
+pub fn unknown_profile(name: &str) -> anyhow::Error {
+    anyhow!("unknown encoding profile '{}'", name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(cmd: &Command) -> Vec<String> {
+        cmd.get_args()
+            .map(|a| a.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin() {
+        let extreme = Profile::builtin("extreme").unwrap();
+        assert_eq!(Encoder::Lame, extreme.encoder);
+        assert_eq!("mp3", extreme.extension);
+        assert_eq!("extreme", extreme.suffix());
+        assert_eq!("cdq", Profile::builtin(CDQ_PROFILE).unwrap().suffix());
+        assert_eq!(
+            "reencoded",
+            Profile::builtin(REENCODE_MP3_PROFILE).unwrap().suffix()
+        );
+        assert!(Profile::builtin("128").is_none());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Profile::builtin("insane").unwrap().validate().is_ok());
+        assert!(Profile::ffmpeg("ogg").validate().is_ok());
+
+        let mut profile = Profile::builtin("insane").unwrap();
+        profile.extension = "flac".to_owned();
+        assert_eq!(
+            "profile 'insane' uses LAME, which can only make mp3 files",
+            profile.validate().unwrap_err().to_string()
+        );
+
+        assert!(Profile::ffmpeg("").validate().is_err());
+    }
+
+    #[test]
+    fn test_ensure_makes() {
+        let cdq = Profile::builtin(CDQ_PROFILE).unwrap();
+        assert!(cdq.ensure_makes("flac").is_ok());
+        assert_eq!(
+            "profile 'cdq' makes flac files, not mp3",
+            cdq.ensure_makes("mp3").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let input = Utf8Path::new("/in/file.flac");
+        let output = Utf8Path::new("/out/file.mp3");
+
+        let cmd = Profile::builtin("standard").unwrap().command(
+            Utf8Path::new("/bin/lame"),
+            &["--add-id3v2"],
+            Utf8Path::new("-"),
+            output,
+        );
+
+        assert_eq!("/bin/lame", cmd.get_program());
+        assert_eq!(
+            vec![
+                "-q1",
+                "--vbr-new",
+                "-V0",
+                "--preset",
+                "standard",
+                "--add-id3v2",
+                "--silent",
+                "-",
+                "/out/file.mp3"
+            ],
+            args(&cmd)
+        );
+
+        let cmd = Profile::builtin(REENCODE_FLAC_PROFILE).unwrap().command(
+            Utf8Path::new("/bin/ffmpeg"),
+            &[],
+            input,
+            output,
+        );
+
+        assert_eq!(
+            vec![
+                "-hide_banner",
+                "-loglevel",
+                "error",
+                "-y",
+                "-i",
+                "/in/file.flac",
+                "-compression_level",
+                "9",
+                "/out/file.mp3"
+            ],
+            args(&cmd)
+        );
+    }
+}
@@ -151,16 +151,8 @@ impl ToFilenameChunk for String {
                         builder.extend(['_', 'p', 'l', 'u', 's']);
                     }
                 }
-                '_' => {
-                    if !builder.is_empty() && i < last_index {
-                        builder.push('_');
-                    }
-                }
-                '*' => {
-                    if !builder.is_empty() && i < last_index {
-                        builder.push('-');
-                    }
-                }
+                '_' if !builder.is_empty() && i < last_index => builder.push('_'),
+                '*' if !builder.is_empty() && i < last_index => builder.push('-'),
                 '#' => {
                     if let Some(&last_pushed) = builder.last() {
                         if ['a', 'b', 'c', 'd', 'e', 'f'].contains(&last_pushed) {
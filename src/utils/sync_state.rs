@@ -0,0 +1,252 @@
+use crate::utils::config::default_cache_dir;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::time::UNIX_EPOCH;
+
+// What every transcoded file was made from, so if its FLAC is replaced, say with a better rip, we
+// know to make it again. Keyed by the path of the transcoded file.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SyncState {
+    sources: HashMap<String, String>,
+    #[serde(default)]
+    flacs: HashMap<String, SeenFlac>,
+    #[serde(skip)]
+    file: Option<Utf8PathBuf>,
+}
+
+// The fingerprint of a FLAC, and what the file looked like when we took it. If it still looks the
+// same, we don't have to open it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct SeenFlac {
+    size: u64,
+    mtime: u64,
+    fingerprint: String,
+}
+
+// Shared by everything which keeps a tree in step with flac/.
+pub fn default_location() -> Utf8PathBuf {
+    default_cache_dir().join("sync.json")
+}
+
+impl SyncState {
+    // A state file which can't be read is treated as empty. Whatever is already there is assumed
+    // to be up to date, and is recorded as such, but moves can't be spotted, so we say so.
+    pub fn load(file: &Utf8Path) -> Self {
+        let state = if file.exists() {
+            match Self::read(file) {
+                Ok(state) => state,
+                Err(e) => {
+                    eprintln!("Ignoring unreadable state file {file}: {e}");
+                    Self::default()
+                }
+            }
+        } else {
+            Self::default()
+        };
+
+        Self {
+            file: Some(file.to_path_buf()),
+            ..state
+        }
+    }
+
+    fn read(file: &Utf8Path) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(file)?)?)
+    }
+
+    // Only a target made from something else is stale. One we know nothing about isn't.
+    pub fn is_stale(&self, target: &Utf8Path, source_fingerprint: &str) -> bool {
+        self.sources
+            .get(target.as_str())
+            .is_some_and(|recorded| recorded != source_fingerprint)
+    }
+
+    pub fn source_of(&self, target: &Utf8Path) -> Option<&str> {
+        self.sources.get(target.as_str()).map(|s| s.as_str())
+    }
+
+    pub fn record(&mut self, target: &Utf8Path, source_fingerprint: String) {
+        self.sources.insert(target.to_string(), source_fingerprint);
+    }
+
+    // source_fingerprint(), remembered for as long as the FLAC's size and mtime don't change.
+    pub fn fingerprint(&mut self, flac: &Utf8Path) -> anyhow::Result<String> {
+        let (size, mtime) = size_and_mtime(flac)?;
+
+        if let Some(seen) = self.flacs.get(flac.as_str())
+            && seen.size == size
+            && seen.mtime == mtime
+        {
+            return Ok(seen.fingerprint.clone());
+        }
+
+        let fingerprint = source_fingerprint(flac)?;
+
+        self.flacs.insert(
+            flac.to_string(),
+            SeenFlac {
+                size,
+                mtime,
+                fingerprint: fingerprint.clone(),
+            },
+        );
+
+        Ok(fingerprint)
+    }
+
+    // Targets which have gone are dropped, so the file doesn't grow forever. mp3dir and syncflac
+    // can run at the same time, so the file is replaced whole, never rewritten in place.
+    pub fn save(mut self) -> anyhow::Result<()> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        self.sources
+            .retain(|target, _| Utf8Path::new(target).exists());
+        self.flacs.retain(|flac, _| Utf8Path::new(flac).exists());
+
+        let dir = file.parent().unwrap_or(Utf8Path::new("."));
+        fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        tmp.write_all(serde_json::to_string(&self)?.as_bytes())?;
+        tmp.persist(&file)?;
+        Ok(())
+    }
+}
+
+// The MD5 of the decoded audio, from STREAMINFO. It only changes if the audio does, so retagging a
+// FLAC doesn't make us transcode it again. Encoders may leave it empty, in which case we fall
+// back to the size and modification time of the file.
+pub fn source_fingerprint(flac: &Utf8Path) -> anyhow::Result<String> {
+    if let Some(md5) = streaminfo_md5(flac)?.filter(|md5| md5.iter().any(|b| *b != 0)) {
+        return Ok(md5.iter().map(|b| format!("{:02x}", b)).collect());
+    }
+
+    let metadata = fs::metadata(flac)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok(format!("{}:{}", metadata.len(), mtime.as_secs()))
+}
+
+// STREAMINFO is always the first block, so there's no need to read the rest, which may be big
+// pictures.
+fn streaminfo_md5(flac: &Utf8Path) -> anyhow::Result<Option<[u8; 16]>> {
+    let mut header = [0u8; 42];
+    File::open(flac)?.read_exact(&mut header)?;
+
+    if &header[0..4] != b"fLaC" || header[4] & 0x7f != 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(header[26..42].try_into()?))
+}
+
+fn size_and_mtime(file: &Utf8Path) -> anyhow::Result<(u64, u64)> {
+    let metadata = fs::metadata(file)?;
+    let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+    Ok((metadata.len(), mtime.as_nanos() as u64))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile_ext::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    fn test_source_fingerprint() {
+        let fingerprint = source_fingerprint(&fixture!("info/test.flac")).unwrap();
+        assert!(!fingerprint.is_empty());
+        assert_eq!(
+            fingerprint,
+            source_fingerprint(&fixture!("info/test.flac")).unwrap()
+        );
+        assert!(source_fingerprint(&fixture!("info/no-such-file.flac")).is_err());
+    }
+
+    #[test]
+    fn test_source_fingerprint_matches_metaflac() {
+        let flac = fixture!("info/test.flac");
+        let md5 = metaflac::Tag::read_from_path(&flac)
+            .unwrap()
+            .get_streaminfo()
+            .unwrap()
+            .md5
+            .clone();
+
+        assert_eq!(md5, streaminfo_md5(&flac).unwrap().unwrap().to_vec());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let flac = tmp.path().join("test.flac");
+        let mut state = SyncState::default();
+        let fingerprint = state.fingerprint(&flac).unwrap();
+
+        assert_eq!(source_fingerprint(&flac).unwrap(), fingerprint);
+
+        // Nothing is read while the file looks the same.
+        state.flacs.get_mut(flac.as_str()).unwrap().fingerprint = "cached".to_owned();
+        assert_eq!("cached", state.fingerprint(&flac).unwrap());
+
+        let (size, _) = size_and_mtime(&flac).unwrap();
+        state.flacs.get_mut(flac.as_str()).unwrap().size = size + 1;
+        assert_eq!(fingerprint, state.fingerprint(&flac).unwrap());
+    }
+
+    #[test]
+    fn test_source_of() {
+        let mut state = SyncState::default();
+        let target = Utf8Path::new("/mp3/eps/band.ep/01.band.song.mp3");
+
+        assert_eq!(None, state.source_of(target));
+        state.record(target, "abc".to_owned());
+        assert_eq!(Some("abc"), state.source_of(target));
+    }
+
+    #[test]
+    fn test_sync_state() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.flac"]).unwrap();
+        let target = tmp.path().join("test.flac");
+        let state_file = tmp.path().join("cache").join("sync.json");
+
+        let mut state = SyncState::load(&state_file);
+        assert!(!state.is_stale(&target, "abc"));
+        state.record(&target, "abc".to_owned());
+        state.record(&tmp.path().join("gone.mp3"), "def".to_owned());
+        assert!(!state.is_stale(&target, "abc"));
+        assert!(state.is_stale(&target, "xyz"));
+        state.save().unwrap();
+
+        let state = SyncState::load(&state_file);
+        assert!(state.is_stale(&target, "xyz"));
+        assert_eq!(1, state.sources.len());
+
+        // State files from before FLACs were remembered still load.
+        fs::write(&state_file, r#"{"sources":{"/a.mp3":"abc"}}"#).unwrap();
+        let state = SyncState::load(&state_file);
+        assert_eq!(Some("abc"), state.source_of(Utf8Path::new("/a.mp3")));
+    }
+
+    #[test]
+    fn test_sync_state_unreadable() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let state_file = tmp.path().join("sync.json");
+        fs::write(&state_file, r#"{"sources":{"/a.mp3":"ab"#).unwrap();
+
+        assert!(SyncState::read(&state_file).is_err());
+
+        let mut state = SyncState::load(&state_file);
+        assert_eq!(None, state.source_of(Utf8Path::new("/a.mp3")));
+        state.record(&state_file, "abc".to_owned());
+        state.save().unwrap();
+
+        assert!(SyncState::read(&state_file).is_ok());
+    }
+}
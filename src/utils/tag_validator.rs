@@ -1,3 +1,4 @@
+use crate::utils::retitler::Retitler;
 use crate::utils::tag_maker::TagMaker;
 use crate::utils::types::Genres;
 use crate::utils::words::Words;
@@ -7,6 +8,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 pub struct TagValidator<'a> {
     current_year: i32,
     tag_maker: TagMaker<'a>,
+    retitler: Retitler<'a>,
     genres: Option<&'a Genres>,
 }
 
@@ -14,6 +16,7 @@ impl<'a> TagValidator<'a> {
     pub fn new(words: &'a Words, genres: Option<&'a Genres>) -> Self {
         TagValidator {
             tag_maker: TagMaker::new(words, false),
+            retitler: Retitler::new(words),
             current_year: this_year(),
             genres,
         }
@@ -24,7 +27,7 @@ impl<'a> TagValidator<'a> {
             "artist" => Ok(self.validate_artist(value)),
             "title" => Ok(self.validate_title(value)),
             "album" => Ok(self.validate_album(value)),
-            "t_num" => Ok(self.validate_t_num(value)),
+            "t_num" | "disc" => Ok(self.validate_t_num(value)),
             "year" => Ok(self.validate_year(value)),
             "genre" => Ok(self.validate_genre(value)),
             _ => Err(anyhow!("'{}' is not a recognised tag", tag_name)),
@@ -43,6 +46,17 @@ impl<'a> TagValidator<'a> {
         self.validate_artist(tag)
     }
 
+    // What retitle would turn the tag into, if that isn't what it already is.
+    pub fn suggest_capitalisation(&self, tag: &str) -> Option<String> {
+        let suggestion = self.retitler.retitle(tag);
+
+        if suggestion == tag {
+            None
+        } else {
+            Some(suggestion)
+        }
+    }
+
     pub fn validate_t_num(&self, tag: &str) -> bool {
         if !(1..=2).contains(&tag.len()) || tag.starts_with('0') {
             return false;
@@ -156,6 +170,20 @@ mod test {
         assert!(!tv.validate_title("File,with Bad Title"));
     }
 
+    #[test]
+    fn test_suggest_capitalisation() {
+        let words = new_words();
+        let tv = TagValidator::new(&words, None);
+        assert_eq!(None, tv.suggest_capitalisation("File for Test"));
+        assert_eq!(None, tv.suggest_capitalisation("ABBA"));
+        assert_eq!(None, tv.suggest_capitalisation("mxbx"));
+        assert_eq!(
+            Some("The Light at the End of the Tunnel".to_owned()),
+            tv.suggest_capitalisation("The Light At The End Of The Tunnel")
+        );
+        assert_eq!(Some("ABBA".to_owned()), tv.suggest_capitalisation("Abba"));
+    }
+
     #[test]
     fn test_validate_t_num() {
         let words = new_words();
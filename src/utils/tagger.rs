@@ -1,23 +1,26 @@
-use crate::utils::metadata::{AurMetadata, AurTags};
+use crate::utils::formats::MediaFormat;
+use crate::utils::loudness::ReplayGain;
+use crate::utils::metadata::{AurMetadata, AurTags, RawTags};
 use anyhow::anyhow;
 use camino::Utf8PathBuf;
-use id3::TagLike;
-use metaflac::block::PictureType;
 
-// A common interface to apply the tags we're interested in to FLACs and MP3s.
+// A common interface to apply the tags we're interested in to any media file. The work is done by
+// the file's MediaFormat.
 
 pub struct Tagger<'a> {
     path: &'a Utf8PathBuf,
-    filetype: &'a String,
+    format: &'static dyn MediaFormat,
     current_tags: &'a AurTags,
+    current_rawtags: &'a RawTags,
 }
 
 impl<'a> Tagger<'a> {
     pub fn new(fileinfo: &'a AurMetadata) -> anyhow::Result<Self> {
         Ok(Tagger {
             path: &fileinfo.path,
-            filetype: &fileinfo.filetype,
+            format: fileinfo.format,
             current_tags: &fileinfo.tags,
+            current_rawtags: &fileinfo.rawtags,
         })
     }
 
@@ -37,6 +40,10 @@ impl<'a> Tagger<'a> {
         self.set_tag("t_num", value, silent)
     }
 
+    pub fn set_disc(&self, value: &str, silent: bool) -> anyhow::Result<bool> {
+        self.set_tag("disc", value, silent)
+    }
+
     pub fn set_year(&self, value: &str, silent: bool) -> anyhow::Result<bool> {
         self.set_tag("year", value, silent)
     }
@@ -52,6 +59,7 @@ impl<'a> Tagger<'a> {
             "title" => &self.current_tags.title,
             "album" => &self.current_tags.album,
             "t_num" => &self.current_tags.t_num.to_string(),
+            "disc" => &self.current_tags.disc.to_string(),
             "year" => &self.current_tags.year.to_string(),
             "genre" => &self.current_tags.genre,
             _ => return Err(anyhow!("Unknown tag name")),
@@ -65,132 +73,39 @@ impl<'a> Tagger<'a> {
             println!("{:>16} -> {}", tag_name, value);
         }
 
-        match self.filetype.as_str() {
-            "flac" => self.set_flac_tag(tag_name, value),
-            "mp3" => self.set_mp3_tag(tag_name, value),
-            _ => Err(anyhow!("Unsupported filetype")),
-        }
+        self.format.set_tag(self.path, tag_name, value)
     }
 
-    fn set_flac_tag(&self, tag_name: &str, value: &str) -> anyhow::Result<bool> {
-        let mut tag = metaflac::Tag::read_from_path(self.path)?;
-        let val = vec![value];
-
-        match tag_name {
-            "artist" => tag.set_vorbis("artist".to_owned(), val),
-            "album" => tag.set_vorbis("album".to_owned(), val),
-            "title" => tag.set_vorbis("title".to_owned(), val),
-            "t_num" => tag.set_vorbis("tracknumber".to_owned(), val),
-            "year" => tag.set_vorbis("date".to_owned(), val),
-            "genre" => tag.set_vorbis("genre".to_owned(), val),
-            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
-        }
-        tag.save()?;
-        Ok(true)
+    pub fn remove_tags(&self, tags: &[String]) -> anyhow::Result<bool> {
+        self.format.remove_tags(self.path, tags)
     }
 
-    fn set_mp3_tag(&self, tag_name: &str, value: &str) -> anyhow::Result<bool> {
-        let mut tag = id3::Tag::read_from_path(self.path)?;
-
-        match tag_name {
-            "artist" => tag.set_artist(value),
-            "album" => tag.set_album(value),
-            "title" => tag.set_title(value),
-            "t_num" => tag.set_track(value.to_owned().parse::<u32>()?),
-            "year" => tag.set_year(value.to_owned().parse::<i32>()?),
-            "genre" => tag.set_genre(value),
-            _ => return Err(anyhow!("unknown tag name: {tag_name}")),
-        }
+    // False if the file already has these values.
+    pub fn set_loudness(&self, gain: &ReplayGain, silent: bool) -> anyhow::Result<bool> {
+        let current = ReplayGain::from_rawtags(self.current_rawtags).map(|g| g.tags());
 
-        tag.write_to_path(self.path, id3::Version::Id3v24)?;
-        Ok(true)
-    }
-
-    pub fn remove_tags(&self, tags: &Vec<String>) -> anyhow::Result<bool> {
-        match self.filetype.as_str() {
-            "flac" => self.remove_flac_tags(tags),
-            "mp3" => self.remove_mp3_tags(tags),
-            _ => Err(anyhow!("Unsupported filetype")),
-        }
-    }
-
-    fn remove_flac_tags(&self, tags: &Vec<String>) -> anyhow::Result<bool> {
-        let mut tagger = metaflac::Tag::read_from_path(self.path)?;
-        let mut ret = false;
-
-        for tag_name in tags {
-            let values: Vec<String> = tagger
-                .get_vorbis(tag_name)
-                .map(|t| t.map(|v| v.to_owned()).collect())
-                .unwrap_or_default();
-
-            for v in values {
-                tagger.remove_vorbis_pair(tag_name, &v);
-                ret = true;
-            }
-        }
-
-        if ret {
-            tagger.save()?;
+        if current.as_ref() == Some(&gain.tags()) {
+            return Ok(false);
         }
-        Ok(ret)
-    }
 
-    fn remove_mp3_tags(&self, tags: &Vec<String>) -> anyhow::Result<bool> {
-        let mut tag = id3::Tag::read_from_path(self.path)?;
-        let mut ret = false;
-
-        for tag_name in tags {
-            if tag.get(tag_name).is_some() {
-                tag.remove(tag_name);
-                ret = true;
-            }
-
-            let tag_name_uc = tag_name.to_uppercase();
-
-            if tag.get(&tag_name_uc).is_some() {
-                tag.remove(&tag_name_uc);
-                ret = true;
+        if !silent {
+            for (name, value) in gain.tags() {
+                println!("{:>16} -> {}", name, value);
             }
         }
 
-        if ret {
-            tag.write_to_path(self.path, id3::Version::Id3v24)?;
-        }
-
-        Ok(ret)
+        self.format.set_loudness(self.path, gain)
     }
 
     pub fn remove_artwork(&self) -> anyhow::Result<bool> {
-        match self.filetype.as_str() {
-            "flac" => self.remove_flac_artwork(),
-            "mp3" => self.remove_mp3_artwork(),
-            _ => Err(anyhow!("Unsupported filetype")),
-        }
-    }
-
-    fn remove_flac_artwork(&self) -> anyhow::Result<bool> {
-        let mut tagger = metaflac::Tag::read_from_path(self.path)?;
-        tagger.remove_picture_type(PictureType::CoverFront);
-        tagger.remove_picture_type(PictureType::CoverBack);
-        tagger.remove_picture_type(PictureType::Media);
-        tagger.remove_picture_type(PictureType::Other);
-        tagger.remove_picture_type(PictureType::Icon);
-        tagger.remove_picture_type(PictureType::Media);
-        tagger.remove_picture_type(PictureType::Leaflet);
-        tagger.remove_picture_type(PictureType::Artist);
-        tagger.save()?;
-        Ok(true)
-    }
-
-    fn remove_mp3_artwork(&self) -> anyhow::Result<bool> {
-        let mut tag = id3::Tag::read_from_path(self.path)?;
-        tag.remove_all_pictures();
-        tag.write_to_path(self.path, id3::Version::Id3v24)?;
-        Ok(true)
+        self.format.remove_artwork(self.path)
     }
 
+    // A disc number of zero means the source doesn't have one, so there's nothing to copy.
     pub fn batch_tag(&self, src_tags: &AurTags, silent: bool) -> Result<bool, anyhow::Error> {
+        let disc_changed =
+            src_tags.disc > 0 && self.set_disc(&src_tags.disc.to_string(), silent)?;
+
         let changes = [
             self.set_artist(&src_tags.artist, silent)?,
             self.set_title(&src_tags.title, silent)?,
@@ -201,7 +116,7 @@ impl<'a> Tagger<'a> {
         ]
         .iter()
         .any(|&changed| changed);
-        Ok(changes)
+        Ok(changes || disc_changed)
     }
 }
 
@@ -286,4 +201,95 @@ mod test {
         let new_info = AurMetadata::new(&flac).unwrap();
         assert_eq!(2001, new_info.tags.year);
     }
+
+    #[test]
+    fn test_set_disc_mp3_and_flac() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &["test.mp3", "test.flac"])
+            .unwrap();
+
+        for file in ["test.mp3", "test.flac"] {
+            let path = tmp.path().join(file);
+            let original_info = AurMetadata::new(&path).unwrap();
+            assert_eq!(0, original_info.tags.disc);
+            let tagger = Tagger::new(&original_info).unwrap();
+            assert!(tagger.set_disc("2", false).unwrap());
+            let new_info = AurMetadata::new(&path).unwrap();
+            assert_eq!(2, new_info.tags.disc);
+            assert_eq!("2", new_info.get_tag("disc").unwrap());
+            assert!(
+                !Tagger::new(&new_info)
+                    .unwrap()
+                    .set_disc("2", false)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_title_and_t_num_opus() {
+        let file = "test.opus";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file]).unwrap();
+        let opus = tmp.path().join(file);
+        let original_info = AurMetadata::new(&opus).unwrap();
+        let tagger = Tagger::new(&original_info).unwrap();
+        assert!(!tagger.set_title("Test Title", false).unwrap());
+        assert!(tagger.set_title("New Title", false).unwrap());
+        let new_info = AurMetadata::new(&opus).unwrap();
+        assert_eq!("New Title".to_owned(), new_info.tags.title);
+        let tagger = Tagger::new(&new_info).unwrap();
+        assert!(tagger.set_t_num("7", false).unwrap());
+        let new_info = AurMetadata::new(&opus).unwrap();
+        assert_eq!(7, new_info.tags.t_num);
+        assert_eq!("Test Artist".to_owned(), new_info.tags.artist);
+    }
+
+    #[test]
+    fn test_remove_tags_opus() {
+        let file = "test.opus";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file]).unwrap();
+        let opus = tmp.path().join(file);
+        let original_info = AurMetadata::new(&opus).unwrap();
+        let tagger = Tagger::new(&original_info).unwrap();
+        assert!(tagger.remove_tags(&["genre".to_owned()]).unwrap());
+        assert!(!tagger.remove_tags(&["genre".to_owned()]).unwrap());
+        let new_info = AurMetadata::new(&opus).unwrap();
+        assert_eq!(5, new_info.rawtags.len());
+    }
+
+    #[test]
+    fn test_set_title_and_t_num_m4a() {
+        let file = "test.m4a";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file]).unwrap();
+        let m4a = tmp.path().join(file);
+        let original_info = AurMetadata::new(&m4a).unwrap();
+        let tagger = Tagger::new(&original_info).unwrap();
+        assert!(!tagger.set_title("Test Title", false).unwrap());
+        assert!(tagger.set_title("New Title", false).unwrap());
+        let new_info = AurMetadata::new(&m4a).unwrap();
+        assert_eq!("New Title".to_owned(), new_info.tags.title);
+        let tagger = Tagger::new(&new_info).unwrap();
+        assert!(tagger.set_t_num("7", false).unwrap());
+        let new_info = AurMetadata::new(&m4a).unwrap();
+        assert_eq!(7, new_info.tags.t_num);
+        assert_eq!("Test Artist".to_owned(), new_info.tags.artist);
+        assert_eq!("00:00:01", new_info.time().formatted);
+    }
+
+    #[test]
+    fn test_remove_tags_m4a() {
+        let file = "test.m4a";
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("info"), &[file]).unwrap();
+        let m4a = tmp.path().join(file);
+        let original_info = AurMetadata::new(&m4a).unwrap();
+        let tagger = Tagger::new(&original_info).unwrap();
+        assert!(tagger.remove_tags(&["\u{a9}gen".to_owned()]).unwrap());
+        assert!(!tagger.remove_tags(&["\u{a9}gen".to_owned()]).unwrap());
+        let new_info = AurMetadata::new(&m4a).unwrap();
+        assert_eq!(5, new_info.rawtags.len());
+    }
 }
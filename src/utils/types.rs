@@ -1,5 +1,6 @@
 use camino::Utf8PathBuf;
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashSet};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -8,6 +9,47 @@ pub enum RenumberDirection {
     Down,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FixMode {
+    /// Make every repair
+    Auto,
+    /// Ask before each repair
+    Ask,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// For people
+    Text,
+    /// One JSON array, printed at the end
+    Json,
+    /// One JSON object per line, printed as we go
+    Jsonl,
+}
+
+// How files from a multi-disc album say which disc they belong to. The suffix is how things have
+// always been done, so it's the default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscStyle {
+    /// " (Disc n)" on the end of the album tag
+    #[default]
+    AlbumSuffix,
+    /// A disc number tag, with the album tag the same on every disc
+    Tag,
+}
+
+// How much a lint rule matters. Anything not configured is an error, as it always was. Only errors
+// make lint and lintdir exit non-zero.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Off,
+    Warn,
+    #[default]
+    Error,
+}
+
 #[derive(Default)]
 pub struct CopytagsOptions {
     pub recurse: bool,
@@ -18,9 +60,10 @@ pub struct CopytagsOptions {
 pub struct TranscodeOptions {
     pub force: bool,
     pub remove_originals: bool,
+    pub profile: Option<String>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct GlobalOpts {
     pub config: Utf8PathBuf,
     pub noop: bool,
@@ -28,9 +71,18 @@ pub struct GlobalOpts {
     pub verbose: bool,
 }
 
+pub struct LintOpts {
+    pub recurse: bool,
+    pub fix: Option<FixMode>,
+    pub format: OutputFormat,
+    pub use_cache: bool,
+    pub authenticity: bool,
+    pub audio: bool,
+}
+
 #[derive(Default)]
 pub struct Mp3dirOpts {
-    pub preset: String,
+    pub profile: String,
     pub force: bool,
     pub recurse: bool,
     pub root: Utf8PathBuf,
@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod test {
+    use assert_cmd::cargo::cargo_bin_cmd;
+    use camino_tempfile_ext::prelude::*;
+    use predicates::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    #[ignore]
+    fn test_artdupes_command() {
+        let tmp = Utf8TempDir::new().unwrap();
+
+        for (fixture_dir, album) in [
+            ("tester.too_big", "albums/abc/band.album"),
+            ("tester.too_big", "albums/abc/band.reissue"),
+            ("tester.too_big", "eps/other_band.ep"),
+            ("tester.good_art", "albums/abc/band.other_album"),
+        ] {
+            tmp.child(album)
+                .copy_from(
+                    fixture!("commands/artfix").join(fixture_dir),
+                    &["cover.jpg"],
+                )
+                .unwrap();
+        }
+
+        let root = tmp.path();
+
+        cargo_bin_cmd!("aur")
+            .arg("--config")
+            .arg(fixture!("config/test.toml"))
+            .arg("artdupes")
+            .arg(root)
+            .assert()
+            .failure()
+            .code(2)
+            .stdout(format!(
+                "{root}/albums/abc/band.album/cover.jpg\n  {root}/eps/other_band.ep/cover.jpg\n\n"
+            ));
+
+        cargo_bin_cmd!("aur")
+            .arg("--config")
+            .arg(fixture!("config/test.toml"))
+            .arg("artdupes")
+            .arg(root.join("albums"))
+            .assert()
+            .success()
+            .stdout("");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_artdupes_missing_dir() {
+        cargo_bin_cmd!("aur")
+            .arg("artdupes")
+            .arg("/does/not/exist")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("/does/not/exist not found"));
+    }
+}
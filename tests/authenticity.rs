@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod test {
+    use assert_cmd::cargo::cargo_bin_cmd;
+    use snltest::fixture;
+
+    #[test]
+    #[ignore]
+    fn test_authenticity_command() {
+        cargo_bin_cmd!("aur")
+            .arg("authenticity")
+            .arg(fixture!("info/test.flac"))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(
+                "16-bit/44100Hz, cutoff: none, effective bit depth: 16",
+            ));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_authenticity_command_no_flacs() {
+        cargo_bin_cmd!("aur")
+            .arg("authenticity")
+            .arg(fixture!("info/test.mp3"))
+            .assert()
+            .failure();
+    }
+}
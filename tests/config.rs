@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod test {
+    use assert_cmd::cargo::cargo_bin_cmd;
+    use predicates::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    #[ignore]
+    fn test_config_test_ignore_command_match() {
+        cargo_bin_cmd!("aur")
+            .arg("--config")
+            .arg(fixture!("config/test.toml"))
+            .args(["config", "test-ignore", "audiobooks/writer"])
+            .assert()
+            .success()
+            .stdout("ignore.wantflac.top_level: audiobooks\n");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_config_test_ignore_command_unused() {
+        let dir = fixture!("commands/wantflac/mp3");
+
+        cargo_bin_cmd!("aur")
+            .arg("--config")
+            .arg(fixture!("config/test.toml"))
+            .args(["config", "test-ignore", "--unused"])
+            .arg(&dir)
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains(format!(
+                "ignore.artdupes: albums/abc/band.reissue matches nothing under {dir}\n"
+            )))
+            .stdout(predicate::str::contains("ignore.wantflac.top_level").not())
+            .stdout(predicate::str::contains("ignore.wantflac.tracks").not());
+    }
+
+    #[test]
+    #[ignore]
+    fn test_config_test_ignore_command_no_match() {
+        cargo_bin_cmd!("aur")
+            .arg("--config")
+            .arg(fixture!("config/test.toml"))
+            .args(["config", "test-ignore", "albums/abc/band.record"])
+            .assert()
+            .failure()
+            .stdout("albums/abc/band.record matches no ignore rule\n");
+    }
+}
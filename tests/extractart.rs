@@ -0,0 +1,48 @@
+#[cfg(test)]
+mod test {
+    use assert_cmd::cargo::cargo_bin_cmd;
+    use aur::utils::config::ARTWORK_FILENAME;
+    use camino_tempfile_ext::prelude::*;
+    use predicates::prelude::*;
+    use snltest::fixture;
+
+    #[test]
+    #[ignore]
+    fn test_extractart_command() {
+        let tmp = Utf8TempDir::new().unwrap();
+        tmp.copy_from(fixture!("commands/strip"), &["01.tester.not_stripped.flac"])
+            .unwrap();
+        let dir_under_test = tmp.path();
+
+        cargo_bin_cmd!("aur")
+            .arg("extractart")
+            .arg(dir_under_test)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "Extract: {dir_under_test}/01.tester.not_stripped.flac -> \
+                 {dir_under_test}/{ARTWORK_FILENAME}"
+            )));
+
+        assert!(dir_under_test.join(ARTWORK_FILENAME).exists());
+
+        cargo_bin_cmd!("aur")
+            .arg("extractart")
+            .arg(dir_under_test)
+            .assert()
+            .success()
+            .stdout("");
+    }
+
+    #[test]
+    #[ignore]
+    fn test_extractart_incorrect_usage() {
+        cargo_bin_cmd!("aur")
+            .arg("extractart")
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "the following required arguments were not provided",
+            ));
+    }
+}
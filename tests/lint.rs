@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod test {
     use assert_cmd::cargo::cargo_bin_cmd;
+    use camino_tempfile_ext::prelude::*;
     use predicates::prelude::*;
     use snltest::fixture;
 
@@ -9,6 +10,7 @@ mod test {
     fn test_lint_command_missing_tags() {
         cargo_bin_cmd!("aur")
             .arg("lint")
+            .arg("--no-cache")
             .arg(fixture!(
                 "commands/lint/00.tester.missing_genre_track_no_year.flac"
             ))
@@ -24,6 +26,7 @@ mod test {
     fn test_lint_command_fine() {
         cargo_bin_cmd!("aur")
             .arg("lint")
+            .arg("--no-cache")
             .arg(fixture!("commands/lint/01.tester.lints_fine.flac"))
             .arg(fixture!("commands/lint/02.tester.lints_fine.mp3"))
             .assert()
@@ -36,6 +39,7 @@ mod test {
     fn test_lint_command_respects_config() {
         cargo_bin_cmd!("aur")
             .arg("lint")
+            .arg("--no-cache")
             .arg(fixture!("commands/lint/09.tester.bad_title_allowed.mp3"))
             .assert()
             .failure()
@@ -45,6 +49,7 @@ mod test {
 
         cargo_bin_cmd!("aur")
             .arg("lint")
+            .arg("--no-cache")
             .arg("--config")
             .arg(fixture!("config/test.toml"))
             .arg(fixture!("commands/lint/09.tester.bad_title_allowed.mp3"))
@@ -53,6 +58,67 @@ mod test {
             .stdout("");
     }
 
+    #[test]
+    #[ignore]
+    fn test_lint_command_audio() {
+        cargo_bin_cmd!("aur")
+            .arg("lint")
+            .arg("--audio")
+            .arg("--no-cache")
+            .arg(fixture!("commands/lint/01.tester.lints_fine.flac"))
+            .assert()
+            .failure()
+            .stdout(predicate::str::contains("DC offset of 1.43%"))
+            .stdout(predicate::str::contains("Track is only 0s long"));
+    }
+
+    #[test]
+    #[ignore]
+    fn test_lint_command_json_verbose() {
+        let output = cargo_bin_cmd!("aur")
+            .args(["lint", "--format", "jsonl", "--verbose", "--no-cache"])
+            .arg(fixture!("commands/lint/03.tester.has_bom_leader.flac"))
+            .assert()
+            .failure()
+            .get_output()
+            .stdout
+            .clone();
+
+        for line in String::from_utf8(output).unwrap().lines() {
+            assert!(
+                serde_json::from_str::<serde_json::Value>(line).is_ok(),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_lint_command_cache() {
+        let tmp = Utf8TempDir::new().unwrap();
+        let file = fixture!("commands/lint/01.tester.lints_fine.flac");
+
+        let lint = || {
+            let mut cmd = cargo_bin_cmd!("aur");
+            cmd.env("XDG_CACHE_HOME", tmp.path())
+                .args(["lint", "--verbose"])
+                .arg(&file);
+            cmd
+        };
+
+        lint()
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("unchanged").not());
+
+        lint().assert().success().stdout(predicate::str::contains(
+            "is unchanged since it was last linted",
+        ));
+
+        assert!(tmp.path().join("aur/lint.json").exists());
+    }
+
     #[test]
     #[ignore]
     fn test_lint_incorrect_usage() {
@@ -9,6 +9,7 @@ mod test {
     fn test_lintdir_command_okay() {
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(fixture!("commands/lintdir/flac/tester.perfect"))
             .arg(fixture!("commands/lintdir/mp3/tester.perfect"))
             .arg(fixture!("commands/lintdir/mp3/artist--band.split_single"))
@@ -27,6 +28,7 @@ mod test {
 
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(&test_dir)
             .assert()
             .failure()
@@ -40,6 +42,7 @@ mod test {
 
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(test_dir)
             .assert()
             .failure()
@@ -53,6 +56,7 @@ mod test {
 
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(test_dir)
             .assert()
             .failure()
@@ -65,6 +69,7 @@ mod test {
         let test_dir = &fixture!("commands/lintdir/mp3/tester.unwanted_art");
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(test_dir)
             .assert()
             .failure()
@@ -81,6 +86,7 @@ mod test {
 
         cargo_bin_cmd!("aur")
             .arg("lintdir")
+            .arg("--no-cache")
             .arg(test_dir)
             .assert()
             .failure()
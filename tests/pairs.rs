@@ -0,0 +1,14 @@
+#[cfg(test)]
+mod test {
+    use assert_cmd::cargo::cargo_bin_cmd;
+
+    #[test]
+    #[ignore]
+    fn test_pairs_command_invalid_tree() {
+        cargo_bin_cmd!("aur")
+            .args(["pairs", "--root", "/tmp"])
+            .assert()
+            .failure()
+            .stderr("ERROR: did not find /tmp/mp3\n");
+    }
+}
@@ -16,11 +16,15 @@ mod test {
             .unwrap();
         let dir_under_test = tmp.path().canonicalize().unwrap().join("syncflac");
 
+        let state_file = tmp.path().join("sync.json");
+
         cargo_bin_cmd!("aur")
             .arg("syncflac")
             .arg("--verbose")
             .arg("-R")
             .arg(&dir_under_test)
+            .arg("--state-file")
+            .arg(&state_file)
             .assert()
             .success()
             .stdout(predicate::str::contains("Creating target"))
@@ -108,9 +112,13 @@ mod test {
             .arg("syncflac")
             .arg("-R")
             .arg(&dir_under_test)
+            .arg("--state-file")
+            .arg(&state_file)
             .assert()
             .success()
             .stdout("");
+
+        assert!(state_file.exists());
     }
 
     #[test]
@@ -40,6 +40,19 @@ mod test {
             ));
     }
 
+    #[test]
+    #[ignore]
+    fn test_transcode_command_lame_profile() {
+        cargo_bin_cmd!("aur")
+            .args(["transcode", "mp3", "--profile", "extreme"])
+            .arg(fixture!("commands/transcode/01.tester.lossless.wav"))
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(
+                "profile 'extreme' uses LAME: transcode needs an ffmpeg profile",
+            ));
+    }
+
     #[test]
     #[ignore]
     fn test_transcode_incorrect_usage() {